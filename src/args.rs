@@ -0,0 +1,57 @@
+/// Command line options. The web build has no command line and always uses the defaults.
+#[derive(Clone, Debug, Default)]
+pub struct Args {
+    /// Render this many frames without the UI, print timings and exit.
+    pub bench: Option<u32>,
+    /// `.ron` scene file to load instead of the default scene.
+    pub scene: Option<std::path::PathBuf>,
+    /// Use the first GPU whose name contains this.
+    pub gpu: Option<String>,
+    /// `.ron` camera path to render frame by frame instead of showing the UI.
+    pub render_sequence: Option<std::path::PathBuf>,
+    /// Number of frames spread evenly over the camera path.
+    pub frames: Option<u32>,
+    /// Directory receiving `frame_0000.png` and so on.
+    pub out: Option<std::path::PathBuf>,
+}
+
+impl Args {
+    pub fn parse() -> Self {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    pub fn parse_from(args: impl IntoIterator<Item = String>) -> Self {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--bench" => match args.next().map(|v| v.parse::<u32>()) {
+                    Some(Ok(frames)) if frames > 0 => parsed.bench = Some(frames),
+                    _ => log::warn!("--bench expects a number of frames"),
+                },
+                "--scene" => match args.next() {
+                    Some(path) => parsed.scene = Some(path.into()),
+                    None => log::warn!("--scene expects a path"),
+                },
+                "--gpu" => match args.next() {
+                    Some(name) => parsed.gpu = Some(name),
+                    None => log::warn!("--gpu expects a part of an adapter name"),
+                },
+                "--render-sequence" => match args.next() {
+                    Some(path) => parsed.render_sequence = Some(path.into()),
+                    None => log::warn!("--render-sequence expects a camera path file"),
+                },
+                "--frames" => match args.next().map(|v| v.parse::<u32>()) {
+                    Some(Ok(frames)) if frames > 0 => parsed.frames = Some(frames),
+                    _ => log::warn!("--frames expects a number of frames"),
+                },
+                "--out" => match args.next() {
+                    Some(dir) => parsed.out = Some(dir.into()),
+                    None => log::warn!("--out expects a directory"),
+                },
+                other => log::warn!("Ignoring unknown argument {}", other),
+            }
+        }
+        parsed
+    }
+}
@@ -1,5 +1,66 @@
+//! A wgpu path tracer. [`run`] opens a window with the full viewer, or the renderer can be
+//! driven from an application's own winit event loop:
+//!
+//! ```no_run
+//! use pathracer_wgpu::{CornellOptions, FrameData, RenderConfig, RenderContext, RenderParam, Scene};
+//! use winit::{
+//!     application::ApplicationHandler,
+//!     event::WindowEvent,
+//!     event_loop::{ActiveEventLoop, EventLoop},
+//!     window::{Window, WindowId},
+//! };
+//!
+//! struct App<'a> {
+//!     window: &'a Window,
+//!     render_context: RenderContext<'a>,
+//!     mouse_pressed: bool,
+//!     last_time: std::time::Instant,
+//! }
+//!
+//! impl ApplicationHandler for App<'_> {
+//!     fn resumed(&mut self, _event_loop: &ActiveEventLoop) {}
+//!
+//!     fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+//!         // Camera controls and the egui panels.
+//!         self.render_context.window_event(&event, &mut self.mouse_pressed);
+//!         match event {
+//!             WindowEvent::CloseRequested => event_loop.exit(),
+//!             WindowEvent::Resized(size) => self.render_context.resize(size),
+//!             WindowEvent::RedrawRequested => {
+//!                 let now = std::time::Instant::now();
+//!                 if let Err(e) = self.render_context.redraw(now - self.last_time) {
+//!                     eprintln!("{e}");
+//!                 }
+//!                 self.last_time = now;
+//!                 self.window.request_redraw();
+//!             }
+//!             _ => {}
+//!         }
+//!     }
+//! }
+//!
+//! let event_loop = EventLoop::new().unwrap();
+//! # #[allow(deprecated)]
+//! let window = event_loop.create_window(Window::default_attributes()).unwrap();
+//! let size = window.inner_size();
+//! let frame_data = FrameData {
+//!     width: size.width,
+//!     height: size.height,
+//!     index: 0,
+//! };
+//! let scene = Scene::cornell_scene(CornellOptions::default(), RenderParam::default(), frame_data);
+//! let render_context =
+//!     pollster::block_on(RenderContext::new(&window, &scene, RenderConfig::default())).unwrap();
+//! let mut app = App {
+//!     window: &window,
+//!     render_context,
+//!     mouse_pressed: false,
+//!     last_time: std::time::Instant::now(),
+//! };
+//! event_loop.run_app(&mut app).unwrap();
+//! ```
+
 use log::info;
-use scene::Scene;
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
@@ -11,15 +72,27 @@ use winit::{
     window::{Window, WindowAttributes, WindowId},
 };
 
+mod args;
+use args::Args;
+
 mod render_context;
-use render_context::RenderContext;
+pub use render_context::{
+    BufferTooLarge, FrameCallback, RenderConfig, RenderContext, RenderInitError,
+};
 
 mod utils;
 
 mod scene;
+pub use scene::{
+    AccumulationFormat, Background, BokehShape, Camera, CameraPath, CameraProjection,
+    CornellOptions, DebugView, FrameData, Material, Medium, RenderParam, SamplerKind, Scene,
+    SceneBuilder, SceneDescriptor, SceneError, Settings, ShadingMode, Texture, TextureFilter,
+    AVAILABLE_SCENES,
+};
 extern crate nalgebra_glm as glm;
 
 mod object;
+pub use object::{Mesh, PointLight};
 struct MyUserEvent;
 
 struct State<'a> {
@@ -29,6 +102,87 @@ struct State<'a> {
     mouse_pressed: bool,
     surface_configured: bool,
     counter: i32,
+    bench: Option<Bench>,
+    #[cfg(not(target_arch = "wasm32"))]
+    sequence: Option<Sequence>,
+}
+
+/// Progress of a `--bench` run.
+struct Bench {
+    frames: u32,
+    rendered: u32,
+    start: Option<instant::Instant>,
+}
+
+impl Bench {
+    fn new(frames: u32) -> Self {
+        Self {
+            frames,
+            rendered: 0,
+            start: None,
+        }
+    }
+
+    fn report(&self, render_context: &RenderContext) {
+        let elapsed = self.start.map(|s| s.elapsed()).unwrap_or_default();
+        let seconds = elapsed.as_secs_f64();
+        let samples = self.rendered as f64
+            * render_context.samples_per_frame() as f64
+            * render_context.size.width as f64
+            * render_context.size.height as f64;
+
+        println!(
+            "Rendered {} frames at {}x{} in {:.3} s",
+            self.rendered, render_context.size.width, render_context.size.height, seconds
+        );
+        println!(
+            "Average: {:.3} ms/frame",
+            1000.0 * seconds / self.rendered as f64
+        );
+        println!("Throughput: {:.3} Msamples/s", samples / seconds / 1e6);
+    }
+}
+
+/// Progress of a `--render-sequence` run.
+#[cfg(not(target_arch = "wasm32"))]
+struct Sequence {
+    path: scene::CameraPath,
+    frames: u32,
+    out: std::path::PathBuf,
+    saved: u32,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Sequence {
+    /// Camera of frame `index`, the frames are spread evenly from the first keyframe to the
+    /// last one.
+    fn camera(&self, index: u32) -> Option<scene::Camera> {
+        let start = self.path.keyframes.first()?.0;
+        let t = match self.frames {
+            0 | 1 => 0.0,
+            frames => index as f32 / (frames - 1) as f32,
+        };
+        self.path.sample(start + t * (self.path.duration() - start))
+    }
+
+    /// Saves the finished frame, returns false once every frame is on disk or saving failed.
+    fn save_frame(&mut self, render_context: &mut RenderContext) -> bool {
+        let file = self.out.join(format!("frame_{:04}.png", self.saved));
+        if let Err(e) = render_context.save_png(&file, false) {
+            eprintln!("Failed to save {}: {e}", file.display());
+            return false;
+        }
+        self.saved += 1;
+        println!("Frame {}/{}: {}", self.saved, self.frames, file.display());
+
+        match self.camera(self.saved) {
+            Some(camera) if self.saved < self.frames => {
+                render_context.set_camera(camera);
+                true
+            }
+            _ => false,
+        }
+    }
 }
 
 impl ApplicationHandler<MyUserEvent> for State<'_> {
@@ -65,17 +219,38 @@ impl ApplicationHandler<MyUserEvent> for State<'_> {
                     log::info!("Surface not configured yet");
                     return;
                 }
+                if self.render_context.is_device_lost() {
+                    #[cfg(not(target_arch = "wasm32"))]
+                    eprintln!("The GPU device was lost, exiting");
+                    event_loop.exit();
+                    return;
+                }
                 let now = instant::Instant::now();
                 let dt = now - self.last_time;
                 self.last_time = now;
 
-                self.render_context.fps = 1.0 / dt.as_secs_f64();
-
-                self.render_context.update(dt);
-                match self.render_context.render() {
-                    Ok(_) => {}
-                    Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
-                        self.render_context.resize(self.render_context.size)
+                if let Some(bench) = self.bench.as_mut() {
+                    bench.start.get_or_insert(now);
+                }
+                match self.render_context.redraw(dt) {
+                    Ok(_) => {
+                        if let Some(bench) = self.bench.as_mut() {
+                            bench.rendered += 1;
+                            if bench.rendered >= bench.frames {
+                                // Timings only make sense once the GPU is done with every frame.
+                                self.render_context.wait_idle();
+                                bench.report(&self.render_context);
+                                event_loop.exit();
+                            }
+                        }
+                        #[cfg(not(target_arch = "wasm32"))]
+                        if let Some(sequence) = self.sequence.as_mut() {
+                            if self.render_context.is_accumulation_complete()
+                                && !sequence.save_frame(&mut self.render_context)
+                            {
+                                event_loop.exit();
+                            }
+                        }
                     }
                     Err(wgpu::SurfaceError::OutOfMemory) => {
                         log::error!("Out of memory");
@@ -85,6 +260,8 @@ impl ApplicationHandler<MyUserEvent> for State<'_> {
                     Err(wgpu::SurfaceError::Timeout) => {
                         log::warn!("Surface timeout")
                     }
+                    // Already reconfigured by `redraw`.
+                    Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {}
                 }
             }
             WindowEvent::Resized(physical_size) => {
@@ -163,7 +340,7 @@ fn init(
         window.set_outer_position(winit::dpi::PhysicalPosition::new(x, y));
     }
 
-    return (window, event_loop);
+    (window, event_loop)
 }
 
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen(start))]
@@ -201,33 +378,121 @@ pub async fn run() {
     };
 
     let (window, event_loop) = init(width, height);
+    let args = Args::parse();
+
+    // The saved settings and the command line override the defaults of the scene.
+    let mut render_param = match &args.scene {
+        Some(_) => scene::RenderParam::default(),
+        None => scene::RenderParam::default_for_scene(AVAILABLE_SCENES[0].name),
+    };
+    #[cfg(not(target_arch = "wasm32"))]
+    let sequence = match &args.render_sequence {
+        Some(path) => {
+            let camera_path = match scene::CameraPath::load(path) {
+                Ok(camera_path) if !camera_path.keyframes.is_empty() => camera_path,
+                Ok(_) => {
+                    eprintln!("{} has no keyframes", path.display());
+                    return;
+                }
+                Err(e) => {
+                    eprintln!("Can't load the camera path: {e}");
+                    return;
+                }
+            };
+            let Some(frames) = args.frames else {
+                eprintln!("--render-sequence needs --frames");
+                return;
+            };
+            let out = args.out.clone().unwrap_or_else(|| ".".into());
+            if let Err(e) = std::fs::create_dir_all(&out) {
+                eprintln!("Can't create {}: {e}", out.display());
+                return;
+            }
+            Some(Sequence {
+                path: camera_path,
+                frames,
+                out,
+                saved: 0,
+            })
+        }
+        None => None,
+    };
+
+    let mut render_config = RenderConfig {
+        adapter_name: args.gpu.clone(),
+        ..Default::default()
+    };
+    if args.bench.is_some() || args.render_sequence.is_some() {
+        // The saved camera and params would change what is measured or rendered.
+        render_config.settings_file = None;
+    }
+    if args.bench.is_some() {
+        // Never stop accumulating and don't wait for vsync, every frame has to do the full work.
+        render_param.samples_max_per_pixel = u32::MAX;
+        render_config.present_mode = wgpu::PresentMode::Immediate;
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if sequence.is_some() {
+        render_config.present_mode = wgpu::PresentMode::Immediate;
+    }
+
+    let frame_data = scene::FrameData {
+        width,
+        height,
+        index: 0,
+    };
+    // TODO: not sync with current_scene_index
+    let default_scene = || {
+        Scene::cornell_scene(
+            scene::CornellOptions::WITHOUT_SUZANNE,
+            render_param,
+            frame_data,
+        )
+    };
+    #[cfg(not(target_arch = "wasm32"))]
+    let scene = match &args.scene {
+        Some(path) => Scene::load(path, render_param, frame_data).unwrap_or_else(|e| {
+            log::error!("Can't load the scene, using the default one: {e}");
+            default_scene()
+        }),
+        None => default_scene(),
+    };
+    #[cfg(target_arch = "wasm32")]
+    let scene = default_scene();
+
+    let render_context = match RenderContext::new(&window, &scene, render_config).await {
+        Ok(render_context) => render_context,
+        Err(e) => {
+            log::error!("Can't initialize the renderer: {e}");
+            #[cfg(not(target_arch = "wasm32"))]
+            eprintln!("Can't initialize the renderer: {e}");
+            return;
+        }
+    };
 
     let mut state = State {
         window: &window,
         mouse_pressed: false,
         surface_configured: true,
         last_time: instant::Instant::now(),
-        render_context: RenderContext::new(
-            &window,
-            // TODO: not sync with current_scene_index
-            &Scene::cornell_scene_without_suzanne(
-                scene::RenderParam {
-                    samples_per_pixel: 1,
-                    max_depth: 15,
-                    samples_max_per_pixel: 200,
-                    total_samples: 0,
-                    clear_samples: 0,
-                },
-                scene::FrameData {
-                    width,
-                    height,
-                    index: 0,
-                },
-            ),
-        )
-        .await,
+        render_context,
         counter: 0,
+        bench: args.bench.map(Bench::new),
+        #[cfg(not(target_arch = "wasm32"))]
+        sequence,
     };
+    state.render_context.show_ui = state.bench.is_none();
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(sequence) = &state.sequence {
+        state.render_context.show_ui = false;
+        if let Some(camera) = sequence.camera(0) {
+            state.render_context.set_camera(camera);
+        }
+    }
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    if let Some(path) = &args.scene {
+        state.render_context.watch_scene_file(path);
+    }
 
     let _ = event_loop.run_app(&mut state);
 }
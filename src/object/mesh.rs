@@ -1,6 +1,9 @@
 use glm::Vec3;
 
-use crate::utils::bvh::{Aabb, Bounded};
+use crate::utils::{
+    bvh::{Aabb, Bounded},
+    ray::{Intersect, Ray},
+};
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable, PartialEq)]
@@ -8,18 +11,28 @@ use crate::utils::bvh::{Aabb, Bounded};
 pub struct Mesh {
     pub vertices: [glm::Vec4; 3],
     pub normals: [glm::Vec4; 3],
+    /// Direction of increasing u in xyz, w is the sign of the bitangent
+    /// (`cross(normal, tangent) * w`). Zero when the mesh has no uvs.
+    pub tangents: [glm::Vec4; 3],
+    pub uvs: [glm::Vec2; 3],
     pub material_idx: u32,
-    pub _padding: [u32; 3],
+    pub _padding: u32,
 }
 
+// Keep the layout in sync with `struct Surface` in raytracing.wgsl.
+const _: () = assert!(std::mem::size_of::<Mesh>() == 176);
+const _: () = assert!(std::mem::offset_of!(Mesh, uvs) == 144);
+const _: () = assert!(std::mem::offset_of!(Mesh, material_idx) == 168);
+
 impl Mesh {
-    #[allow(dead_code)]
     pub fn empty() -> Self {
         Self {
             vertices: [glm::vec4(0.0, 0.0, 0.0, 0.0); 3],
             normals: [glm::vec4(0.0, 0.0, 0.0, 0.0); 3],
+            tangents: [glm::vec4(0.0, 0.0, 0.0, 0.0); 3],
+            uvs: [glm::vec2(0.0, 0.0); 3],
             material_idx: 0,
-            _padding: [0; 3],
+            _padding: 0,
         }
     }
 
@@ -37,8 +50,10 @@ impl Mesh {
                     glm::vec4(0.0, 0.0, 0.0, 1.0),
                     glm::vec4(0.0, 0.0, 0.0, 1.0),
                 ],
+                tangents: [glm::vec4(0.0, 0.0, 0.0, 0.0); 3],
+                uvs: [glm::vec2(0.0, 0.0); 3],
                 material_idx: 0,
-                _padding: [0; 3],
+                _padding: 0,
             },
             Mesh {
                 vertices: [
@@ -51,8 +66,10 @@ impl Mesh {
                     glm::vec4(0.0, 0.0, 0.0, 1.0),
                     glm::vec4(0.0, 0.0, 0.0, 1.0),
                 ],
+                tangents: [glm::vec4(0.0, 0.0, 0.0, 0.0); 3],
+                uvs: [glm::vec2(0.0, 0.0); 3],
                 material_idx: 0,
-                _padding: [0; 3],
+                _padding: 0,
             },
         ]
     }
@@ -70,13 +87,19 @@ impl Mesh {
                     glm::vec4(0.0, 0.0, 0.5, 1.0),
                     glm::vec4(0.0, 0.0, 0.5, 1.0),
                 ],
+                tangents: [glm::vec4(1.0, 0.0, 0.0, 1.0); 3],
+                uvs: [
+                    glm::vec2(0.0, 0.0),
+                    glm::vec2(1.0, 0.0),
+                    glm::vec2(0.0, 1.0),
+                ],
                 material_idx: 0,
-                _padding: [0; 3],
+                _padding: 0,
             },
             Mesh {
                 vertices: [
-                    glm::vec4(1.0, 1.0, 0.0, 1.0),
                     glm::vec4(1.0, -1.0, 0.0, 1.0),
+                    glm::vec4(1.0, 1.0, 0.0, 1.0),
                     glm::vec4(-1.0, 1.0, 0.0, 1.0),
                 ],
                 normals: [
@@ -84,91 +107,152 @@ impl Mesh {
                     glm::vec4(0.0, 0.0, 0.5, 1.0),
                     glm::vec4(0.0, 0.0, 0.5, 1.0),
                 ],
+                tangents: [glm::vec4(1.0, 0.0, 0.0, 1.0); 3],
+                uvs: [
+                    glm::vec2(1.0, 0.0),
+                    glm::vec2(1.0, 1.0),
+                    glm::vec2(0.0, 1.0),
+                ],
                 material_idx: 0,
-                _padding: [0; 3],
+                _padding: 0,
             },
         ]
     }
 
     pub fn cube() -> Vec<Mesh> {
+        let faces = [
+            // Front
+            (0.0, glm::vec3(0.0, 1.0, 0.0), glm::vec3(0.0, 0.0, 1.0)),
+            // Back
+            (180.0, glm::vec3(0.0, 1.0, 0.0), glm::vec3(0.0, 0.0, -1.0)),
+            // Top
+            (-90.0, glm::vec3(1.0, 0.0, 0.0), glm::vec3(0.0, 1.0, 0.0)),
+            // Bottom
+            (90.0, glm::vec3(1.0, 0.0, 0.0), glm::vec3(0.0, -1.0, 0.0)),
+            // Right
+            (90.0, glm::vec3(0.0, 1.0, 0.0), glm::vec3(1.0, 0.0, 0.0)),
+            // Left
+            (-90.0, glm::vec3(0.0, 1.0, 0.0), glm::vec3(-1.0, 0.0, 0.0)),
+        ];
+
         let mut meshes = vec![];
-        // Front
-        let mut front = Mesh::quad();
-        translate(&mut front, glm::vec3(0.0, 0.0, 1.0));
-        for v in front.iter_mut() {
-            v.normals = [
-                glm::vec4(0.0, 0.0, 1.0, 1.0),
-                glm::vec4(0.0, 0.0, 1.0, 1.0),
-                glm::vec4(0.0, 0.0, 1.0, 1.0),
-            ]
-        }
-        meshes.append(&mut front);
-
-        // Back
-        let mut back = Mesh::quad();
-        rotate(&mut back, 180.0, glm::vec3(0.0, 1.0, 0.0));
-        translate(&mut back, glm::vec3(0.0, 0.0, -1.0));
-        for v in back.iter_mut() {
-            v.normals = [
-                glm::vec4(0.0, 0.0, -1.0, 1.0),
-                glm::vec4(0.0, 0.0, -1.0, 1.0),
-                glm::vec4(0.0, 0.0, -1.0, 1.0),
-            ]
-        }
-        meshes.append(&mut back);
-
-        // Top
-        let mut top = Mesh::quad();
-        rotate(&mut top, 90.0, glm::vec3(1.0, 0.0, 0.0));
-        translate(&mut top, glm::vec3(0.0, 1.0, 0.0));
-        for v in top.iter_mut() {
-            v.normals = [
-                glm::vec4(0.0, 1.0, 0.0, 1.0),
-                glm::vec4(0.0, 1.0, 0.0, 1.0),
-                glm::vec4(0.0, 1.0, 0.0, 1.0),
-            ]
-        }
-        meshes.append(&mut top);
-
-        // Bottom
-        let mut bottom = Mesh::quad();
-        rotate(&mut bottom, -90.0, glm::vec3(1.0, 0.0, 0.0));
-        translate(&mut bottom, glm::vec3(0.0, -1.0, 0.0));
-        for v in bottom.iter_mut() {
-            v.normals = [
-                glm::vec4(0.0, -1.0, 0.0, 1.0),
-                glm::vec4(0.0, -1.0, 0.0, 1.0),
-                glm::vec4(0.0, -1.0, 0.0, 1.0),
-            ]
-        }
-        meshes.append(&mut bottom);
-
-        // Right
-        let mut right = Mesh::quad();
-        rotate(&mut right, 90.0, glm::vec3(0.0, 1.0, 0.0));
-        translate(&mut right, glm::vec3(1.0, 0.0, 0.0));
-        for v in right.iter_mut() {
-            v.normals = [
-                glm::vec4(1.0, 0.0, 0.0, 1.0),
-                glm::vec4(1.0, 0.0, 0.0, 1.0),
-                glm::vec4(1.0, 0.0, 0.0, 1.0),
-            ]
-        }
-        meshes.append(&mut right);
-
-        // Left
-        let mut left = Mesh::quad();
-        rotate(&mut left, -90.0, glm::vec3(0.0, 1.0, 0.0));
-        translate(&mut left, glm::vec3(-1.0, 0.0, 0.0));
-        for v in left.iter_mut() {
-            v.normals = [
-                glm::vec4(-1.0, 0.0, 0.0, 1.0),
-                glm::vec4(-1.0, 0.0, 0.0, 1.0),
-                glm::vec4(-1.0, 0.0, 0.0, 1.0),
-            ]
-        }
-        meshes.append(&mut left);
+        for (angle, axis, center) in faces {
+            let mut face = Mesh::quad();
+            rotate(&mut face, angle, axis);
+            translate(&mut face, center);
+            meshes.append(&mut face);
+        }
+        // Every face is wound counter-clockwise seen from outside.
+        Mesh::recompute_normals(&mut meshes);
+        meshes
+    }
+
+    fn triangle(points: [(Vec3, Vec3, glm::Vec2); 3]) -> Mesh {
+        Mesh {
+            vertices: points.map(|(p, _, _)| glm::vec4(p.x, p.y, p.z, 1.0)),
+            normals: points.map(|(_, n, _)| glm::vec4(n.x, n.y, n.z, 0.0)),
+            tangents: [glm::vec4(0.0, 0.0, 0.0, 0.0); 3],
+            uvs: points.map(|(_, _, uv)| uv),
+            material_idx: 0,
+            _padding: 0,
+        }
+    }
 
+    /// Triangulates the parametric surface `f(u, v) -> (position, normal)` over `[0, 1]²`,
+    /// dropping the triangles that collapse to a point like at the poles of a sphere. The
+    /// surface faces the side of `∂f/∂u × ∂f/∂v`.
+    fn grid(segments_u: u32, segments_v: u32, f: impl Fn(f32, f32) -> (Vec3, Vec3)) -> Vec<Mesh> {
+        let point = |i: u32, j: u32| {
+            let uv = glm::vec2(i as f32 / segments_u as f32, j as f32 / segments_v as f32);
+            let (p, n) = f(uv.x, uv.y);
+            (p, n, uv)
+        };
+        let mut meshes = Vec::with_capacity(2 * (segments_u * segments_v) as usize);
+        for (i, j) in (0..segments_u).flat_map(|i| (0..segments_v).map(move |j| (i, j))) {
+            let (p00, p10, p11, p01) = (
+                point(i, j),
+                point(i + 1, j),
+                point(i + 1, j + 1),
+                point(i, j + 1),
+            );
+            for triangle in [[p00, p10, p11], [p00, p11, p01]] {
+                let e1 = triangle[1].0 - triangle[0].0;
+                let e2 = triangle[2].0 - triangle[0].0;
+                if e1.cross(&e2).norm_squared() > 1e-12 {
+                    meshes.push(Mesh::triangle(triangle));
+                }
+            }
+        }
+        meshes
+    }
+
+    /// The `[-1, 1]²` square of the xy plane facing +z like [`Mesh::quad`], split in
+    /// `2 * segments_x * segments_y` triangles.
+    pub fn plane(segments_x: u32, segments_y: u32) -> Vec<Mesh> {
+        let mut meshes = Mesh::grid(segments_x.max(1), segments_y.max(1), |u, v| {
+            (
+                glm::vec3(2.0 * u - 1.0, 2.0 * v - 1.0, 0.0),
+                glm::vec3(0.0, 0.0, 1.0),
+            )
+        });
+        Mesh::compute_tangents(&mut meshes);
+        meshes
+    }
+
+    /// Unit sphere around the origin with smooth normals, made of `2 * sectors * (rings - 1)`
+    /// triangles. u goes around the y axis and v from the bottom pole to the top one.
+    pub fn uv_sphere(rings: u32, sectors: u32) -> Vec<Mesh> {
+        let mut meshes = Mesh::grid(sectors.max(3), rings.max(2), |u, v| {
+            let (phi, theta) = (u * std::f32::consts::TAU, v * std::f32::consts::PI);
+            let n = glm::vec3(
+                theta.sin() * phi.cos(),
+                -theta.cos(),
+                -theta.sin() * phi.sin(),
+            );
+            (n, n)
+        });
+        Mesh::compute_tangents(&mut meshes);
+        meshes
+    }
+
+    /// Capped cylinder along the y axis, centered on the origin, made of `4 * segments`
+    /// triangles. The side has smooth normals, the caps are flat.
+    pub fn cylinder(segments: u32, height: f32, radius: f32) -> Vec<Mesh> {
+        let segments = segments.max(3);
+        let half = 0.5 * height;
+        let around = |u: f32| {
+            let phi = u * std::f32::consts::TAU;
+            glm::vec3(phi.cos(), 0.0, -phi.sin())
+        };
+
+        let mut meshes = Mesh::grid(segments, 1, |u, v| {
+            let n = around(u);
+            (n * radius + glm::vec3(0.0, (v - 0.5) * height, 0.0), n)
+        });
+
+        for (y, normal) in [(half, 1.0), (-half, -1.0)] {
+            let center = (
+                glm::vec3(0.0, y, 0.0),
+                glm::vec3(0.0, normal, 0.0),
+                glm::vec2(0.5, 0.5),
+            );
+            let rim = |i: u32| {
+                let d = around(i as f32 / segments as f32);
+                (
+                    d * radius + glm::vec3(0.0, y, 0.0),
+                    glm::vec3(0.0, normal, 0.0),
+                    glm::vec2(0.5 + 0.5 * d.x, 0.5 - 0.5 * d.z),
+                )
+            };
+            for i in 0..segments {
+                // Counter-clockwise seen from outside of the cap.
+                meshes.push(match normal > 0.0 {
+                    true => Mesh::triangle([center, rim(i), rim(i + 1)]),
+                    false => Mesh::triangle([center, rim(i + 1), rim(i)]),
+                });
+            }
+        }
+        Mesh::compute_tangents(&mut meshes);
         meshes
     }
 
@@ -186,25 +270,187 @@ impl Mesh {
             .map(|c| glm::vec4(c[0], c[1], c[2], 0.0))
             .collect::<Vec<_>>();
 
-        let indices = mesh.indices.chunks(3).map(|c| Mesh {
-            vertices: [
-                vertices[c[0] as usize],
-                vertices[c[1] as usize],
-                vertices[c[2] as usize],
-            ],
-            normals: [
-                normals[c[0] as usize],
-                normals[c[1] as usize],
-                normals[c[2] as usize],
-            ],
-            material_idx: 0,
-            _padding: [0; 3],
-        });
-        indices.collect()
+        let uvs = mesh
+            .texcoords
+            .chunks(2)
+            .map(|c| glm::vec2(c[0], c[1]))
+            .collect::<Vec<_>>();
+        // Positions and texture coordinates can have their own indices.
+        let uv_indices = match mesh.texcoord_indices.is_empty() {
+            true => &mesh.indices,
+            false => &mesh.texcoord_indices,
+        };
+
+        let mut meshes = mesh
+            .indices
+            .chunks(3)
+            .zip(uv_indices.chunks(3))
+            .map(|(c, t)| Mesh {
+                vertices: [
+                    vertices[c[0] as usize],
+                    vertices[c[1] as usize],
+                    vertices[c[2] as usize],
+                ],
+                normals: [
+                    normals[c[0] as usize],
+                    normals[c[1] as usize],
+                    normals[c[2] as usize],
+                ],
+                tangents: [glm::vec4(0.0, 0.0, 0.0, 0.0); 3],
+                uvs: match uvs.is_empty() {
+                    true => [glm::vec2(0.0, 0.0); 3],
+                    false => [uvs[t[0] as usize], uvs[t[1] as usize], uvs[t[2] as usize]],
+                },
+                material_idx: 0,
+                _padding: 0,
+            })
+            .collect::<Vec<_>>();
+
+        if !uvs.is_empty() {
+            Mesh::compute_tangents(&mut meshes);
+        }
+        meshes
+    }
+
+    /// Concatenates meshes built separately so they can be added as a single object.
+    pub fn merge(parts: Vec<Vec<Mesh>>) -> Vec<Mesh> {
+        parts.into_iter().flatten().collect()
+    }
+
+    /// Replaces the normals by the face normal of each triangle, facing the side its vertices
+    /// are counter-clockwise from.
+    pub fn recompute_normals(meshes: &mut [Mesh]) {
+        for mesh in meshes.iter_mut() {
+            let e1 = mesh.vertices[1].xyz() - mesh.vertices[0].xyz();
+            let e2 = mesh.vertices[2].xyz() - mesh.vertices[0].xyz();
+            let n = e1.cross(&e2).try_normalize(1e-12).unwrap_or_default();
+            mesh.normals = [glm::vec4(n.x, n.y, n.z, 0.0); 3];
+        }
+    }
+
+    /// Snaps vertices closer than `epsilon` together and gives each welded vertex the average
+    /// of its normals, which smooths the shading across the triangles sharing it. Triangles
+    /// that collapse are dropped.
+    pub fn weld(mut meshes: Vec<Mesh>, epsilon: f32) -> Vec<Mesh> {
+        let epsilon = epsilon.max(f32::EPSILON);
+        let cell = |p: Vec3| (p / epsilon).map(|c| c.floor()).map(|c| c as i64);
+
+        // Welded vertices are bucketed by cell, a vertex can be welded to one in the next cell.
+        let mut grid = std::collections::HashMap::<_, Vec<usize>>::new();
+        let mut welded: Vec<(glm::Vec4, Vec3)> = Vec::new();
+        let mut corners = Vec::with_capacity(3 * meshes.len());
+        for mesh in &meshes {
+            for (k, (vertex, normal)) in mesh.vertices.iter().zip(mesh.normals.iter()).enumerate() {
+                let position = vertex.xyz();
+                let home = cell(position);
+                let neighbour = (-1..=1)
+                    .flat_map(|x| (-1..=1).flat_map(move |y| (-1..=1).map(move |z| (x, y, z))))
+                    .filter_map(|(x, y, z)| grid.get(&(home + glm::vec3(x, y, z))))
+                    .flatten()
+                    .copied()
+                    .find(|&i| glm::distance(&welded[i].0.xyz(), &position) <= epsilon);
+                let index = neighbour.unwrap_or_else(|| {
+                    welded.push((*vertex, Vec3::zeros()));
+                    grid.entry(home).or_default().push(welded.len() - 1);
+                    welded.len() - 1
+                });
+                // Weighted by the angle of the corner, so the split of faces in triangles
+                // doesn't matter.
+                let to = |i: usize| {
+                    (mesh.vertices[(k + i) % 3].xyz() - position)
+                        .try_normalize(1e-12)
+                        .unwrap_or_default()
+                };
+                let angle = to(1).dot(&to(2)).clamp(-1.0, 1.0).acos();
+                welded[index].1 += angle * normal.xyz().try_normalize(1e-12).unwrap_or_default();
+                corners.push(index);
+            }
+        }
+
+        for (mesh, corners) in meshes.iter_mut().zip(corners.chunks(3)) {
+            for (i, &index) in corners.iter().enumerate() {
+                let (vertex, normal) = welded[index];
+                mesh.vertices[i] = vertex;
+                // Opposite normals cancel out, keep the original one then.
+                if let Some(n) = normal.try_normalize(1e-6) {
+                    mesh.normals[i] = glm::vec4(n.x, n.y, n.z, 0.0);
+                }
+            }
+        }
+        let mut meshes = meshes
+            .into_iter()
+            .zip(corners.chunks(3))
+            .filter(|(_, c)| c[0] != c[1] && c[1] != c[2] && c[0] != c[2])
+            .map(|(mesh, _)| mesh)
+            .collect::<Vec<_>>();
+        Mesh::compute_tangents(&mut meshes);
+        meshes
+    }
+
+    /// Turns every triangle inside out: negates the normals and reverses the winding, so that
+    /// [`Mesh::recompute_normals`] agrees with the result.
+    #[allow(dead_code)]
+    pub fn flip_normals(meshes: &mut [Mesh]) {
+        for mesh in meshes.iter_mut() {
+            mesh.vertices.swap(1, 2);
+            mesh.normals.swap(1, 2);
+            mesh.tangents.swap(1, 2);
+            mesh.uvs.swap(1, 2);
+            for normal in mesh.normals.iter_mut() {
+                normal.x = -normal.x;
+                normal.y = -normal.y;
+                normal.z = -normal.z;
+            }
+            // The bitangent is built from the normal, keep it pointing along v.
+            for tangent in mesh.tangents.iter_mut() {
+                tangent.w = -tangent.w;
+            }
+        }
+    }
+
+    /// Fills `tangents` from the uv gradients of each triangle, orthogonalized against every
+    /// vertex normal. Triangles with degenerate uvs get no tangents and are not normal mapped.
+    pub fn compute_tangents(meshes: &mut [Mesh]) {
+        for mesh in meshes.iter_mut() {
+            let e1 = mesh.vertices[1].xyz() - mesh.vertices[0].xyz();
+            let e2 = mesh.vertices[2].xyz() - mesh.vertices[0].xyz();
+            let d1 = mesh.uvs[1] - mesh.uvs[0];
+            let d2 = mesh.uvs[2] - mesh.uvs[0];
+
+            let det = d1.x * d2.y - d2.x * d1.y;
+            if det.abs() < 1e-8 {
+                mesh.tangents = [glm::vec4(0.0, 0.0, 0.0, 0.0); 3];
+                continue;
+            }
+            let tangent = (e1 * d2.y - e2 * d1.y) / det;
+            let bitangent = (e2 * d1.x - e1 * d2.x) / det;
+
+            for (i, normal) in mesh.normals.iter().enumerate() {
+                let n = normal.xyz().try_normalize(1e-8).unwrap_or_default();
+                let Some(t) = (tangent - n * n.dot(&tangent)).try_normalize(1e-8) else {
+                    mesh.tangents[i] = glm::vec4(0.0, 0.0, 0.0, 0.0);
+                    continue;
+                };
+                let w = match n.cross(&t).dot(&bitangent) < 0.0 {
+                    true => -1.0,
+                    false => 1.0,
+                };
+                mesh.tangents[i] = glm::vec4(t.x, t.y, t.z, w);
+            }
+        }
+    }
+
+    pub fn area(&self) -> f32 {
+        let v0: Vec3 = self.vertices[0].xyz();
+        let e1 = self.vertices[1].xyz() - v0;
+        let e2 = self.vertices[2].xyz() - v0;
+        0.5 * e1.cross(&e2).norm()
     }
 }
 
 impl Bounded for Mesh {
+    // Only xyz, w is not part of the position: 0 for vertices loaded from an OBJ and 1 for the
+    // generated ones.
     fn aabb(&self) -> Aabb {
         let v0: Vec3 = self.vertices[0].xyz();
         let v1: Vec3 = self.vertices[1].xyz();
@@ -218,19 +464,81 @@ impl Bounded for Mesh {
     }
 }
 
-pub fn rotate(meshes: &mut Vec<Mesh>, angle: f32, axis: glm::Vec3) {
+impl Intersect for Mesh {
+    // Möller–Trumbore, same as `hit_triangle` in the shader.
+    fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<f32> {
+        let v0: Vec3 = self.vertices[0].xyz();
+        let e1 = self.vertices[1].xyz() - v0;
+        let e2 = self.vertices[2].xyz() - v0;
+        let h = ray.direction.cross(&e2);
+        let a = e1.dot(&h);
+        if a.abs() < 1e-4 {
+            return None;
+        }
+
+        let f = 1.0 / a;
+        let s = ray.origin - v0;
+        let u = f * s.dot(&h);
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(&e1);
+        let v = f * ray.direction.dot(&q);
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = f * e2.dot(&q);
+        (t > t_min && t < t_max).then_some(t)
+    }
+}
+
+/// Smallest box containing every triangle of `meshes`.
+pub fn bounding_box(meshes: &[Mesh]) -> Aabb {
+    let mut aabb = Aabb::empty();
+    meshes.iter().for_each(|m| aabb.grow_aabb(&m.aabb()));
+    aabb
+}
+
+/// Center of mass of the surface of `meshes`, each triangle weighted by its area. The center
+/// of the bounding box when they have no area.
+pub fn center_surface(meshes: &[Mesh]) -> glm::Vec3 {
+    let (weighted, total_area) =
+        meshes
+            .iter()
+            .fold((glm::Vec3::zeros(), 0.0), |(weighted, total_area), mesh| {
+                let centroid =
+                    (mesh.vertices[0].xyz() + mesh.vertices[1].xyz() + mesh.vertices[2].xyz())
+                        / 3.0;
+                (weighted + centroid * mesh.area(), total_area + mesh.area())
+            });
+    match total_area > 0.0 {
+        true => weighted / total_area,
+        false if meshes.is_empty() => glm::Vec3::zeros(),
+        false => bounding_box(meshes).center(),
+    }
+}
+
+/// Rotates by `angle` degrees around `axis` going through the origin, see [`rotate_about`].
+pub fn rotate(meshes: &mut [Mesh], angle: f32, axis: glm::Vec3) {
+    rotate_about(meshes, angle, axis, glm::Vec3::zeros());
+}
+
+/// Rotates by `angle` degrees around `axis` going through `pivot`.
+pub fn rotate_about(meshes: &mut [Mesh], angle: f32, axis: glm::Vec3, pivot: glm::Vec3) {
     // degree to radian
     let angle = angle.to_radians();
     let rotation = glm::quat_angle_axis(angle, &axis);
     for mesh in meshes.iter_mut() {
         for vertex in mesh.vertices.iter_mut() {
-            let position = glm::vec3(vertex.x, vertex.y, vertex.z);
-            let rotated = glm::quat_rotate_vec3(&rotation, &position);
+            let position = glm::vec3(vertex.x, vertex.y, vertex.z) - pivot;
+            let rotated = glm::quat_rotate_vec3(&rotation, &position) + pivot;
             vertex.x = rotated.x;
             vertex.y = rotated.y;
             vertex.z = rotated.z;
         }
-        for normal in mesh.normals.iter_mut() {
+        for normal in mesh.normals.iter_mut().chain(mesh.tangents.iter_mut()) {
             let position = glm::vec3(normal.x, normal.y, normal.z);
             let rotated = glm::quat_rotate_vec3(&rotation, &position);
             normal.x = rotated.x;
@@ -240,7 +548,15 @@ pub fn rotate(meshes: &mut Vec<Mesh>, angle: f32, axis: glm::Vec3) {
     }
 }
 
-pub fn translate(meshes: &mut Vec<Mesh>, translation: glm::Vec3) {
+/// Rotates in place, around `axis` going through [`center_surface`], so that the order with
+/// [`translate`] does not matter.
+#[allow(dead_code)]
+pub fn origin_center(meshes: &mut [Mesh], angle: f32, axis: glm::Vec3) {
+    let pivot = center_surface(meshes);
+    rotate_about(meshes, angle, axis, pivot);
+}
+
+pub fn translate(meshes: &mut [Mesh], translation: glm::Vec3) {
     for mesh in meshes.iter_mut() {
         for vertex in mesh.vertices.iter_mut() {
             vertex.x += translation.x;
@@ -250,7 +566,7 @@ pub fn translate(meshes: &mut Vec<Mesh>, translation: glm::Vec3) {
     }
 }
 
-pub fn scale(meshes: &mut Vec<Mesh>, scale: glm::Vec3) {
+pub fn scale(meshes: &mut [Mesh], scale: glm::Vec3) {
     for mesh in meshes.iter_mut() {
         for vertex in mesh.vertices.iter_mut() {
             vertex.x *= scale.x;
@@ -262,5 +578,220 @@ pub fn scale(meshes: &mut Vec<Mesh>, scale: glm::Vec3) {
             normal.y *= scale.y;
             normal.z *= scale.z;
         }
+        // Tangents follow the surface, unlike normals, they are normalized in the shader.
+        for tangent in mesh.tangents.iter_mut() {
+            tangent.x *= scale.x;
+            tangent.y *= scale.y;
+            tangent.z *= scale.z;
+        }
+    }
+}
+
+/// Applies `m` to the vertices as points, and to the normals through its inverse transpose so
+/// that they stay perpendicular under non-uniform scaling, unlike [`scale`].
+pub fn transform(meshes: &mut [Mesh], m: glm::Mat4) {
+    let linear = glm::mat4_to_mat3(&m);
+    let normal_matrix = linear.try_inverse().unwrap_or(linear).transpose();
+    for mesh in meshes.iter_mut() {
+        for vertex in mesh.vertices.iter_mut() {
+            let transformed = m * glm::vec4(vertex.x, vertex.y, vertex.z, 1.0);
+            vertex.x = transformed.x;
+            vertex.y = transformed.y;
+            vertex.z = transformed.z;
+        }
+        for normal in mesh.normals.iter_mut() {
+            let transformed = normal_matrix * normal.xyz();
+            normal.x = transformed.x;
+            normal.y = transformed.y;
+            normal.z = transformed.z;
+        }
+        for tangent in mesh.tangents.iter_mut() {
+            let transformed = linear * tangent.xyz();
+            tangent.x = transformed.x;
+            tangent.y = transformed.y;
+            tangent.z = transformed.z;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn centroid(mesh: &Mesh) -> Vec3 {
+        (mesh.vertices[0].xyz() + mesh.vertices[1].xyz() + mesh.vertices[2].xyz()) / 3.0
+    }
+
+    #[test]
+    fn recomputed_cube_normals_point_outward() {
+        let mut cube = Mesh::cube();
+        Mesh::recompute_normals(&mut cube);
+
+        assert_eq!(cube.len(), 12);
+        for mesh in &cube {
+            for normal in mesh.normals {
+                // Faces are one unit away from the center of the cube.
+                assert!((normal.xyz().norm() - 1.0).abs() < 1e-5);
+                assert!(normal.xyz().dot(&centroid(mesh)) > 0.99, "{mesh:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn flipped_normals_are_negated() {
+        let cube = Mesh::cube();
+        let mut flipped = cube.clone();
+        Mesh::flip_normals(&mut flipped);
+
+        for (mesh, flipped) in cube.iter().zip(&flipped) {
+            for normal in flipped.normals {
+                assert!(glm::distance(&normal.xyz(), &-mesh.normals[0].xyz()) < 1e-5);
+            }
+        }
+        // The winding is reversed too.
+        let mut recomputed = flipped.clone();
+        Mesh::recompute_normals(&mut recomputed);
+        for (flipped, recomputed) in flipped.iter().zip(&recomputed) {
+            assert!(glm::distance(&flipped.normals[0].xyz(), &recomputed.normals[0].xyz()) < 1e-5);
+        }
+    }
+
+    #[test]
+    fn transform_matches_helpers() {
+        let (angle, axis) = (30.0_f32, glm::vec3(0.0, 1.0, 0.0));
+        let (translation, scaling) = (glm::vec3(1.0, -2.0, 3.0), glm::vec3(0.5, 2.0, 0.3));
+
+        let mut helpers = Mesh::cube();
+        scale(&mut helpers, scaling);
+        rotate(&mut helpers, angle, axis);
+        translate(&mut helpers, translation);
+
+        let mut transformed = Mesh::cube();
+        transform(
+            &mut transformed,
+            glm::translation(&translation)
+                * glm::rotation(angle.to_radians(), &axis)
+                * glm::scaling(&scaling),
+        );
+
+        for (helpers, transformed) in helpers.iter().zip(&transformed) {
+            for (a, b) in helpers.vertices.iter().zip(&transformed.vertices) {
+                assert!(glm::distance(&a.xyz(), &b.xyz()) < 1e-5);
+            }
+            // `scale` only gets the side right under non-uniform scaling, `transform` keeps the
+            // normals perpendicular to the faces.
+            let e1 = transformed.vertices[1].xyz() - transformed.vertices[0].xyz();
+            let e2 = transformed.vertices[2].xyz() - transformed.vertices[0].xyz();
+            for (a, b) in helpers.normals.iter().zip(&transformed.normals) {
+                assert!(a.xyz().dot(&b.xyz()) > 0.0);
+                assert!(b.xyz().normalize().dot(&e1).abs() < 1e-5);
+                assert!(b.xyz().normalize().dot(&e2).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn plane_triangle_count() {
+        for (segments_x, segments_y) in [(1, 1), (3, 2), (8, 5)] {
+            let plane = Mesh::plane(segments_x, segments_y);
+            assert_eq!(plane.len() as u32, 2 * segments_x * segments_y);
+        }
+    }
+
+    #[test]
+    fn uv_sphere_triangle_count() {
+        // The triangles touching a pole collapse to a segment and are dropped.
+        for (rings, sectors) in [(2, 3), (8, 16), (5, 7)] {
+            let sphere = Mesh::uv_sphere(rings, sectors);
+            assert_eq!(sphere.len() as u32, 2 * sectors * (rings - 1));
+            assert!(sphere.iter().all(|m| m.area() > 0.0));
+        }
+    }
+
+    #[test]
+    fn cylinder_triangle_count() {
+        for segments in [3, 4, 32] {
+            let cylinder = Mesh::cylinder(segments, 2.0, 0.5);
+            assert_eq!(cylinder.len() as u32, 4 * segments);
+        }
+    }
+
+    #[test]
+    fn unit_triangle_aabb_is_tight() {
+        let triangle = Mesh {
+            // The w padding must not leak into the box.
+            vertices: [
+                glm::vec4(0.0, 0.0, 0.0, 5.0),
+                glm::vec4(1.0, 0.0, 0.0, -5.0),
+                glm::vec4(0.0, 1.0, 0.0, 1.0),
+            ],
+            ..Mesh::empty()
+        };
+        let aabb = triangle.aabb();
+
+        assert_eq!(aabb.min, glm::vec3(0.0, 0.0, 0.0));
+        assert_eq!(aabb.max, glm::vec3(1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn welded_cube_averages_corner_normals() {
+        let welded = Mesh::weld(Mesh::cube(), 1e-4);
+
+        assert_eq!(welded.len(), 12);
+        let mut corners = welded.iter().flat_map(|m| m.vertices).collect::<Vec<_>>();
+        corners.sort_by(|a, b| a.as_slice().partial_cmp(b.as_slice()).unwrap());
+        corners.dedup();
+        assert_eq!(corners.len(), 8);
+        for mesh in &welded {
+            for (vertex, normal) in mesh.vertices.iter().zip(&mesh.normals) {
+                // Three faces meet at each corner, all at right angles.
+                let expected = vertex.xyz().normalize();
+                assert!(glm::distance(&normal.xyz(), &expected) < 1e-5, "{mesh:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn rotation_about_the_center_keeps_it_fixed() {
+        let (angle, axis) = (40.0, glm::normalize(&glm::vec3(1.0, 2.0, 0.5)));
+        let center = glm::vec3(2.0, -1.0, 0.5);
+        let mut pivoted = Mesh::cube();
+        translate(&mut pivoted, center);
+        origin_center(&mut pivoted, angle, axis);
+
+        assert!(glm::distance(&center_surface(&pivoted), &center) < 1e-5);
+
+        // Same as rotating before moving the cube away from the origin.
+        let mut helpers = Mesh::cube();
+        rotate(&mut helpers, angle, axis);
+        translate(&mut helpers, center);
+        for (a, b) in helpers.iter().zip(&pivoted) {
+            for i in 0..3 {
+                assert!(glm::distance(&a.vertices[i].xyz(), &b.vertices[i].xyz()) < 1e-5);
+                assert!(glm::distance(&a.normals[i].xyz(), &b.normals[i].xyz()) < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn quad_area_and_center() {
+        let mut quad = Mesh::quad();
+        assert_eq!(quad.iter().map(Mesh::area).sum::<f32>(), 4.0);
+        assert_eq!(center_surface(&quad), glm::vec3(0.0, 0.0, 0.0));
+
+        translate(&mut quad, glm::vec3(1.0, 2.0, 3.0));
+        assert_eq!(center_surface(&quad), glm::vec3(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn center_surface_is_area_weighted() {
+        // A quad 4 times as large as the other one, 3 units to its right.
+        let mut large = Mesh::quad();
+        let mut small = Mesh::quad();
+        scale(&mut small, glm::vec3(0.5, 0.5, 1.0));
+        translate(&mut small, glm::vec3(3.0, 0.0, 0.0));
+        large.extend(small);
+
+        assert!(glm::distance(&center_surface(&large), &glm::vec3(0.6, 0.0, 0.0)) < 1e-5);
     }
 }
@@ -4,6 +4,13 @@ pub use sphere::Sphere;
 mod mesh;
 pub use mesh::*;
 
+#[cfg(not(target_arch = "wasm32"))]
+mod ply;
+#[cfg(not(target_arch = "wasm32"))]
+mod stl;
+
+use crate::utils::bvh::{Aabb, Bounded};
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable, PartialEq)]
 pub struct Object {
@@ -37,17 +44,6 @@ impl ObjectList {
         }
     }
 
-    pub fn new_empty_mesh() -> Self {
-        ObjectList {
-            objects: Vec::new(),
-            counter: 0,
-            offset_counter: 0,
-            offset_counter_spheres: 0,
-            meshes: vec![Mesh::empty()],
-            object_hashmap: std::collections::HashMap::new(),
-        }
-    }
-
     pub fn add(&mut self, obj: Object, meshes: Option<Vec<Mesh>>) {
         match obj.obj_type.into() {
             ObjectType::Sphere => self.offset_counter_spheres += obj.count,
@@ -55,11 +51,8 @@ impl ObjectList {
         }
         self.objects.push(obj);
         self.counter += 1;
-        if let Some(mut mesh) = meshes {
-            mesh.iter_mut().for_each(|m| {
-                m.material_idx = obj.id;
-            });
-            mesh.iter().for_each(|m| self.meshes.push(*m));
+        if let Some(mesh) = meshes {
+            self.meshes.extend(mesh);
 
             self.object_hashmap.insert(
                 obj.id,
@@ -80,7 +73,10 @@ impl ObjectList {
         );
     }
 
-    pub fn add_mesh(&mut self, count: Option<usize>, meshes: Vec<Mesh>) {
+    pub fn add_mesh(&mut self, count: Option<usize>, mut meshes: Vec<Mesh>, material_idx: u32) {
+        meshes
+            .iter_mut()
+            .for_each(|m| m.material_idx = material_idx);
         self.add(
             Object::new(
                 self.counter,
@@ -92,6 +88,80 @@ impl ObjectList {
         );
     }
 
+    /// Removes the object `id` and its meshes. Ids are indices into `objects`, so the objects
+    /// after it are renumbered and the offsets of those of the same type shifted back.
+    pub fn remove(&mut self, id: u32) -> Option<Object> {
+        let index = self.objects.iter().position(|o| o.id == id)?;
+        let removed = self.objects.remove(index);
+
+        match ObjectType::from(removed.obj_type) {
+            ObjectType::Sphere => self.offset_counter_spheres -= removed.count,
+            ObjectType::Mesh => {
+                let start = removed.offset as usize;
+                self.meshes.drain(start..start + removed.count as usize);
+                self.offset_counter -= removed.count;
+            }
+        }
+
+        for object in self.objects.iter_mut() {
+            if object.id > id {
+                object.id -= 1;
+            }
+            if object.obj_type == removed.obj_type && object.offset > removed.offset {
+                object.offset -= removed.count;
+            }
+        }
+        self.counter -= 1;
+
+        self.object_hashmap = self
+            .objects
+            .iter()
+            .filter(|o| ObjectType::from(o.obj_type) == ObjectType::Mesh)
+            .map(|o| (o.id, (o.offset, o.offset + o.count)))
+            .collect();
+
+        Some(removed)
+    }
+
+    /// Bounding box of every primitive of `object`, `spheres` being the scene spheres its
+    /// offset indexes into when it is one.
+    pub fn object_aabb(&self, object: &Object, spheres: &[Sphere]) -> Aabb {
+        let range = object.offset as usize..(object.offset + object.count) as usize;
+        match ObjectType::from(object.obj_type) {
+            ObjectType::Sphere => {
+                let mut aabb = Aabb::empty();
+                spheres[range]
+                    .iter()
+                    .for_each(|s| aabb.grow_aabb(&s.aabb()));
+                aabb
+            }
+            ObjectType::Mesh => bounding_box(&self.meshes[range]),
+        }
+    }
+
+    /// Surface area of every primitive of `object`, `spheres` being the scene spheres its
+    /// offset indexes into when it is one.
+    pub fn object_area(&self, object: &Object, spheres: &[Sphere]) -> f32 {
+        let range = object.offset as usize..(object.offset + object.count) as usize;
+        match ObjectType::from(object.obj_type) {
+            ObjectType::Sphere => spheres[range]
+                .iter()
+                .map(|s| 4.0 * std::f32::consts::PI * s.radius * s.radius)
+                .sum(),
+            ObjectType::Mesh => self.meshes[range].iter().map(Mesh::area).sum(),
+        }
+    }
+
+    /// Union of the bounds of every object. Meshes not owned by an object, like the
+    /// placeholder of sphere-only scenes, are left out.
+    pub fn scene_aabb(&self, spheres: &[Sphere]) -> Aabb {
+        let mut aabb = Aabb::empty();
+        for object in &self.objects {
+            aabb.grow_aabb(&self.object_aabb(object, spheres));
+        }
+        aabb
+    }
+
     pub fn ui(&self, ui: &mut egui::Ui) {
         ui.heading("Objects");
         ui.separator();
@@ -134,18 +204,85 @@ impl From<u32> for ObjectType {
     }
 }
 
+/// [`Light::light_type`] of a [`PointLight`], the others are an [`ObjectType`].
+pub const LIGHT_TYPE_POINT: u32 = 2;
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable, PartialEq)]
 pub struct Light {
     pub id: u32,
     pub light_type: u32,
+    /// Surface area of the object, the triangles of a mesh light are sampled in proportion
+    /// to their share of it.
+    pub area: f32,
+    /// Point lights only, see [`PointLight`].
+    pub intensity: f32,
+    pub position: glm::Vec4,
+    pub color: glm::Vec4,
 }
 
+// Keep the layout in sync with `struct Light` in raytracing.wgsl.
+const _: () = assert!(std::mem::size_of::<Light>() == 48);
+const _: () = assert!(std::mem::offset_of!(Light, position) == 16);
+
 impl Light {
-    pub fn new(id: u32, light_type: ObjectType) -> Self {
+    pub fn new(id: u32, light_type: ObjectType, area: f32) -> Self {
         Light {
             id,
             light_type: light_type as u32,
+            area,
+            intensity: 0.0,
+            position: glm::Vec4::zeros(),
+            color: glm::Vec4::zeros(),
+        }
+    }
+}
+
+/// Light leaving a single point, `color * intensity / distance^2` reaches the surfaces around
+/// it. Rays can't hit it, it only lights the diffuse bounces.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointLight {
+    pub position: glm::Vec3,
+    pub color: glm::Vec3,
+    pub intensity: f32,
+}
+
+impl PointLight {
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Position:");
+            for coordinate in self.position.iter_mut() {
+                changed |= ui
+                    .add(egui::DragValue::new(coordinate).speed(0.01))
+                    .changed();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Color:");
+            let mut color = self.color.into();
+            if ui.color_edit_button_rgb(&mut color).changed() {
+                self.color = color.into();
+                changed = true;
+            }
+        });
+        ui.label("Intensity:");
+        changed |= ui
+            .add(egui::Slider::new(&mut self.intensity, 0.0..=100.0).logarithmic(true))
+            .changed();
+        changed
+    }
+}
+
+impl From<&PointLight> for Light {
+    fn from(light: &PointLight) -> Self {
+        Light {
+            id: 0,
+            light_type: LIGHT_TYPE_POINT,
+            area: 0.0,
+            intensity: light.intensity,
+            position: glm::vec4(light.position.x, light.position.y, light.position.z, 1.0),
+            color: glm::vec4(light.color.x, light.color.y, light.color.z, 0.0),
         }
     }
 }
@@ -0,0 +1,145 @@
+use std::path::Path;
+
+use ply_rs::{
+    parser::Parser,
+    ply::{DefaultElement, Property},
+};
+
+use crate::scene::SceneError;
+
+use super::Mesh;
+
+/// Any scalar property as a float, lists are not scalars.
+fn scalar(property: &Property) -> Option<f32> {
+    Some(match *property {
+        Property::Char(v) => v as f32,
+        Property::UChar(v) => v as f32,
+        Property::Short(v) => v as f32,
+        Property::UShort(v) => v as f32,
+        Property::Int(v) => v as f32,
+        Property::UInt(v) => v as f32,
+        Property::Float(v) => v,
+        Property::Double(v) => v as f32,
+        _ => return None,
+    })
+}
+
+/// Any integer list property as indices.
+fn indices(property: &Property) -> Option<Vec<usize>> {
+    Some(match property {
+        Property::ListChar(v) => v.iter().map(|&i| i as usize).collect(),
+        Property::ListUChar(v) => v.iter().map(|&i| i as usize).collect(),
+        Property::ListShort(v) => v.iter().map(|&i| i as usize).collect(),
+        Property::ListUShort(v) => v.iter().map(|&i| i as usize).collect(),
+        Property::ListInt(v) => v.iter().map(|&i| i as usize).collect(),
+        Property::ListUInt(v) => v.iter().map(|&i| i as usize).collect(),
+        _ => return None,
+    })
+}
+
+/// `element[names]` as a vector, if every one of them is a scalar.
+fn vec3(element: &DefaultElement, [x, y, z]: [&str; 3]) -> Option<glm::Vec4> {
+    let get = |name| element.get(name).and_then(scalar);
+    Some(glm::vec4(get(x)?, get(y)?, get(z)?, 0.0))
+}
+
+impl Mesh {
+    /// Reads the triangles of an ASCII or binary PLY file, polygons are split in fans. Vertex
+    /// normals are used when the file has them, the face normals otherwise.
+    pub fn from_ply(path: &Path) -> Result<Vec<Mesh>, SceneError> {
+        let parse_error = |message: String| SceneError::Parse {
+            path: path.to_owned(),
+            message,
+        };
+        let mut file = std::fs::File::open(path).map_err(|e| SceneError::Io {
+            path: path.to_owned(),
+            message: e.to_string(),
+        })?;
+        let ply = Parser::<DefaultElement>::new()
+            .read_ply(&mut file)
+            .map_err(|e| parse_error(e.to_string()))?;
+
+        let no_elements = Vec::new();
+        let vertices = ply.payload.get("vertex").unwrap_or(&no_elements);
+        let positions = vertices
+            .iter()
+            .map(|v| vec3(v, ["x", "y", "z"]))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| parse_error("vertex without x, y and z".to_string()))?;
+        let normals = vertices
+            .iter()
+            .map(|v| vec3(v, ["nx", "ny", "nz"]))
+            .collect::<Option<Vec<_>>>();
+
+        let mut meshes = Vec::new();
+        for face in ply.payload.get("face").unwrap_or(&no_elements) {
+            let face = ["vertex_indices", "vertex_index"]
+                .iter()
+                .find_map(|name| face.get(*name).and_then(indices))
+                .ok_or_else(|| parse_error("face without vertex indices".to_string()))?;
+            if let Some(&i) = face.iter().find(|&&i| i >= positions.len()) {
+                return Err(parse_error(format!(
+                    "face uses vertex {i} but there are only {} vertices",
+                    positions.len()
+                )));
+            }
+            for j in 1..face.len().saturating_sub(1) {
+                let corners = [face[0], face[j], face[j + 1]];
+                meshes.push(Mesh {
+                    vertices: corners.map(|i| positions[i]),
+                    normals: match &normals {
+                        Some(normals) => corners.map(|i| normals[i]),
+                        None => [glm::vec4(0.0, 0.0, 0.0, 0.0); 3],
+                    },
+                    ..Mesh::empty()
+                });
+            }
+        }
+
+        if normals.is_none() {
+            Mesh::recompute_normals(&mut meshes);
+        }
+        Ok(meshes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_tetrahedron() {
+        let ply = "\
+ply
+format ascii 1.0
+element vertex 4
+property float x
+property float y
+property float z
+element face 4
+property list uchar int vertex_indices
+end_header
+1 1 1
+1 -1 -1
+-1 1 -1
+-1 -1 1
+3 0 1 2
+3 0 3 1
+3 0 2 3
+3 1 3 2
+";
+        let path = std::env::temp_dir().join(format!("tetrahedron-{}.ply", std::process::id()));
+        std::fs::write(&path, ply).unwrap();
+        let meshes = Mesh::from_ply(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let meshes = meshes.unwrap();
+        assert_eq!(meshes.len(), 4);
+        for mesh in &meshes {
+            let centroid = mesh.vertices.iter().map(|v| v.xyz()).sum::<glm::Vec3>() / 3.0;
+            for normal in mesh.normals {
+                assert!(normal.xyz().dot(&centroid) > 0.0, "{mesh:?}");
+            }
+        }
+    }
+}
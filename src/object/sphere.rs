@@ -1,6 +1,9 @@
 use glm::Vec3;
 
-use crate::utils::bvh::{Aabb, Bounded};
+use crate::utils::{
+    bvh::{Aabb, Bounded},
+    ray::{Intersect, Ray},
+};
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable, PartialEq)]
@@ -9,6 +12,10 @@ pub struct Sphere {
     pub radius: f32,        // 16 byte offset
     pub material_idx: u32,  // 20 byte offset
     pub _padding: [u32; 2], // 24 byte offset, 8 bytes size
+    /// Distance the center travels while the shutter is open, see [`Camera::shutter`].
+    ///
+    /// [`Camera::shutter`]: crate::scene::Camera::shutter
+    pub velocity: glm::Vec4, // 32 byte offset
 }
 
 impl Sphere {
@@ -19,26 +26,78 @@ impl Sphere {
             radius: 0.0,
             material_idx: 0,
             _padding: [0; 2],
+            velocity: glm::Vec4::zeros(),
         }
     }
 
-    #[allow(dead_code)]
+    /// `material_idx` indexes `Scene::materials` and is read as-is by the shader.
     pub fn new(center: glm::Vec3, radius: f32, material_idx: u32) -> Self {
         Self {
             center: glm::vec3_to_vec4(&center),
             radius,
             material_idx,
             _padding: [0; 2],
+            velocity: glm::Vec4::zeros(),
         }
     }
+
+    pub fn with_velocity(mut self, velocity: glm::Vec3) -> Self {
+        self.velocity = glm::vec3_to_vec4(&velocity);
+        self
+    }
 }
 
 impl Bounded for Sphere {
+    /// Covers the whole sweep of a moving sphere, from where the shutter opens to where it
+    /// closes at the latest.
     fn aabb(&self) -> Aabb {
-        let center: Vec3 = self.center.xyz();
-        let radius = self.radius;
-        let min = center - Vec3::repeat(radius);
-        let max = center + Vec3::repeat(radius);
-        Aabb::new(min, max)
+        let start: Vec3 = self.center.xyz();
+        let end = start + self.velocity.xyz();
+        let radius = Vec3::repeat(self.radius);
+        Aabb::new(
+            glm::min2(&start, &end) - radius,
+            glm::max2(&start, &end) + radius,
+        )
+    }
+}
+
+// Moving spheres are picked where the shutter opens.
+impl Intersect for Sphere {
+    fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<f32> {
+        let oc = ray.origin - self.center.xyz();
+        let a = ray.direction.dot(&ray.direction);
+        let b = ray.direction.dot(&oc);
+        let c = oc.dot(&oc) - self.radius * self.radius;
+        let discriminant = b * b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        let sqrtd = discriminant.sqrt();
+        [(-b - sqrtd) / a, (-b + sqrtd) / a]
+            .into_iter()
+            .find(|t| (t_min..=t_max).contains(t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The shader reads `material_idx` straight from the storage buffer, the offsets are the ones
+    // of `struct Sphere` in raytracing.wgsl.
+    #[test]
+    fn material_idx_survives_gpu_layout() {
+        let sphere =
+            Sphere::new(glm::vec3(1.0, 2.0, 3.0), 0.5, 7).with_velocity(glm::vec3(0.0, 1.0, 0.0));
+        let bytes = bytemuck::bytes_of(&sphere);
+
+        assert_eq!(bytes.len(), 48);
+        assert_eq!(bytemuck::pod_read_unaligned::<f32>(&bytes[16..20]), 0.5);
+        assert_eq!(bytemuck::pod_read_unaligned::<u32>(&bytes[20..24]), 7);
+        assert_eq!(
+            bytemuck::pod_read_unaligned::<[f32; 4]>(&bytes[32..48]),
+            [0.0, 1.0, 0.0, 0.0]
+        );
     }
 }
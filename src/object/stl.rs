@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use crate::scene::SceneError;
+
+use super::Mesh;
+
+impl Mesh {
+    /// Reads the triangles of an ASCII or binary STL file, shaded flat with their facet
+    /// normals. Facets written without a normal get the one of their winding.
+    pub fn from_stl(path: &Path) -> Result<Vec<Mesh>, SceneError> {
+        let parse_error = |e: std::io::Error| SceneError::Parse {
+            path: path.to_owned(),
+            message: e.to_string(),
+        };
+        let file = std::fs::File::open(path).map_err(|e| SceneError::Io {
+            path: path.to_owned(),
+            message: e.to_string(),
+        })?;
+        let mut reader = std::io::BufReader::new(file);
+
+        let mut meshes = Vec::new();
+        for triangle in stl_io::create_stl_reader(&mut reader).map_err(parse_error)? {
+            let triangle = triangle.map_err(parse_error)?;
+            let [x, y, z] = triangle.normal.0;
+            let mut mesh = Mesh {
+                vertices: triangle.vertices.map(|v| glm::vec4(v[0], v[1], v[2], 0.0)),
+                normals: [glm::vec4(x, y, z, 0.0); 3],
+                ..Mesh::empty()
+            };
+            if glm::vec3(x, y, z) == glm::Vec3::zeros() {
+                Mesh::recompute_normals(std::slice::from_mut(&mut mesh));
+            }
+            meshes.push(mesh);
+        }
+        Ok(meshes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str, bytes: &[u8]) -> Result<Vec<Mesh>, SceneError> {
+        let path = std::env::temp_dir().join(format!("{name}-{}.stl", std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        let meshes = Mesh::from_stl(&path);
+        std::fs::remove_file(&path).unwrap();
+        meshes
+    }
+
+    #[test]
+    fn binary_cube() {
+        let cube = Mesh::cube();
+        let mut bytes = vec![0_u8; 80];
+        bytes.extend((cube.len() as u32).to_le_bytes());
+        for mesh in &cube {
+            let normal = mesh.normals[0];
+            for v in std::iter::once(&normal).chain(&mesh.vertices) {
+                bytes.extend([v.x, v.y, v.z].iter().flat_map(|c| c.to_le_bytes()));
+            }
+            bytes.extend(0_u16.to_le_bytes());
+        }
+
+        let meshes = load("cube", &bytes).unwrap();
+        assert_eq!(meshes.len(), 12);
+        for (mesh, expected) in meshes.iter().zip(&cube) {
+            for i in 0..3 {
+                assert_eq!(mesh.vertices[i].xyz(), expected.vertices[i].xyz());
+                assert_eq!(mesh.normals[i].xyz(), expected.normals[0].xyz());
+            }
+        }
+    }
+
+    #[test]
+    fn ascii_facet_without_normal() {
+        let stl = "\
+solid triangle
+  facet normal 0 0 0
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid triangle
+";
+        let meshes = load("triangle", stl.as_bytes()).unwrap();
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].normals, [glm::vec4(0.0, 0.0, 1.0, 0.0); 3]);
+    }
+}
@@ -1,13 +1,28 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
 use egui_wgpu::ScreenDescriptor;
 use wgpu::util::DeviceExt;
 use winit::{
-    event::{DeviceEvent, WindowEvent},
+    event::{DeviceEvent, ElementState, KeyEvent, MouseButton, Touch, TouchPhase, WindowEvent},
+    keyboard::{KeyCode, PhysicalKey},
     window::Window,
 };
 
 use crate::{
-    scene::{GpuCamera, GpuMaterial, Scene, AVAILABLE_SCENES},
-    utils::{EguiRenderer, StorageBuffer, UniformBuffer, Vertex},
+    object::PointLight,
+    scene::{
+        AccumulationFormat, BokehShape, Camera, CameraPath, CameraProjection, DebugView, FrameData,
+        GpuCamera, GpuMaterial, GpuSceneParam, Material, Medium, RenderParam, SamplerKind, Scene,
+        SceneStats, ShadingMode, Texture, AVAILABLE_SCENES,
+    },
+    utils::{
+        BufferReadback, ConvergenceMonitor, EguiRenderer, FrameTimes, Gizmo, ImageReprojector,
+        ImageResampler, PostProcess, StorageBuffer, UniformBuffer, Vertex, Wireframe,
+        RADIANCE_FORMAT,
+    },
 };
 
 use egui::{special_emojis::GITHUB, Hyperlink};
@@ -17,26 +32,291 @@ pub struct RenderContext<'a> {
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
+    present_modes: Vec<wgpu::PresentMode>,
+    /// Set when the present mode changed, the surface is configured again once the frame
+    /// holding its texture is presented.
+    surface_dirty: bool,
+    adapter_info: wgpu::AdapterInfo,
     pub size: winit::dpi::PhysicalSize<u32>,
     window: &'a Window,
     render_pipeline: wgpu::RenderPipeline,
+    post_process: PostProcess,
+    /// None when the device can't draw lines.
+    wireframe: Option<Wireframe>,
+    /// Draw the edges of the triangles over the render.
+    show_wireframe: bool,
+    gizmo: Gizmo,
+    /// Draw a grid on the y = 0 plane over the render.
+    show_grid: bool,
+    /// Draw the world axes over the render, x in red, y in green and z in blue.
+    show_axes: bool,
+    /// World units between the grid lines, the axes are `GIZMO_AXIS_CELLS` of them long.
+    grid_spacing: f32,
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    render_pipeline_layout: wgpu::PipelineLayout,
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    shader_watcher: Option<crate::utils::FileWatcher>,
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    scene_watcher: Option<SceneWatcher>,
+    #[cfg(feature = "gamepad")]
+    gamepads: Option<crate::utils::Gamepads>,
     vertex_buffer: wgpu::Buffer,
     image_bind_group_layout: wgpu::BindGroupLayout,
     image_bind_group: wgpu::BindGroup,
     image_buffer: StorageBuffer,
+    image_resampler: ImageResampler,
+    /// Resample the accumulated image on resize instead of starting over.
+    keep_samples_on_resize: bool,
+    image_reprojector: ImageReprojector,
+    /// The image buffer before the last reprojection, the next one writes over it.
+    spare_image_buffer: Option<StorageBuffer>,
+    /// Camera the accumulated image was traced from.
+    traced_camera: GpuCamera,
     camera_buffer: UniformBuffer,
     render_param_buffer: UniformBuffer,
+    scene_param_buffer: UniformBuffer,
     frame_data_buffer: UniformBuffer,
     scene_bind_group_layout: wgpu::BindGroupLayout,
     current_scene_index: usize,
-    scene_bind_group: wgpu::BindGroup,
+    scene_buffers: SceneBuffers,
     scene: Scene,
-    latest_scene: Scene,
-    pub egui_renderer: EguiRenderer,
+    egui_renderer: EguiRenderer,
     pub fps: f64,
+    frame_times: FrameTimes,
+    /// Draw the egui panels on top of the render.
+    pub show_ui: bool,
     window_focused: bool,
+    cursor_position: winit::dpi::PhysicalPosition<f64>,
+    /// Left clicks set the focus distance to whatever is under the cursor.
+    pick_focus: bool,
+    /// Where the left button went down, `None` when egui took the press.
+    left_press_position: Option<winit::dpi::PhysicalPosition<f64>>,
+    /// Id of the object picked with a left click.
+    pub selected: Option<u32>,
+    /// Index of the material shown in the material editor.
+    selected_material: usize,
+    /// Computed when the stats are shown, cleared when the scene is rebuilt.
+    stats: Option<SceneStats>,
+    /// Set when only the materials changed, they are re-uploaded before the next frame.
+    materials_dirty: bool,
+    /// Point lights changed, see `upload_lights`.
+    lights_dirty: bool,
+    /// Stops accumulating, the last image stays on screen.
+    pub paused: bool,
+    convergence: ConvergenceMonitor,
+    /// Set when the camera moved, the accumulated samples are dropped before the next frame.
+    camera_dirty: bool,
+    camera_path: CameraPath,
+    /// Camera the scene was built with, restored by "Reset camera".
+    initial_camera: Camera,
+    /// Position on `camera_path` in seconds.
+    path_time: f32,
+    /// The camera follows `camera_path`, advancing `path_time` every frame.
+    playing_path: bool,
+    /// Frames left to trace at `motion_scale` of the resolution, counted down once the
+    /// camera stops.
+    motion_frames_left: u32,
+    /// Set when something else the rays see changed, like the background or the max depth,
+    /// the accumulated samples are dropped before the next frame.
+    geometry_dirty: bool,
+    /// Set when a post effect changed. Those only change how the accumulated radiance is
+    /// displayed, so unlike the other two flags it keeps the samples.
+    post_dirty: bool,
+    /// Set by wgpu when the driver resets or the GPU goes away.
+    device_lost: Arc<AtomicBool>,
+    frame_callback: Option<FrameCallback>,
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    settings_file: Option<std::path::PathBuf>,
+    /// Set by `save_png` for the frame it renders.
+    screenshot: Option<Screenshot>,
+    /// The P key captures the egui panels too.
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    screenshot_ui: bool,
+}
+
+/// Surface capture requested for the next frame, before the egui pass or after it.
+struct Screenshot {
+    include_ui: bool,
+    /// Copy of the surface texture, with rows `bytes_per_row` apart.
+    capture: Option<(wgpu::Buffer, u32)>,
+}
+
+/// Records a copy of `texture` into a new buffer, rows padded to what copies require.
+fn capture_texture(
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    texture: &wgpu::Texture,
+) -> (wgpu::Buffer, u32) {
+    let bytes_per_row = (texture.width() * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+        * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("screenshot buffer"),
+        size: (bytes_per_row * texture.height()) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: None,
+            },
+        },
+        texture.size(),
+    );
+    (buffer, bytes_per_row)
+}
+
+/// Called after every presented frame with its [`FrameData`] and the FPS, see
+/// [`RenderContext::set_frame_callback`].
+pub type FrameCallback = Box<dyn FnMut(&FrameData, f64)>;
+
+/// Startup options for [`RenderContext::new`].
+#[derive(Clone, Debug)]
+pub struct RenderConfig {
+    /// Preferred present mode, FIFO is used when the surface doesn't support it.
+    pub present_mode: wgpu::PresentMode,
+    /// Which GPU to ask for when no adapter is picked by name.
+    pub power_preference: wgpu::PowerPreference,
+    /// Use the first adapter whose name contains this, case insensitively. Native only.
+    pub adapter_name: Option<String>,
+    /// Render params and camera loaded at startup and written by the "Save settings" button.
+    /// Native only.
+    pub settings_file: Option<std::path::PathBuf>,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            present_mode: wgpu::PresentMode::Fifo,
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            adapter_name: None,
+            settings_file: Some("config.ron".into()),
+        }
+    }
+}
+
+/// Why [`RenderContext::new`] could not set up rendering.
+#[derive(Debug)]
+pub enum RenderInitError {
+    #[cfg(target_arch = "wasm32")]
+    NoCanvas,
+    CreateSurface(wgpu::CreateSurfaceError),
+    NoAdapter,
+    RequestDevice(wgpu::RequestDeviceError),
+    BufferTooLarge(BufferTooLarge),
+}
+
+impl std::fmt::Display for RenderInitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            #[cfg(target_arch = "wasm32")]
+            RenderInitError::NoCanvas => write!(f, "the window has no canvas"),
+            RenderInitError::CreateSurface(e) => write!(f, "can't create the surface: {e}"),
+            RenderInitError::NoAdapter => write!(f, "no GPU adapter can present to the window"),
+            RenderInitError::RequestDevice(e) => write!(f, "can't create the GPU device: {e}"),
+            RenderInitError::BufferTooLarge(e) => write!(f, "can't upload the scene: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RenderInitError {}
+
+/// Device and queue of `adapter`, able to draw lines when it supports them.
+async fn request_device(
+    adapter: &wgpu::Adapter,
+    required_limits: wgpu::Limits,
+) -> Result<(wgpu::Device, wgpu::Queue), RenderInitError> {
+    adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                required_features: match Wireframe::supported(adapter) {
+                    true => wgpu::Features::POLYGON_MODE_LINE,
+                    false => wgpu::Features::empty(),
+                },
+                required_limits,
+                label: Some("Device"),
+                memory_hints: Default::default(),
+            },
+            None,
+        )
+        .await
+        .map_err(RenderInitError::RequestDevice)
+}
+
+/// The adapter named by `render_config` if there is one that can present to `surface`,
+/// otherwise the one wgpu prefers for its power preference.
+async fn select_adapter(
+    instance: &wgpu::Instance,
+    backends: wgpu::Backends,
+    surface: &wgpu::Surface<'_>,
+    render_config: &RenderConfig,
+) -> Option<wgpu::Adapter> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(name) = &render_config.adapter_name {
+        let name = name.to_lowercase();
+        let adapter = instance.enumerate_adapters(backends).into_iter().find(|a| {
+            a.is_surface_supported(surface) && a.get_info().name.to_lowercase().contains(&name)
+        });
+        match adapter {
+            Some(adapter) => return Some(adapter),
+            None => log::warn!("No adapter matches \"{name}\", using the default one"),
+        }
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = backends;
+
+    instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: render_config.power_preference,
+            compatible_surface: Some(surface),
+            force_fallback_adapter: false,
+        })
+        .await
+}
+
+/// FIFO is the only mode every surface has to support.
+fn choose_present_mode(
+    requested: wgpu::PresentMode,
+    supported: &[wgpu::PresentMode],
+) -> wgpu::PresentMode {
+    if supported.contains(&requested) {
+        requested
+    } else {
+        log::warn!(
+            "Present mode {:?} is not supported (supported: {:?}), falling back to Fifo",
+            requested,
+            supported
+        );
+        wgpu::PresentMode::Fifo
+    }
 }
 
+/// Scene storage buffers that can be updated in place, along with the bind group using them.
+struct SceneBuffers {
+    materials: StorageBuffer,
+    textures: StorageBuffer,
+    surfaces: StorageBuffer,
+    lights: StorageBuffer,
+    bind_group: wgpu::BindGroup,
+}
+
+/// A left press and release further apart than this (in pixels) is a drag, not a click.
+const CLICK_TOLERANCE: f64 = 4.0;
+
+/// Length of the axes drawn over the render, in grid cells.
+const GIZMO_AXIS_CELLS: f32 = 2.0;
+
+/// Frames the camera stays still before it is traced at full resolution again.
+const MOTION_IDLE_FRAMES: u32 = 3;
+
+/// Frames worth of samples a pixel keeps at most when reprojected, a longer history is less
+/// noisy while moving but takes longer to wash out what the reprojection got wrong.
+const TAA_HISTORY_FRAMES: u32 = 16;
+
 // const RGB_TRIANGLE: &[Vertex] = &[
 //     Vertex { position: [0.0, 0.5, 0.0], color: [1.0, 0.0, 0.0] },
 //     Vertex { position: [-0.5, -0.5, 0.0], color: [0.0, 1.0, 0.0] },
@@ -62,6 +342,15 @@ const VERTICES: &[Vertex] = &[
 
 const VERTICES_LEN: usize = VERTICES.len();
 
+/// Where the H key saves the render.
+#[cfg(not(target_arch = "wasm32"))]
+const HDR_PATH: &str = "render.hdr";
+/// Where the P key saves a screenshot.
+#[cfg(not(target_arch = "wasm32"))]
+const PNG_PATH: &str = "screenshot.png";
+#[cfg(not(target_arch = "wasm32"))]
+const CAMERA_PATH_FILE: &str = "camera_path.ron";
+
 fn create_scene_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
     let mut entries = Vec::new();
     for i in 0..7 {
@@ -83,150 +372,190 @@ fn create_scene_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayou
     })
 }
 
-fn create_scene_bind_group(
-    device: &wgpu::Device,
-    layout: &wgpu::BindGroupLayout,
-    scene: &Scene,
-) -> wgpu::BindGroup {
-    let objects_buffer = StorageBuffer::new_from_bytes(
-        device,
-        bytemuck::cast_slice(scene.object_list.objects.as_slice()),
-        0_u32,
-        Some("objects buffer"),
-    );
-
-    let sphere_buffer = StorageBuffer::new_from_bytes(
-        device,
-        bytemuck::cast_slice(scene.spheres.as_slice()),
-        1_u32,
-        Some("sphere buffer"),
-    );
+/// A scene buffer larger than what the device allows for a single binding.
+#[derive(Debug)]
+pub struct BufferTooLarge {
+    pub label: &'static str,
+    pub size: u64,
+    pub limit: u64,
+}
 
-    let mut global_texture_data = Vec::new();
-    let mut material_data: Vec<GpuMaterial> = Vec::with_capacity(scene.materials.len());
-    for material in scene.materials.iter() {
-        material_data.push(GpuMaterial::new(material, &mut global_texture_data));
+impl std::fmt::Display for BufferTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the {} takes {:.1} MiB but the device binds at most {:.1} MiB, try a smaller scene",
+            self.label,
+            self.size as f64 / (1 << 20) as f64,
+            self.limit as f64 / (1 << 20) as f64,
+        )
     }
+}
 
-    let material_buffer = StorageBuffer::new_from_bytes(
-        device,
-        bytemuck::cast_slice(material_data.as_slice()),
-        2_u32,
-        Some("material buffer"),
-    );
-
-    let texture_buffer = StorageBuffer::new_from_bytes(
-        device,
-        bytemuck::cast_slice(global_texture_data.as_slice()),
-        3_u32,
-        Some("texture buffer"),
-    );
-
-    let surfaces_buffer = StorageBuffer::new_from_bytes(
-        device,
-        bytemuck::cast_slice(scene.object_list.meshes.as_slice()),
-        4_u32,
-        Some("surfaces buffer"),
-    );
-
-    let lights_buffer = StorageBuffer::new_from_bytes(
-        device,
-        bytemuck::cast_slice(scene.lights.as_slice()),
-        5_u32,
-        Some("lights buffer"),
-    );
+impl std::error::Error for BufferTooLarge {}
 
+/// Fails before creating any buffer when one of them can't be bound, wgpu would only report
+/// a validation error on the bind group without saying which buffer is at fault.
+fn create_scene_buffers(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    scene: &Scene,
+) -> Result<SceneBuffers, BufferTooLarge> {
+    let (material_data, global_texture_data) =
+        GpuMaterial::from_materials(&scene.materials, &scene.background);
+    let light_data = scene.gpu_lights();
     let bvh_nodes = crate::utils::bvh::build_bvh_flat(&scene.spheres, &scene.object_list.meshes);
 
-    let bvh_buffer = StorageBuffer::new_from_bytes(
-        device,
-        bytemuck::cast_slice(bvh_nodes.as_slice()),
-        6_u32,
-        Some("bvh buffer"),
-    );
+    let contents: [(&'static str, &[u8]); 7] = [
+        (
+            "objects buffer",
+            bytemuck::cast_slice(scene.object_list.objects.as_slice()),
+        ),
+        (
+            "sphere buffer",
+            bytemuck::cast_slice(scene.spheres.as_slice()),
+        ),
+        (
+            "material buffer",
+            bytemuck::cast_slice(material_data.as_slice()),
+        ),
+        (
+            "texture buffer",
+            bytemuck::cast_slice(global_texture_data.as_slice()),
+        ),
+        (
+            "surfaces buffer",
+            bytemuck::cast_slice(scene.object_list.meshes.as_slice()),
+        ),
+        ("lights buffer", bytemuck::cast_slice(light_data.as_slice())),
+        ("bvh buffer", bytemuck::cast_slice(bvh_nodes.as_slice())),
+    ];
+
+    let limit = device.limits().max_storage_buffer_binding_size as u64;
+    if let Some((label, bytes)) = contents
+        .iter()
+        .find(|(_, bytes)| bytes.len() as u64 > limit)
+    {
+        return Err(BufferTooLarge {
+            label,
+            size: bytes.len() as u64,
+            limit,
+        });
+    }
 
-    device.create_bind_group(&wgpu::BindGroupDescriptor {
+    let [objects, spheres, materials, textures, surfaces, lights, bvh] = std::array::from_fn(|i| {
+        let (label, bytes) = contents[i];
+        StorageBuffer::new_from_bytes(device, bytes, i as u32, Some(label))
+    });
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
         layout,
         entries: &[
-            objects_buffer.binding(),
-            sphere_buffer.binding(),
-            material_buffer.binding(),
-            texture_buffer.binding(),
-            surfaces_buffer.binding(),
-            lights_buffer.binding(),
-            bvh_buffer.binding(),
+            objects.binding(),
+            spheres.binding(),
+            materials.binding(),
+            textures.binding(),
+            surfaces.binding(),
+            lights.binding(),
+            bvh.binding(),
         ],
         label: Some("scene bind group"),
+    });
+
+    Ok(SceneBuffers {
+        materials,
+        textures,
+        surfaces,
+        lights,
+        bind_group,
     })
 }
 
 impl<'a> RenderContext<'a> {
-    pub async fn new(window: &'a Window, scene: &Scene) -> RenderContext<'a> {
-        let size;
-        cfg_if::cfg_if! {
-            if #[cfg(target_arch = "wasm32")] {
-                use winit::platform::web::WindowExtWebSys;
-                let canvas = window.canvas().unwrap();
-                // On the web, `client_width/height` can be 0 during early layout.
-                // Fall back to the canvas intrinsic size and clamp to >= 1.
-                let mut width = canvas.client_width().max(0) as u32;
-                let mut height = canvas.client_height().max(0) as u32;
-                if width == 0 || height == 0 {
-                    width = canvas.width();
-                    height = canvas.height();
-                }
-                width = width.max(1);
-                height = height.max(1);
-
-                size = winit::dpi::PhysicalSize::new(width, height);
-            } else {
-                size = window.inner_size();
+    pub async fn new(
+        window: &'a Window,
+        scene: &Scene,
+        render_config: RenderConfig,
+    ) -> Result<RenderContext<'a>, RenderInitError> {
+        let mut scene = scene.clone();
+        if let Err(e) = scene.validate() {
+            log::error!("Invalid scene, clamping its indices: {e}");
+            scene.clamp_indices();
+        }
+        let initial_camera = scene.camera;
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(file) = &render_config.settings_file {
+            match crate::scene::Settings::load(file) {
+                Ok(settings) => settings.apply(&mut scene),
+                Err(crate::scene::SceneError::Io { .. }) if !file.exists() => {}
+                Err(e) => log::warn!("{e}, using the default settings"),
             }
         }
 
+        #[cfg(target_arch = "wasm32")]
+        let size = {
+            use winit::platform::web::WindowExtWebSys;
+            let canvas = window.canvas().ok_or(RenderInitError::NoCanvas)?;
+            // On the web, `client_width/height` can be 0 during early layout.
+            // Fall back to the canvas intrinsic size and clamp to >= 1.
+            let mut width = canvas.client_width().max(0) as u32;
+            let mut height = canvas.client_height().max(0) as u32;
+            if width == 0 || height == 0 {
+                width = canvas.width();
+                height = canvas.height();
+            }
+            width = width.max(1);
+            height = height.max(1);
+
+            winit::dpi::PhysicalSize::new(width, height)
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let size = window.inner_size();
+
         // The instance is a handle to our GPU
         // Backends::all => Vulkan + Metal + DX12 + Browser WebGPU
+        let backends = wgpu::util::backend_bits_from_env().unwrap_or_else(wgpu::Backends::all);
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::util::backend_bits_from_env().unwrap_or_else(wgpu::Backends::all),
+            backends,
             ..Default::default()
         });
 
         log::debug!("Instance: {:?}", instance);
-        let surface: wgpu::Surface<'_> = instance.create_surface(window).unwrap();
+        let surface: wgpu::Surface<'_> = instance
+            .create_surface(window)
+            .map_err(RenderInitError::CreateSurface)?;
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
-
-        log::debug!("Adapter: {:?}", adapter.get_info());
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    required_features: wgpu::Features::empty(),
-                    // WebGL doesn't support all of wgpu's features, so if
-                    // we're building for the web, we'll have to disable some.
-                    required_limits: if cfg!(target_arch = "wasm32") {
-                        wgpu::Limits::default().using_resolution(adapter.limits())
-                    } else {
-                        wgpu::Limits {
-                            max_storage_buffer_binding_size: 512_u32 << 20,
-                            ..Default::default()
-                        }
-                    },
-                    label: Some("Device"),
-                    memory_hints: Default::default(),
-                },
-                None,
-            )
+        let adapter = select_adapter(&instance, backends, &surface, &render_config)
             .await
-            .unwrap();
+            .ok_or(RenderInitError::NoAdapter)?;
+
+        log::info!("Adapter: {:?}", adapter.get_info());
+        let wireframe_supported = Wireframe::supported(&adapter);
+        // WebGL doesn't support all of wgpu's features, so if
+        // we're building for the web, we'll have to disable some.
+        let required_limits = if cfg!(target_arch = "wasm32") {
+            wgpu::Limits::default().using_resolution(adapter.limits())
+        } else {
+            wgpu::Limits {
+                max_storage_buffer_binding_size: 512_u32 << 20,
+                ..Default::default()
+            }
+        };
+        let (device, queue) = request_device(&adapter, required_limits).await?;
         log::debug!("Device: {:?}", device);
 
+        let device_lost = Arc::new(AtomicBool::new(false));
+        {
+            let device_lost = device_lost.clone();
+            device.set_device_lost_callback(move |reason, message| {
+                // Dropping the device at exit also ends up here.
+                if reason != wgpu::DeviceLostReason::Dropped {
+                    log::error!("GPU device lost ({reason:?}): {message}");
+                    device_lost.store(true, Ordering::Relaxed);
+                }
+            });
+        }
+
         let camera_buffer = {
             let camera = GpuCamera::new(&scene.camera, (size.width, size.height));
 
@@ -256,15 +585,15 @@ impl<'a> RenderContext<'a> {
                 Some("render param buffer"),
             )
         };
-        let image_buffer = {
-            let buffer = vec![[0_f32; 3]; size.width as usize * size.height as usize];
-            StorageBuffer::new_from_bytes(
-                &device,
-                bytemuck::cast_slice(buffer.as_slice()),
-                3_u32,
-                Some("image buffer"),
-            )
-        };
+        let scene_param_buffer = UniformBuffer::new_from_bytes(
+            &device,
+            bytemuck::bytes_of(&GpuSceneParam::new(&scene)),
+            4_u32,
+            Some("scene param buffer"),
+        );
+
+        let image_buffer =
+            create_image_buffer(&device, size, scene.render_param.accumulation_format.into());
 
         let image_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -273,6 +602,7 @@ impl<'a> RenderContext<'a> {
                     frame_data_buffer.layout(wgpu::ShaderStages::FRAGMENT),
                     render_param_buffer.layout(wgpu::ShaderStages::FRAGMENT),
                     image_buffer.layout(wgpu::ShaderStages::FRAGMENT, false),
+                    scene_param_buffer.layout(wgpu::ShaderStages::FRAGMENT),
                 ],
                 label: Some("image layout"),
             });
@@ -284,14 +614,27 @@ impl<'a> RenderContext<'a> {
                 frame_data_buffer.binding(),
                 render_param_buffer.binding(),
                 image_buffer.binding(),
+                scene_param_buffer.binding(),
             ],
             label: Some("image bind group"),
         });
 
         let scene_bind_group_layout = create_scene_bind_group_layout(&device);
-        let scene_bind_group = create_scene_bind_group(&device, &scene_bind_group_layout, scene);
+        let scene_buffers = create_scene_buffers(&device, &scene_bind_group_layout, &scene)
+            .map_err(RenderInitError::BufferTooLarge)?;
 
+        #[cfg(not(all(feature = "hot-reload", not(target_arch = "wasm32"))))]
         let shader = device.create_shader_module(wgpu::include_wgsl!("shader/raytracing.wgsl"));
+        // Start from the file on disk too, it may have changed since the build.
+        #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(SHADER_PATH),
+            source: wgpu::ShaderSource::Wgsl(
+                std::fs::read_to_string(SHADER_PATH)
+                    .map(Into::into)
+                    .unwrap_or(include_str!("shader/raytracing.wgsl").into()),
+            ),
+        });
 
         let surface_caps = surface.get_capabilities(&adapter);
         // Shader code in this tutorial assumes an sRGB surface texture. Using a different
@@ -308,12 +651,17 @@ impl<'a> RenderContext<'a> {
 
         log::debug!("Surface format: {:?}", surface_format);
 
+        // Copies of the surface are only for screenshots, see `save_png`.
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | (surface_caps.usages & wgpu::TextureUsages::COPY_SRC),
             format: surface_format,
             width: size.width,
             height: size.height,
-            present_mode: surface_caps.present_modes[0],
+            present_mode: choose_present_mode(
+                render_config.present_mode,
+                &surface_caps.present_modes,
+            ),
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
@@ -329,50 +677,10 @@ impl<'a> RenderContext<'a> {
                 push_constant_ranges: &[],
             });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[Vertex::desc()],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: match surface_format.is_srgb() {
-                    true => Some("fs_main_srgb"),
-                    // for webgpu
-                    false => Some("fs_main_rgb"),
-                },
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
-        });
+        let render_pipeline = create_render_pipeline(&device, &render_pipeline_layout, &shader);
+        let post_process = PostProcess::new(&device, config.format, size);
+        let wireframe = wireframe_supported.then(|| Wireframe::new(&device, config.format));
+        let gizmo = Gizmo::new(&device, config.format);
 
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Vertex buffer"),
@@ -380,68 +688,196 @@ impl<'a> RenderContext<'a> {
             usage: wgpu::BufferUsages::VERTEX,
         });
 
+        let image_resampler = ImageResampler::new(&device);
+        let image_reprojector = ImageReprojector::new(&device);
+
         let egui_renderer = EguiRenderer::new(&device, config.format, None, 1, window);
 
-        Self {
+        Ok(Self {
             surface,
             device,
             queue,
             config,
+            present_modes: surface_caps.present_modes,
+            surface_dirty: false,
             size,
             window,
             render_pipeline,
+            post_process,
+            wireframe,
+            show_wireframe: false,
+            gizmo,
+            show_grid: false,
+            show_axes: false,
+            grid_spacing: 1.0,
+            #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+            render_pipeline_layout,
+            #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+            shader_watcher: crate::utils::FileWatcher::new(std::path::Path::new(SHADER_PATH))
+                .inspect_err(|e| log::warn!("Can't watch {SHADER_PATH}: {e}"))
+                .ok(),
+            #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+            scene_watcher: None,
+            #[cfg(feature = "gamepad")]
+            gamepads: crate::utils::Gamepads::new(),
             vertex_buffer,
             image_bind_group_layout,
             image_bind_group,
             image_buffer,
+            image_resampler,
+            keep_samples_on_resize: true,
+            image_reprojector,
+            spare_image_buffer: None,
+            traced_camera: GpuCamera::new(&scene.camera, (size.width, size.height)),
             camera_buffer,
             frame_data_buffer,
             render_param_buffer,
+            scene_param_buffer,
             scene_bind_group_layout,
             current_scene_index: 0,
-            scene_bind_group,
-            scene: scene.clone(),
-            latest_scene: scene.clone(),
+            scene_buffers,
+            scene,
             egui_renderer,
+            adapter_info: adapter.get_info(),
             fps: 0.0,
+            frame_times: FrameTimes::new(),
+            show_ui: true,
             window_focused: true,
-        }
+            cursor_position: winit::dpi::PhysicalPosition::new(0.0, 0.0),
+            pick_focus: false,
+            left_press_position: None,
+            selected: None,
+            selected_material: 0,
+            stats: None,
+            materials_dirty: false,
+            lights_dirty: false,
+            paused: false,
+            convergence: ConvergenceMonitor::new(),
+            camera_dirty: false,
+            camera_path: CameraPath::default(),
+            initial_camera,
+            path_time: 0.0,
+            playing_path: false,
+            motion_frames_left: 0,
+            geometry_dirty: false,
+            post_dirty: false,
+            device_lost,
+            frame_callback: None,
+            settings_file: render_config.settings_file,
+            screenshot: None,
+            screenshot_ui: false,
+        })
+    }
+
+    /// Nothing can be rendered anymore, the device would have to be created again.
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Relaxed)
+    }
+
+    /// Configures the surface again with the current size, for when it was lost or outdated.
+    pub fn reconfigure_surface(&mut self) {
+        self.surface.configure(&self.device, &self.config);
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
+            let old_size = self.size;
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
+            self.post_process.resize(&self.device, new_size);
+
+            let keep_samples = self.keep_samples_on_resize
+                && AccumulationFormat::from(self.scene.render_param.accumulation_format)
+                    == AccumulationFormat::F32;
+            self.recreate_image_buffer(old_size, keep_samples);
+        }
+    }
 
-            // The raytracer stores the image in a storage buffer sized to width*height.
-            // When resizing, we must recreate that buffer (otherwise the shader indexes OOB).
-            let buffer = vec![[0_f32; 3]; new_size.width as usize * new_size.height as usize];
-            self.image_buffer = StorageBuffer::new_from_bytes(
+    /// The raytracer stores the image in a storage buffer sized to width*height, it has to be
+    /// recreated when the size or the accumulation format changes.
+    fn recreate_image_buffer(
+        &mut self,
+        old_size: winit::dpi::PhysicalSize<u32>,
+        keep_samples: bool,
+    ) {
+        let new_size = self.size;
+        let image_buffer = create_image_buffer(
+            &self.device,
+            new_size,
+            self.scene.render_param.accumulation_format.into(),
+        );
+
+        // Averages are stretched to the new size, the aspect ratio change is not accounted for
+        // but it fades out as new samples come in.
+        let keep_samples = keep_samples && self.scene.render_param.total_samples > 0;
+        if keep_samples {
+            self.image_resampler.resample(
                 &self.device,
-                bytemuck::cast_slice(buffer.as_slice()),
-                3_u32,
-                Some("image buffer"),
+                &self.queue,
+                (&self.image_buffer, (old_size.width, old_size.height)),
+                (&image_buffer, (new_size.width, new_size.height)),
             );
+        }
+        self.image_buffer = image_buffer;
+        self.spare_image_buffer = None;
+        self.rebind_image_buffer();
 
-            self.image_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: &self.image_bind_group_layout,
-                entries: &[
-                    self.camera_buffer.binding(),
-                    self.frame_data_buffer.binding(),
-                    self.render_param_buffer.binding(),
-                    self.image_buffer.binding(),
-                ],
-                label: Some("image bind group"),
-            });
-
-            // Reset accumulation after resizing.
-            self.scene.render_param.total_samples = 0;
+        if !keep_samples {
+            self.scene.render_param.reset_accumulation();
             self.scene.frame_data.index = 0;
         }
     }
 
+    fn rebind_image_buffer(&mut self) {
+        self.image_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.image_bind_group_layout,
+            entries: &[
+                self.camera_buffer.binding(),
+                self.frame_data_buffer.binding(),
+                self.render_param_buffer.binding(),
+                self.image_buffer.binding(),
+                self.scene_param_buffer.binding(),
+            ],
+            label: Some("image bind group"),
+        });
+    }
+
+    /// Moves the accumulated samples into the view of the current camera and trace size when
+    /// [`RenderParam::taa`] is on, returns false when they have to be discarded instead.
+    fn reproject_image(&mut self) -> bool {
+        let render_param = &self.scene.render_param;
+        if render_param.taa == 0
+            || render_param.total_samples == 0
+            || AccumulationFormat::from(render_param.accumulation_format) != AccumulationFormat::F32
+        {
+            return false;
+        }
+        let max_samples = TAA_HISTORY_FRAMES * render_param.requested_samples_per_pixel.max(1);
+
+        let image_buffer = match self.spare_image_buffer.take() {
+            Some(buffer) if buffer.handle().size() == self.image_buffer.handle().size() => buffer,
+            _ => create_image_buffer(&self.device, self.size, AccumulationFormat::F32),
+        };
+        let camera = GpuCamera::new(&self.scene.camera, (self.size.width, self.size.height));
+        // The frame data still holds the size of the last traced frame.
+        let traced_size = (self.scene.frame_data.width, self.scene.frame_data.height);
+        self.image_reprojector.reproject(
+            &self.device,
+            &self.queue,
+            (&self.image_buffer, traced_size, self.traced_camera),
+            (&image_buffer, self.trace_size(), camera),
+            max_samples,
+        );
+        self.spare_image_buffer = Some(std::mem::replace(&mut self.image_buffer, image_buffer));
+        self.rebind_image_buffer();
+
+        let render_param = &mut self.scene.render_param;
+        render_param.total_samples = render_param.total_samples.min(max_samples);
+        true
+    }
+
     pub fn window_event(&mut self, event: &WindowEvent, mouse_pressed: &mut bool) {
         self.egui_renderer.handle_input(self.window, event);
 
@@ -449,6 +885,100 @@ impl<'a> RenderContext<'a> {
             WindowEvent::Focused(focused) => {
                 self.window_focused = *focused;
             }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = *position;
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    self.left_press_position =
+                        (!self.egui_renderer.context().is_pointer_over_area())
+                            .then_some(self.cursor_position);
+                }
+                ElementState::Released => {
+                    if let Some(pressed) = self.left_press_position.take() {
+                        let dx = self.cursor_position.x - pressed.x;
+                        let dy = self.cursor_position.y - pressed.y;
+                        if dx.hypot(dy) <= CLICK_TOLERANCE {
+                            self.click(self.cursor_position);
+                        }
+                    }
+                }
+            },
+            WindowEvent::MouseWheel { .. } => {
+                // Let egui scroll its own panels.
+                if !self.egui_renderer.context().is_pointer_over_area() {
+                    self.scene
+                        .camera_controller
+                        .handle_input(event, mouse_pressed);
+                }
+            }
+            // Fingers landing on egui don't move the camera.
+            WindowEvent::Touch(Touch {
+                phase: TouchPhase::Started,
+                ..
+            }) if self.egui_renderer.context().is_pointer_over_area() => {}
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyK),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } if !self.egui_renderer.context().wants_keyboard_input() => {
+                self.paused = !self.paused;
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyR),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } if !self.egui_renderer.context().wants_keyboard_input() => {
+                self.reset_camera();
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyH),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } if !self.egui_renderer.context().wants_keyboard_input() => {
+                let path = std::path::Path::new(HDR_PATH);
+                match self.save_hdr(path) {
+                    Ok(()) => log::info!("Saved the render to {}", path.display()),
+                    Err(e) => log::error!("Failed to save {}: {e}", path.display()),
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyP),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } if !self.egui_renderer.context().wants_keyboard_input() => {
+                let path = std::path::Path::new(PNG_PATH);
+                match self.save_png(path, self.screenshot_ui) {
+                    Ok(()) => log::info!("Saved a screenshot to {}", path.display()),
+                    Err(e) => log::error!("Failed to save {}: {e}", path.display()),
+                }
+            }
             WindowEvent::KeyboardInput { .. } => {
                 if self.window_focused {
                     self.scene
@@ -464,118 +994,731 @@ impl<'a> RenderContext<'a> {
         }
     }
 
-    pub fn device_event(&mut self, event: &DeviceEvent, mouse_pressed: bool) {
-        self.scene
-            .camera_controller
-            .handle_mouse(event, mouse_pressed);
+    /// Selects the object under the given window pixel, and focuses on it when focus picking
+    /// is enabled.
+    fn click(&mut self, position: winit::dpi::PhysicalPosition<f64>) {
+        let camera = GpuCamera::new(&self.scene.camera, (self.size.width, self.size.height));
+        let u = position.x as f32 / self.size.width as f32;
+        let v = 1.0 - position.y as f32 / self.size.height as f32;
+        let ray = camera.ray(u, v);
+
+        let Some(hit) = self.scene.intersect(&ray) else {
+            log::debug!("Picking missed the scene at {:?}", position);
+            self.selected = None;
+            return;
+        };
+
+        log::debug!("Picked object {} at t = {}", hit.object_id, hit.t);
+        self.selected = Some(hit.object_id);
+        if let Some(material_idx) = self.scene.object_material(hit.object_id) {
+            self.selected_material = material_idx as usize;
+        }
+
+        if self.pick_focus {
+            let w = glm::normalize(&self.scene.camera.eye_dir);
+            // The focus plane is perpendicular to the view direction.
+            self.scene.camera.focus_distance =
+                glm::dot(&(hit.point - self.scene.camera.eye_pos), &w).max(0.1);
+            self.camera_dirty = true;
+        }
     }
 
-    fn rebuild_scene(&mut self) {
-        self.scene_bind_group =
-            create_scene_bind_group(&self.device, &self.scene_bind_group_layout, &self.scene);
-        self.scene.render_param.total_samples = 0;
-        self.scene.frame_data.index = 0;
+    /// Outlines the bounding box of the selected object on top of the render.
+    fn draw_selection(&self, ctx: &egui::Context) {
+        let Some(aabb) = self.selected.and_then(|id| self.scene.object_aabb(id)) else {
+            return;
+        };
+
+        let camera = GpuCamera::new(&self.scene.camera, (self.size.width, self.size.height));
+        let screen = ctx.screen_rect();
+        let corners = aabb.corners().map(|corner| {
+            camera.project(corner).map(|uv| {
+                egui::pos2(
+                    screen.min.x + uv.x * screen.width(),
+                    screen.min.y + (1.0 - uv.y) * screen.height(),
+                )
+            })
+        });
+
+        // Background layer so the side panel stays on top of the outline.
+        let painter = ctx.layer_painter(egui::LayerId::background());
+        let stroke = egui::Stroke::new(1.5, egui::Color32::from_rgb(255, 165, 0));
+        for a in 0..8 {
+            for axis in [1, 2, 4] {
+                let b = a | axis;
+                if a == b {
+                    continue;
+                }
+                if let (Some(pa), Some(pb)) = (corners[a], corners[b]) {
+                    painter.line_segment([pa, pb], stroke);
+                }
+            }
+        }
     }
 
-    pub fn update(&mut self, dt: std::time::Duration) {
+    /// Arrows moving the camera, for touch screens without a keyboard. Held buttons keep
+    /// moving.
+    #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+    fn draw_touch_controls(&mut self, ctx: &egui::Context) {
+        let size = egui::vec2(48.0, 48.0);
+        let held = |ui: &mut egui::Ui, label: &str| {
+            let button = egui::Button::new(egui::RichText::new(label).size(24.0)).min_size(size);
+            ui.add(button).is_pointer_button_down_on() as u32 as f32
+        };
+        let (mut forward, mut right) = (0.0, 0.0);
+        egui::Area::new(egui::Id::new("touch_controls"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-16.0, -16.0))
+            .show(ctx, |ui| {
+                egui::Grid::new("touch_arrows").show(ui, |ui| {
+                    ui.label("");
+                    forward += held(ui, "⬆");
+                    ui.end_row();
+                    right -= held(ui, "⬅");
+                    forward -= held(ui, "⬇");
+                    right += held(ui, "➡");
+                    ui.end_row();
+                });
+            });
         self.scene
             .camera_controller
-            .update_camera(&mut self.scene.camera, dt);
+            .process_touch_buttons(forward, right);
+    }
 
-        if self.latest_scene != self.scene {
-            let samples_per_pixel = self.latest_scene.render_param.samples_per_pixel;
-            self.latest_scene = self.scene.clone();
-            self.scene.render_param.total_samples = 0;
-            self.scene.render_param.samples_per_pixel = samples_per_pixel;
-            self.latest_scene.render_param.samples_per_pixel = samples_per_pixel;
+    /// Frame times of the last frames, newest on the right.
+    fn frame_times_ui(&self, ui: &mut egui::Ui) {
+        let points: egui_plot::PlotPoints = self
+            .frame_times
+            .iter()
+            .enumerate()
+            .map(|(i, ms)| [i as f64, ms as f64])
+            .collect();
+        egui_plot::Plot::new("frame_times")
+            .height(60.0)
+            .include_y(0.0)
+            .show_axes([false, true])
+            .allow_drag(false)
+            .allow_zoom(false)
+            .allow_scroll(false)
+            .allow_boxed_zoom(false)
+            .show(ui, |plot| {
+                plot.line(egui_plot::Line::new(points).name("ms"))
+            });
+        if let Some((min, avg, max)) = self.frame_times.stats() {
+            ui.label(format!(
+                "Frame time: {min:.1} / {avg:.1} / {max:.1} ms (min / avg / max)"
+            ));
         }
     }
 
-    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        // On wasm, resizing the browser window typically does NOT emit a reliable `WindowEvent::Resized`.
-        // Instead, keep the canvas backing resolution in sync with its CSS size.
-        #[cfg(target_arch = "wasm32")]
-        {
-            use winit::platform::web::WindowExtWebSys;
-
-            let canvas = self.window.canvas().unwrap();
-
-            let css_width = canvas.client_width().max(1) as f64;
-            let css_height = canvas.client_height().max(1) as f64;
-            let dpr = web_sys::window()
-                .map(|w| w.device_pixel_ratio())
-                .unwrap_or(1.0)
-                .max(1.0);
+    /// Puts the camera back where the scene started and stops it from drifting.
+    fn reset_camera(&mut self) {
+        self.camera_dirty |= self.scene.camera != self.initial_camera;
+        self.scene.camera = self.initial_camera;
+        self.scene.camera_controller.clear();
+        self.playing_path = false;
+    }
 
-            let new_width = (css_width * dpr).round().max(1.0) as u32;
-            let new_height = (css_height * dpr).round().max(1.0) as u32;
+    fn set_camera_from_path(&mut self) {
+        if let Some(camera) = self.camera_path.sample(self.path_time) {
+            self.camera_dirty |= self.scene.camera != camera;
+            self.scene.camera = camera;
+        }
+    }
 
-            if canvas.width() != new_width || canvas.height() != new_height {
-                canvas.set_width(new_width);
-                canvas.set_height(new_height);
-                self.resize(winit::dpi::PhysicalSize::new(new_width, new_height));
+    fn camera_path_ui(&mut self, ui: &mut egui::Ui) {
+        let path = &mut self.camera_path;
+        let mut removed = None;
+        let mut retimed = false;
+        egui::Grid::new("keyframes").show(ui, |ui| {
+            for (i, (time, _)) in path.keyframes.iter_mut().enumerate() {
+                ui.label(format!("#{i}"));
+                retimed |= ui
+                    .add(
+                        egui::DragValue::new(time)
+                            .speed(0.1)
+                            .range(0.0..=f32::MAX)
+                            .suffix(" s"),
+                    )
+                    .changed();
+                if ui.small_button("✖").clicked() {
+                    removed = Some(i);
+                }
+                ui.end_row();
             }
+        });
+        if let Some(i) = removed {
+            path.keyframes.remove(i);
+        }
+        if retimed {
+            path.keyframes.sort_by(|a, b| a.0.total_cmp(&b.0));
         }
 
+        if ui
+            .button("Add keyframe")
+            .on_hover_text("Append the current camera, one second after the last keyframe")
+            .clicked()
         {
-            let camera = GpuCamera::new(&self.scene.camera, (self.size.width, self.size.height));
+            let time = match path.keyframes.is_empty() {
+                true => 0.0,
+                false => path.duration() + 1.0,
+            };
+            path.add_keyframe(time, self.scene.camera);
+        }
 
-            self.queue
-                .write_buffer(&self.camera_buffer.handle(), 0, bytemuck::bytes_of(&camera));
+        let duration = path.duration();
+        ui.horizontal(|ui| {
+            let label = if self.playing_path { "Stop" } else { "Play" };
+            if ui.button(label).clicked() {
+                self.playing_path = !self.playing_path;
+                if self.playing_path && self.path_time >= duration {
+                    self.path_time = 0.0;
+                }
+            }
+            if ui
+                .add(egui::Slider::new(&mut self.path_time, 0.0..=duration).suffix(" s"))
+                .changed()
+            {
+                self.set_camera_from_path();
+            }
+        });
 
-            self.scene.frame_data.width = self.size.width;
-            self.scene.frame_data.height = self.size.height;
-            self.scene.frame_data.index += 1;
+        #[cfg(not(target_arch = "wasm32"))]
+        ui.horizontal(|ui| {
+            let file = std::path::Path::new(CAMERA_PATH_FILE);
+            if ui.button("Save").clicked() {
+                match self.camera_path.save(file) {
+                    Ok(()) => log::info!("Saved the camera path to {}", file.display()),
+                    Err(e) => log::error!("Failed to save the camera path: {e}"),
+                }
+            }
+            if ui.button("Load").clicked() {
+                match CameraPath::load(file) {
+                    Ok(path) => {
+                        self.camera_path = path;
+                        self.path_time = 0.0;
+                        self.playing_path = false;
+                    }
+                    Err(e) => log::error!("Failed to load the camera path: {e}"),
+                }
+            }
+            ui.label(CAMERA_PATH_FILE);
+        });
+    }
 
-            self.queue.write_buffer(
-                &self.frame_data_buffer.handle(),
-                0,
-                bytemuck::bytes_of(&self.scene.frame_data),
-            );
+    /// Size the image is traced at, the top left part of the radiance texture. Smaller than
+    /// the surface while the camera moves.
+    fn trace_size(&self) -> (u32, u32) {
+        let scale = match self.motion_frames_left > 0 {
+            true => self.scene.render_param.motion_scale.clamp(0.1, 1.0),
+            false => 1.0,
+        };
+        let scaled = |length: u32| ((length as f32 * scale).round() as u32).max(1);
+        (scaled(self.size.width), scaled(self.size.height))
+    }
 
-            self.scene.render_param.update();
+    /// Samples traced per pixel by the next frame.
+    pub fn samples_per_frame(&self) -> u32 {
+        self.scene.render_param.samples_per_pixel
+    }
 
-            self.queue.write_buffer(
-                &self.render_param_buffer.handle(),
-                0,
-                bytemuck::bytes_of(&self.scene.render_param),
-            );
+    /// Reads the accumulated image back, as the average radiance of every pixel row by row
+    /// starting from the bottom one.
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub async fn read_image(&self) -> Vec<[f32; 3]> {
+        let readback: BufferReadback<u32> =
+            BufferReadback::new(&self.device, &self.queue, self.image_buffer.handle());
+        #[cfg(not(target_arch = "wasm32"))]
+        self.device.poll(wgpu::Maintain::Wait);
+
+        let format = AccumulationFormat::from(self.scene.render_param.accumulation_format);
+        match readback.await {
+            Ok(words) => format.decode(&words),
+            Err(e) => {
+                log::warn!("Image readback failed: {e}");
+                Vec::new()
+            }
         }
+    }
 
-        let output = self.surface.get_current_texture()?;
+    /// The accumulated image row by row from the top, as image files want it.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read_image_rows(&self) -> Vec<[f32; 3]> {
+        let pixels = pollster::block_on(self.read_image());
+        pixels
+            .chunks(self.size.width as usize)
+            .rev()
+            .flatten()
+            .copied()
+            .collect()
+    }
 
-        let view = output.texture.create_view(&wgpu::TextureViewDescriptor {
-            label: Some("View"),
-            format: Some(self.config.format),
-            dimension: Some(wgpu::TextureViewDimension::D2),
-            aspect: wgpu::TextureAspect::All,
-            base_mip_level: 0,
-            mip_level_count: None,
-            base_array_layer: 0,
-            array_layer_count: None,
+    /// Writes the accumulated radiance to a Radiance `.hdr` file, without any tonemapping.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_hdr(&self, path: &std::path::Path) -> std::io::Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        crate::utils::write_hdr(
+            file,
+            (self.size.width, self.size.height),
+            &self.read_image_rows(),
+        )
+    }
+
+    /// Renders a frame and writes it as shown on screen to an 8-bit `.png` file, with the
+    /// egui panels only if `include_ui`. Surfaces that can't be copied from save the
+    /// accumulated image instead, without post effects nor panels.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_png(&mut self, path: &std::path::Path, include_ui: bool) -> std::io::Result<()> {
+        let pixels = match self.config.usage.contains(wgpu::TextureUsages::COPY_SRC) {
+            true => self.capture_frame(include_ui)?,
+            false => None,
+        };
+        let pixels = pixels.unwrap_or_else(|| {
+            log::warn!("Can't copy from the surface, saving the accumulated image");
+            self.read_image_rows()
         });
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        crate::utils::write_png(file, (self.size.width, self.size.height), &pixels)
+    }
 
-        encoder.insert_debug_marker("Render Pass");
+    /// Renders a frame and reads the surface back, as linear colors row by row from the top.
+    /// None for surface formats other than 8-bit RGBA and BGRA.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn capture_frame(&mut self, include_ui: bool) -> std::io::Result<Option<Vec<[f32; 3]>>> {
+        let channels = match self.config.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => [0, 1, 2],
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => [2, 1, 0],
+            _ => return Ok(None),
+        };
+        self.screenshot = Some(Screenshot {
+            include_ui,
+            capture: None,
+        });
+        let rendered = self.render();
+        let capture = self
+            .screenshot
+            .take()
+            .and_then(|screenshot| screenshot.capture);
+        rendered.map_err(std::io::Error::other)?;
+        let (buffer, bytes_per_row) =
+            capture.ok_or_else(|| std::io::Error::other("the frame was not captured"))?;
+
+        let readback: BufferReadback<u8> = BufferReadback::new(&self.device, &self.queue, &buffer);
+        self.device.poll(wgpu::Maintain::Wait);
+        let bytes = pollster::block_on(readback).map_err(std::io::Error::other)?;
+
+        // Both kinds of surfaces hold sRGB encoded colors, the shader encodes them itself
+        // for the non sRGB ones.
+        let linear = |c: u8| {
+            let c = c as f32 / 255.0;
+            match c <= 0.04045 {
+                true => c / 12.92,
+                false => ((c + 0.055) / 1.055).powf(2.4),
+            }
+        };
+        let row_pixels = self.config.width as usize;
+        Ok(Some(
+            bytes
+                .chunks(bytes_per_row as usize)
+                .flat_map(|row| row[..4 * row_pixels].chunks_exact(4))
+                .map(|pixel| channels.map(|c| linear(pixel[c])))
+                .collect(),
+        ))
+    }
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.012,
-                            g: 0.012,
-                            b: 0.012,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
+    /// Blocks until the GPU finished all submitted work.
+    /// Moves the camera, the accumulated samples are dropped before the next frame even if
+    /// it did not move.
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub fn set_camera(&mut self, camera: crate::scene::Camera) {
+        self.camera_dirty = true;
+        self.scene.camera = camera;
+    }
+
+    /// Every sample up to the max per pixel is in the image, later frames add nothing.
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub fn is_accumulation_complete(&self) -> bool {
+        let render_param = &self.scene.render_param;
+        render_param.total_samples > render_param.samples_max_per_pixel
+    }
+
+    /// Fraction of the max samples per pixel accumulated so far, 1.0 once the cap is reached.
+    pub fn progress(&self) -> f32 {
+        let render_param = &self.scene.render_param;
+        match render_param.samples_max_per_pixel {
+            0 => 1.0,
+            max => (render_param.total_samples as f32 / max as f32).min(1.0),
+        }
+    }
+
+    pub fn wait_idle(&self) {
+        self.device.poll(wgpu::Maintain::Wait);
+    }
+
+    pub fn device_event(&mut self, event: &DeviceEvent, mouse_pressed: bool) {
+        self.scene
+            .camera_controller
+            .handle_mouse(event, mouse_pressed);
+    }
+
+    /// Keeps the previous buffers when the scene no longer fits, it is drawn as it was before.
+    fn rebuild_scene(&mut self) {
+        match create_scene_buffers(&self.device, &self.scene_bind_group_layout, &self.scene) {
+            Ok(scene_buffers) => self.scene_buffers = scene_buffers,
+            Err(e) => log::error!("Can't upload the scene: {e}"),
+        }
+        self.scene.render_param.reset_accumulation();
+        self.scene.frame_data.index = 0;
+        self.stats = None;
+    }
+
+    /// Adds a grey sphere at the focus point of the camera and selects it.
+    fn add_sphere(&mut self) {
+        let camera = &self.scene.camera;
+        let center = camera.eye_pos + camera.focus_distance * glm::normalize(&camera.eye_dir);
+        let radius = 0.1 * camera.focus_distance;
+        let id = self.scene.add_sphere(
+            center,
+            radius,
+            Material::Lambertian {
+                albedo: Texture::new_from_color(glm::vec3(0.5, 0.5, 0.5)),
+            },
+        );
+        self.selected = Some(id);
+        self.selected_material = self.scene.materials.len() - 1;
+        self.rebuild_scene();
+    }
+
+    /// Writes the materials and their textures over the current buffers, only rebuilding the
+    /// whole scene when the texture data changed size.
+    fn upload_materials(&mut self) {
+        let (material_data, texture_data) =
+            GpuMaterial::from_materials(&self.scene.materials, &self.scene.background);
+        let material_bytes: &[u8] = bytemuck::cast_slice(material_data.as_slice());
+        let texture_bytes: &[u8] = bytemuck::cast_slice(texture_data.as_slice());
+
+        if material_bytes.len() as u64 != self.scene_buffers.materials.handle().size()
+            || texture_bytes.len() as u64 != self.scene_buffers.textures.handle().size()
+        {
+            self.rebuild_scene();
+            return;
+        }
+
+        self.queue
+            .write_buffer(self.scene_buffers.materials.handle(), 0, material_bytes);
+        self.queue
+            .write_buffer(self.scene_buffers.textures.handle(), 0, texture_bytes);
+        self.scene.render_param.reset_accumulation();
+        self.scene.frame_data.index = 0;
+    }
+
+    /// Writes the lights over the current buffer, rebuilding the scene when their count
+    /// changed.
+    fn upload_lights(&mut self) {
+        let lights = self.scene.gpu_lights();
+        let light_bytes: &[u8] = bytemuck::cast_slice(lights.as_slice());
+        if light_bytes.len() as u64 != self.scene_buffers.lights.handle().size() {
+            self.rebuild_scene();
+            return;
+        }
+        self.queue
+            .write_buffer(self.scene_buffers.lights.handle(), 0, light_bytes);
+        self.scene.render_param.reset_accumulation();
+        self.scene.frame_data.index = 0;
+    }
+
+    pub fn update(&mut self, dt: std::time::Duration) {
+        #[cfg(feature = "gamepad")]
+        if let Some(gamepads) = self.gamepads.as_mut() {
+            self.scene
+                .camera_controller
+                .process_gamepad(gamepads.poll());
+        }
+        let moved = self
+            .scene
+            .camera_controller
+            .update_camera(&mut self.scene.camera, dt);
+        self.camera_dirty |= moved;
+
+        if self.playing_path {
+            self.path_time += dt.as_secs_f32();
+            if self.path_time >= self.camera_path.duration() {
+                self.path_time = self.camera_path.duration();
+                self.playing_path = false;
+            }
+            self.set_camera_from_path();
+        }
+
+        let trace_size = self.trace_size();
+        self.motion_frames_left = match moved || self.playing_path {
+            true => MOTION_IDLE_FRAMES,
+            false => self.motion_frames_left.saturating_sub(1),
+        };
+        // The accumulated pixels are laid out for the previous size.
+        let resized = self.trace_size() != trace_size;
+
+        // Material changes reset through `upload_materials`.
+        let view_changed = std::mem::take(&mut self.camera_dirty) | resized;
+        if std::mem::take(&mut self.geometry_dirty) || view_changed && !self.reproject_image() {
+            self.scene.render_param.reset_accumulation();
+        }
+        // The post pass reads the params every frame, there is nothing to drop.
+        self.post_dirty = false;
+    }
+
+    /// Rebuilds the render pipeline if the shader changed on disk. A shader that fails to
+    /// compile is logged and the current pipeline kept.
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    fn reload_shader(&mut self) {
+        if !self.shader_watcher.as_ref().is_some_and(|w| w.changed()) {
+            return;
+        }
+        let source = match std::fs::read_to_string(SHADER_PATH) {
+            Ok(source) => source,
+            Err(e) => {
+                log::error!("Can't read {SHADER_PATH}: {e}");
+                return;
+            }
+        };
+
+        // Validation errors would otherwise go to the uncaptured error handler and panic.
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader = self
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(SHADER_PATH),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let render_pipeline =
+            create_render_pipeline(&self.device, &self.render_pipeline_layout, &shader);
+        match pollster::block_on(self.device.pop_error_scope()) {
+            Some(e) => log::error!("Shader reload failed: {e}"),
+            None => {
+                log::info!("Reloaded {SHADER_PATH}");
+                self.render_pipeline = render_pipeline;
+                self.scene.render_param.reset_accumulation();
+                self.scene.frame_data.index = 0;
+            }
+        }
+    }
+
+    /// Reloads the scene from `path` whenever it changes on disk.
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    pub fn watch_scene_file(&mut self, path: &std::path::Path) {
+        match crate::utils::FileWatcher::new(path) {
+            Ok(watcher) => {
+                self.scene_watcher = Some(SceneWatcher {
+                    path: path.to_owned(),
+                    watcher,
+                    loaded_camera: self.scene.camera,
+                })
+            }
+            Err(e) => log::warn!("Can't watch {}: {e}", path.display()),
+        }
+    }
+
+    /// Replaces the scene by the watched file if it changed. A file that fails to load is
+    /// logged and the current scene kept, so is the camera unless the file moved it.
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    fn reload_scene(&mut self) {
+        let Some(scene_watcher) = &mut self.scene_watcher else {
+            return;
+        };
+        if !scene_watcher.watcher.changed() {
+            return;
+        }
+
+        let scene = Scene::load(
+            &scene_watcher.path,
+            self.scene.render_param,
+            self.scene.frame_data,
+        )
+        .and_then(|scene| scene.validate().map(|()| scene));
+        let mut scene = match scene {
+            Ok(scene) => scene,
+            Err(e) => {
+                log::error!("Scene reload failed: {e}");
+                return;
+            }
+        };
+        log::info!("Reloaded {}", scene_watcher.path.display());
+
+        let file_camera = scene.camera;
+        if file_camera == scene_watcher.loaded_camera {
+            scene.camera = self.scene.camera;
+            scene.camera_controller = self.scene.camera_controller;
+        }
+        scene_watcher.loaded_camera = file_camera;
+        self.initial_camera = file_camera;
+
+        self.scene = scene;
+        self.selected = None;
+        self.selected_material = 0;
+        self.rebuild_scene();
+    }
+
+    /// One iteration of the frame loop: moves the camera by `dt`, then renders and presents.
+    /// A lost or outdated surface is reconfigured and the frame skipped, other errors are
+    /// left to the caller.
+    pub fn redraw(&mut self, dt: std::time::Duration) -> Result<(), wgpu::SurfaceError> {
+        self.fps = 1.0 / dt.as_secs_f64();
+        self.frame_times.push(dt);
+        self.update(dt);
+        match self.render() {
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.reconfigure_surface();
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+        {
+            self.reload_shader();
+            self.reload_scene();
+        }
+
+        // On wasm, resizing the browser window typically does NOT emit a reliable `WindowEvent::Resized`.
+        // Instead, keep the canvas backing resolution in sync with its CSS size.
+        #[cfg(target_arch = "wasm32")]
+        {
+            use winit::platform::web::WindowExtWebSys;
+
+            let canvas = self.window.canvas().unwrap();
+
+            let css_width = canvas.client_width().max(1) as f64;
+            let css_height = canvas.client_height().max(1) as f64;
+            let dpr = web_sys::window()
+                .map(|w| w.device_pixel_ratio())
+                .unwrap_or(1.0)
+                .max(1.0);
+
+            let new_width = (css_width * dpr).round().max(1.0) as u32;
+            let new_height = (css_height * dpr).round().max(1.0) as u32;
+
+            if canvas.width() != new_width || canvas.height() != new_height {
+                canvas.set_width(new_width);
+                canvas.set_height(new_height);
+                self.resize(winit::dpi::PhysicalSize::new(new_width, new_height));
+            }
+        }
+
+        if std::mem::take(&mut self.materials_dirty) {
+            self.upload_materials();
+        }
+        if std::mem::take(&mut self.lights_dirty) {
+            self.upload_lights();
+        }
+
+        let passes;
+        {
+            let camera = GpuCamera::new(&self.scene.camera, (self.size.width, self.size.height));
+
+            self.queue
+                .write_buffer(self.camera_buffer.handle(), 0, bytemuck::bytes_of(&camera));
+            self.traced_camera = camera;
+
+            (self.scene.frame_data.width, self.scene.frame_data.height) = self.trace_size();
+            self.scene.frame_data.index += 1;
+
+            self.queue.write_buffer(
+                self.frame_data_buffer.handle(),
+                0,
+                bytemuck::bytes_of(&self.scene.frame_data),
+            );
+
+            // While paused the pass adds no samples and only displays the average, unless it
+            // were just discarded and there is nothing to display.
+            let render_param = match self.paused && self.scene.render_param.total_samples > 0 {
+                true => RenderParam {
+                    samples_per_pixel: 0,
+                    clear_samples: 0,
+                    ..self.scene.render_param
+                },
+                false => {
+                    self.scene.render_param.update();
+                    self.scene.render_param
+                }
+            };
+            passes = render_param.passes();
+
+            self.queue.write_buffer(
+                self.scene_param_buffer.handle(),
+                0,
+                bytemuck::bytes_of(&GpuSceneParam::new(&self.scene)),
+            );
+        }
+
+        let output = self.surface.get_current_texture()?;
+
+        let view = output.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("View"),
+            format: Some(self.config.format),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            aspect: wgpu::TextureAspect::All,
+            base_mip_level: 0,
+            mip_level_count: None,
+            base_array_layer: 0,
+            array_layer_count: None,
+        });
+        // Every pass and every tile but the last is submitted on its own, so that no single
+        // submission runs long enough to trip the GPU watchdog. Each submission sees the
+        // parameters of its pass written right before it.
+        let (trace_width, trace_height) = self.trace_size();
+        let tiles = tiles(
+            (trace_width, trace_height),
+            self.scene.render_param.tile_size,
+        );
+        let mut encoder: Option<wgpu::CommandEncoder> = None;
+        let tiles = passes.iter().enumerate().flat_map(|(pass, render_param)| {
+            let tiles = tiles.iter().enumerate();
+            tiles.map(move |(i, tile)| (pass, render_param, i, tile))
+        });
+        for (j, (pass, render_param, i, &(x, y, width, height))) in tiles.enumerate() {
+            if let Some(encoder) = encoder.take() {
+                self.queue.submit(std::iter::once(encoder.finish()));
+            }
+            if i == 0 {
+                // A new frame index gives every pass its own random numbers.
+                if pass > 0 {
+                    self.scene.frame_data.index += 1;
+                    self.queue.write_buffer(
+                        self.frame_data_buffer.handle(),
+                        0,
+                        bytemuck::bytes_of(&self.scene.frame_data),
+                    );
+                }
+                self.queue.write_buffer(
+                    self.render_param_buffer.handle(),
+                    0,
+                    bytemuck::bytes_of(render_param),
+                );
+            }
+            let tile_encoder = encoder.insert(self.device.create_command_encoder(
+                &wgpu::CommandEncoderDescriptor {
+                    label: Some("Render Encoder"),
+                },
+            ));
+
+            tile_encoder.insert_debug_marker("Render Pass");
+
+            let mut render_pass = tile_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: self.post_process.radiance_view(),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: match j {
+                            0 => wgpu::LoadOp::Clear(wgpu::Color {
+                                r: 0.012,
+                                g: 0.012,
+                                b: 0.012,
+                                a: 1.0,
+                            }),
+                            _ => wgpu::LoadOp::Load,
+                        },
+                        store: wgpu::StoreOp::Store,
                     },
                 })],
                 depth_stencil_attachment: None,
@@ -583,15 +1726,54 @@ impl<'a> RenderContext<'a> {
                 timestamp_writes: None,
             });
 
+            render_pass.set_viewport(0.0, 0.0, trace_width as f32, trace_height as f32, 0.0, 1.0);
+            render_pass.set_scissor_rect(x, y, width, height);
             render_pass.set_pipeline(&self.render_pipeline);
             render_pass.set_bind_group(0, &self.image_bind_group, &[]);
-            render_pass.set_bind_group(1, &self.scene_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.scene_buffers.bind_group, &[]);
             render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
             render_pass.draw(0..VERTICES_LEN as u32, 0..1);
         }
+        let mut encoder = encoder.expect("a frame has at least one tile");
+        self.post_process.render(
+            &self.queue,
+            &mut encoder,
+            &self.vertex_buffer,
+            &view,
+            &self.scene.render_param,
+            [
+                trace_width as f32 / self.size.width as f32,
+                trace_height as f32 / self.size.height as f32,
+            ],
+        );
+        if let Some(wireframe) = self.wireframe.as_ref().filter(|_| self.show_wireframe) {
+            wireframe.render(
+                &self.device,
+                &mut encoder,
+                &view,
+                &self.camera_buffer,
+                &self.scene_buffers.surfaces,
+                self.scene.object_list.meshes.len() as u32,
+            );
+        }
+        if self.show_grid || self.show_axes {
+            self.gizmo.render(
+                &self.device,
+                &mut encoder,
+                &view,
+                &self.camera_buffer,
+                self.show_grid.then_some(self.grid_spacing),
+                self.show_axes
+                    .then_some(GIZMO_AXIS_CELLS * self.grid_spacing),
+            );
+        }
+        // Everything but the egui panels.
+        if let Some(screenshot) = self.screenshot.as_mut().filter(|s| !s.include_ui) {
+            screenshot.capture = Some(capture_texture(&self.device, &mut encoder, &output.texture));
+        }
 
-        {
-            self.egui_renderer.begin_frame(&self.window);
+        if self.show_ui {
+            self.egui_renderer.begin_frame(self.window);
             let ctx = self.egui_renderer.context().clone();
 
             egui::SidePanel::left("left_panel")
@@ -613,7 +1795,10 @@ impl<'a> RenderContext<'a> {
                                 "Max samples: {}",
                                 self.scene.render_param.samples_max_per_pixel
                             ));
+                            ui.add(egui::ProgressBar::new(self.progress()).show_percentage());
                             ui.label(format!("FPS: {:.2}", self.fps));
+                            self.frame_times_ui(ui);
+                            ui.checkbox(&mut self.paused, "Paused (K)");
 
                             ui.separator();
 
@@ -646,6 +1831,9 @@ impl<'a> RenderContext<'a> {
                                     ui.separator();
 
                                     ui.label("Right Mouse Button + Move: Rotate camera");
+                                    ui.label("Mouse Wheel: Zoom (field of view)");
+                                    ui.label("Ctrl + Mouse Wheel: Focus distance");
+                                    ui.label("Left Click: Select object");
 
                                     ui.separator();
                                     ui.heading("Keyboard Controls");
@@ -657,6 +1845,18 @@ impl<'a> RenderContext<'a> {
                                     ui.label("D / Arrow Right: Move right");
                                     ui.label("Space: Move up");
                                     ui.label("Shift: Move down");
+                                    ui.label("Q / E: Roll left / right");
+                                    ui.label("K: Pause / resume rendering");
+                                    #[cfg(not(target_arch = "wasm32"))]
+                                    ui.label(format!("H: Save the radiance to {HDR_PATH}"));
+                                    #[cfg(not(target_arch = "wasm32"))]
+                                    {
+                                        ui.label(format!("P: Save a screenshot to {PNG_PATH}"));
+                                        ui.checkbox(
+                                            &mut self.screenshot_ui,
+                                            "Screenshots include the UI",
+                                        );
+                                    }
 
                                     ui.separator();
                                     ui.heading("Window Status");
@@ -680,6 +1880,28 @@ impl<'a> RenderContext<'a> {
 
                             ui.separator();
 
+                            egui::CollapsingHeader::new("Device")
+                                .default_open(false)
+                                .show(ui, |ui| {
+                                    let info = &self.adapter_info;
+                                    egui::Grid::new("device").show(ui, |ui| {
+                                        for (label, value) in [
+                                            ("Name", info.name.clone()),
+                                            ("Type", format!("{:?}", info.device_type)),
+                                            ("Backend", info.backend.to_string()),
+                                            ("Driver", info.driver.clone()),
+                                            ("Driver info", info.driver_info.clone()),
+                                            ("Surface format", format!("{:?}", self.config.format)),
+                                        ] {
+                                            ui.label(label);
+                                            ui.label(value);
+                                            ui.end_row();
+                                        }
+                                    });
+                                });
+
+                            ui.separator();
+
                             egui::CollapsingHeader::new("Params")
                                 .default_open(false)
                                 .show(ui, |ui| {
@@ -699,11 +1921,17 @@ impl<'a> RenderContext<'a> {
                                                     )
                                                     .clicked()
                                                 {
+                                                    let mut render_param =
+                                                        self.scene.render_param;
+                                                    render_param.apply_scene_defaults(scene_desc);
                                                     let new_scene = (scene_desc.creator)(
-                                                        self.scene.render_param.clone(),
-                                                        self.scene.frame_data.clone(),
+                                                        render_param,
+                                                        self.scene.frame_data,
                                                     );
+                                                    self.initial_camera = new_scene.camera;
                                                     self.scene = new_scene;
+                                                    self.selected = None;
+                                                    self.selected_material = 0;
                                                     self.rebuild_scene();
                                                 }
                                             }
@@ -711,36 +1939,393 @@ impl<'a> RenderContext<'a> {
 
                                     ui.separator();
 
+                                    let present_mode = self.config.present_mode;
+                                    egui::ComboBox::from_label("Present mode")
+                                        .selected_text(format!("{:?}", present_mode))
+                                        .show_ui(ui, |ui| {
+                                            for mode in &self.present_modes {
+                                                ui.selectable_value(
+                                                    &mut self.config.present_mode,
+                                                    *mode,
+                                                    format!("{:?}", mode),
+                                                );
+                                            }
+                                        });
+                                    if self.config.present_mode != present_mode {
+                                        self.surface_dirty = true;
+                                    }
+
+                                    ui.separator();
+
                                     ui.label("Max samples per pixel:");
                                     ui.add(egui::Slider::new(
                                         &mut self.scene.render_param.samples_max_per_pixel,
                                         1..=10000,
                                     ));
 
-                                    ui.separator();
+                                    ui.label("Samples per frame:");
+                                    ui.add(egui::Slider::new(
+                                        &mut self.scene.render_param.requested_samples_per_pixel,
+                                        1..=64,
+                                    ));
 
-                                    ui.label("Max depth:");
+                                    ui.label("Samples per pass (0 is the whole frame):");
                                     ui.add(egui::Slider::new(
-                                        &mut self.scene.render_param.max_depth,
-                                        1..=100,
+                                        &mut self.scene.render_param.samples_per_pass,
+                                        0..=64,
+                                    ))
+                                    .on_hover_text(
+                                        "Splits frames in passes submitted one after the other, so that many samples per frame don't stall slow GPUs",
+                                    );
+
+                                    ui.label("Pause when converged (0 disables):");
+                                    ui.add(egui::Slider::new(
+                                        &mut self.scene.render_param.convergence_epsilon,
+                                        0.0..=0.05,
                                     ));
 
                                     ui.separator();
 
-                                    ui.label("Field of view:");
+                                    let tile_size = &mut self.scene.render_param.tile_size;
+                                    let tile_label = |size: u32| match size {
+                                        0 => "Whole frame".to_string(),
+                                        size => format!("{size}x{size}"),
+                                    };
+                                    egui::ComboBox::from_label("Tiles")
+                                        .selected_text(tile_label(*tile_size))
+                                        .show_ui(ui, |ui| {
+                                            for size in [0, 128, 256, 512, 1024] {
+                                                ui.selectable_value(
+                                                    tile_size,
+                                                    size,
+                                                    tile_label(size),
+                                                );
+                                            }
+                                        })
+                                        .response
+                                        .on_hover_text(
+                                            "Smaller tiles keep each GPU submission short at high resolutions",
+                                        );
+
+                                    ui.label("Resolution while moving:");
                                     ui.add(egui::Slider::new(
-                                        &mut self.scene.camera.vfov,
-                                        2.0..=179.0,
+                                        &mut self.scene.render_param.motion_scale,
+                                        0.25..=1.0,
                                     ));
 
                                     ui.separator();
 
+                                    ui.checkbox(
+                                        &mut self.keep_samples_on_resize,
+                                        "Keep samples on resize",
+                                    );
+
+                                    let mut half = AccumulationFormat::from(
+                                        self.scene.render_param.accumulation_format,
+                                    ) == AccumulationFormat::F16;
+                                    let response = ui
+                                        .checkbox(&mut half, "Half precision accumulation")
+                                        .on_hover_text(
+                                            "Uses 8 instead of 24 bytes per pixel, but noise stops going down after a couple thousand samples, and fireflies can't be rejected nor samples reprojected",
+                                        );
+                                    if response.changed() {
+                                        let format = match half {
+                                            true => AccumulationFormat::F16,
+                                            false => AccumulationFormat::F32,
+                                        };
+                                        self.scene.render_param.accumulation_format =
+                                            format as u32;
+                                        self.recreate_image_buffer(self.size, false);
+                                    }
+                                    ui.add_enabled_ui(!half, |ui| {
+                                        ui.label("Firefly rejection (sigma):").on_hover_text(
+                                            "Leaves out frames this many standard deviations away from the pixel average, 0 disables it",
+                                        );
+                                        self.geometry_dirty |= ui
+                                            .add(egui::Slider::new(
+                                                &mut self.scene.render_param.firefly_sigma,
+                                                0.0..=10.0,
+                                            ))
+                                            .changed();
+                                        let mut taa = self.scene.render_param.taa == 1;
+                                        if ui
+                                            .checkbox(&mut taa, "Temporal reprojection")
+                                            .on_hover_text(
+                                                "Keeps the samples that are still in view when the camera moves instead of starting over",
+                                            )
+                                            .changed()
+                                        {
+                                            self.scene.render_param.taa = taa as u32;
+                                        }
+                                    });
+
+                                    ui.separator();
+
+                                    let mut cull_backfaces =
+                                        self.scene.render_param.cull_backfaces == 1;
+                                    if ui
+                                        .checkbox(&mut cull_backfaces, "Cull backfaces")
+                                        .changed()
+                                    {
+                                        self.scene.render_param.cull_backfaces =
+                                            cull_backfaces as u32;
+                                        self.geometry_dirty = true;
+                                    }
+
+                                    let mut shading =
+                                        ShadingMode::from(self.scene.render_param.shading);
+                                    ui.horizontal(|ui| {
+                                        ui.label("Shading:");
+                                        ui.radio_value(&mut shading, ShadingMode::Smooth, "Smooth");
+                                        ui.radio_value(&mut shading, ShadingMode::Flat, "Flat");
+                                    });
+                                    if self.scene.render_param.shading != shading as u32 {
+                                        self.scene.render_param.shading = shading as u32;
+                                        self.geometry_dirty = true;
+                                    }
+
+                                    ui.label("Max depth:");
+                                    self.geometry_dirty |= ui
+                                        .add(egui::Slider::new(
+                                            &mut self.scene.render_param.max_depth,
+                                            1..=100,
+                                        ))
+                                        .changed();
+                                    ui.label("Min depth:");
+                                    self.geometry_dirty |= ui
+                                        .add(egui::Slider::new(
+                                            &mut self.scene.render_param.min_depth,
+                                            0..=self.scene.render_param.max_depth,
+                                        ))
+                                        .changed();
+                                    ui.label("Ray epsilon:")
+                                        .on_hover_text("Offset of rays leaving a surface");
+                                    self.geometry_dirty |= ui
+                                        .add(
+                                            egui::Slider::new(
+                                                &mut self.scene.render_param.ray_epsilon,
+                                                1e-6..=1e-1,
+                                            )
+                                            .logarithmic(true),
+                                        )
+                                        .changed();
+
+                                    ui.separator();
+
+                                    let mut sampler =
+                                        SamplerKind::from(self.scene.render_param.sampler);
+                                    egui::ComboBox::from_label("Sampler")
+                                        .selected_text(format!("{:?}", sampler))
+                                        .show_ui(ui, |ui| {
+                                            for kind in
+                                                [SamplerKind::Random, SamplerKind::Stratified]
+                                            {
+                                                ui.selectable_value(
+                                                    &mut sampler,
+                                                    kind,
+                                                    format!("{:?}", kind),
+                                                );
+                                            }
+                                        });
+                                    self.scene.render_param.sampler = sampler as u32;
+
+                                    ui.separator();
+
+                                    let mut debug_view =
+                                        DebugView::from(self.scene.render_param.debug_view);
+                                    egui::ComboBox::from_label("View")
+                                        .selected_text(format!("{:?}", debug_view))
+                                        .show_ui(ui, |ui| {
+                                            for view in DebugView::ALL {
+                                                ui.selectable_value(
+                                                    &mut debug_view,
+                                                    view,
+                                                    format!("{:?}", view),
+                                                );
+                                            }
+                                        });
+                                    if self.scene.render_param.debug_view != debug_view as u32 {
+                                        self.scene.render_param.debug_view = debug_view as u32;
+                                        self.geometry_dirty = true;
+                                    }
+                                    if self.wireframe.is_some() {
+                                        ui.checkbox(&mut self.show_wireframe, "Wireframe overlay");
+                                    }
+                                    ui.horizontal(|ui| {
+                                        ui.checkbox(&mut self.show_grid, "Grid");
+                                        ui.checkbox(&mut self.show_axes, "Axes");
+                                    });
+                                    if self.show_grid || self.show_axes {
+                                        ui.label("Grid spacing:");
+                                        ui.add(
+                                            egui::Slider::new(&mut self.grid_spacing, 0.01..=100.0)
+                                                .logarithmic(true),
+                                        );
+                                    }
+                                    if debug_view == DebugView::AmbientOcclusion {
+                                        ui.label("Occlusion radius:");
+                                        self.geometry_dirty |= ui
+                                            .add(
+                                                egui::Slider::new(
+                                                    &mut self.scene.render_param.ao_radius,
+                                                    0.01..=10.0,
+                                                )
+                                                .logarithmic(true),
+                                            )
+                                            .changed();
+                                        ui.label("Occlusion rays:");
+                                        self.geometry_dirty |= ui
+                                            .add(egui::Slider::new(
+                                                &mut self.scene.render_param.ao_samples,
+                                                1..=64,
+                                            ))
+                                            .changed();
+                                    }
+
+                                    ui.separator();
+
+                                    // Post effects only change what is shown, see `post_dirty`.
+                                    ui.label("Bloom:");
+                                    self.post_dirty |= ui
+                                        .add(egui::Slider::new(
+                                            &mut self.scene.render_param.bloom_intensity,
+                                            0.0..=1.0,
+                                        ))
+                                        .changed();
+                                    ui.label("Vignette:");
+                                    self.post_dirty |= ui
+                                        .add(egui::Slider::new(
+                                            &mut self.scene.render_param.vignette,
+                                            0.0..=1.0,
+                                        ))
+                                        .changed();
+                                    ui.label("Contrast:");
+                                    self.post_dirty |= ui
+                                        .add(egui::Slider::new(
+                                            &mut self.scene.render_param.contrast,
+                                            0.5..=2.0,
+                                        ))
+                                        .changed();
+                                    ui.label("Saturation:");
+                                    self.post_dirty |= ui
+                                        .add(egui::Slider::new(
+                                            &mut self.scene.render_param.saturation,
+                                            0.0..=2.0,
+                                        ))
+                                        .changed();
+                                    ui.label("White balance:");
+                                    self.post_dirty |= ui
+                                        .add(
+                                            egui::Slider::new(
+                                                &mut self.scene.render_param.white_balance_k,
+                                                2000.0..=10000.0,
+                                            )
+                                            .suffix(" K"),
+                                        )
+                                        .changed();
+
+                                    ui.separator();
+
+                                    // The camera widgets edit it in place, compare once they are done.
+                                    let camera = self.scene.camera;
+
+                                    if !self.scene.camera_presets.is_empty() {
+                                        let presets = &self.scene.camera_presets;
+                                        let mut selected = None;
+                                        egui::ComboBox::from_label("Camera preset")
+                                            .selected_text(
+                                                presets
+                                                    .iter()
+                                                    .find(|(_, preset)| *preset == camera)
+                                                    .map_or("Custom", |(name, _)| name.as_str()),
+                                            )
+                                            .show_ui(ui, |ui| {
+                                                for (name, preset) in presets {
+                                                    if ui.selectable_label(false, name).clicked() {
+                                                        selected = Some(*preset);
+                                                    }
+                                                }
+                                            });
+                                        if let Some(preset) = selected {
+                                            self.scene.camera = preset;
+                                        }
+                                        ui.separator();
+                                    }
+
+                                    ui.label("Projection:");
+                                    let mut orthographic = matches!(
+                                        self.scene.camera.projection,
+                                        CameraProjection::Orthographic { .. }
+                                    );
+                                    ui.horizontal(|ui| {
+                                        ui.radio_value(&mut orthographic, false, "Perspective");
+                                        ui.radio_value(&mut orthographic, true, "Orthographic");
+                                    });
+                                    self.scene.camera.set_orthographic(orthographic);
+
+                                    ui.separator();
+
+                                    match &mut self.scene.camera.projection {
+                                        CameraProjection::Perspective { vfov } => {
+                                            ui.label("Field of view:");
+                                            ui.add(egui::Slider::new(vfov, 2.0..=179.0));
+                                        }
+                                        CameraProjection::Orthographic { height } => {
+                                            ui.label("View height:");
+                                            ui.add(
+                                                egui::Slider::new(height, 0.01..=100.0)
+                                                    .logarithmic(true),
+                                            );
+                                        }
+                                    }
+
+                                    ui.separator();
+
                                     ui.label("Aperture:");
                                     ui.add(egui::Slider::new(
                                         &mut self.scene.camera.aperture,
                                         0.0..=1.0,
                                     ));
 
+                                    let bokeh = &mut self.scene.camera.bokeh;
+                                    egui::ComboBox::from_label("Bokeh")
+                                        .selected_text(match *bokeh {
+                                            BokehShape::Blade(_) => "Blades".to_string(),
+                                            shape => format!("{:?}", shape),
+                                        })
+                                        .show_ui(ui, |ui| {
+                                            for shape in [
+                                                BokehShape::Circle,
+                                                BokehShape::Hexagon,
+                                                BokehShape::Pentagon,
+                                            ] {
+                                                ui.selectable_value(
+                                                    bokeh,
+                                                    shape,
+                                                    format!("{:?}", shape),
+                                                );
+                                            }
+                                            let blades = matches!(bokeh, BokehShape::Blade(_));
+                                            if ui.selectable_label(blades, "Blades").clicked()
+                                                && !blades
+                                            {
+                                                *bokeh = BokehShape::Blade(7);
+                                            }
+                                        });
+                                    if let BokehShape::Blade(n) = bokeh {
+                                        ui.add(egui::Slider::new(n, 3..=12).text("blades"));
+                                    }
+
+                                    ui.separator();
+
+                                    ui.label("Shutter:").on_hover_text(
+                                        "How long the shutter stays open, moving spheres are blurred along their velocity",
+                                    );
+                                    ui.add(egui::Slider::new(
+                                        &mut self.scene.camera.shutter,
+                                        0.0..=1.0,
+                                    ));
+
                                     ui.separator();
 
                                     ui.label("Focus distance:");
@@ -751,6 +2336,7 @@ impl<'a> RenderContext<'a> {
                                         )
                                         .step_by(0.1),
                                     );
+                                    ui.checkbox(&mut self.pick_focus, "Pick focus on click");
 
                                     ui.separator();
 
@@ -764,6 +2350,189 @@ impl<'a> RenderContext<'a> {
                                         self.scene.camera.eye_pos
                                     ));
                                     ui.label(format!("Up vector: {:?}", self.scene.camera.up));
+                                    if ui
+                                        .button("Frame scene")
+                                        .on_hover_text(
+                                            "Move the camera back until the whole scene fits",
+                                        )
+                                        .clicked()
+                                    {
+                                        let bounds = self.scene.bounds();
+                                        let center = self.scene.framing_center();
+                                        self.scene.camera.frame_bounds(&bounds, center);
+                                    }
+                                    if ui
+                                        .button("Reset camera")
+                                        .on_hover_text("Back to the camera the scene started with (R)")
+                                        .clicked()
+                                    {
+                                        self.reset_camera();
+                                    }
+
+                                    self.camera_dirty |= self.scene.camera != camera;
+
+                                    ui.separator();
+
+                                    // Only how the camera reacts to input, the image stays.
+                                    let controller = &mut self.scene.camera_controller;
+                                    ui.label("Movement speed:");
+                                    ui.add(
+                                        egui::Slider::new(&mut controller.speed, 0.1..=50.0)
+                                            .logarithmic(true),
+                                    );
+                                    ui.label("Look sensitivity:");
+                                    ui.add(egui::Slider::new(
+                                        &mut controller.sensitivity,
+                                        0.05..=2.0,
+                                    ));
+
+                                    #[cfg(not(target_arch = "wasm32"))]
+                                    if let Some(file) = &self.settings_file {
+                                        if ui
+                                            .button("Save settings")
+                                            .on_hover_text(file.display().to_string())
+                                            .clicked()
+                                        {
+                                            match crate::scene::Settings::from_scene(&self.scene).save(file) {
+                                                Ok(()) => log::info!(
+                                                    "Saved the settings to {}",
+                                                    file.display()
+                                                ),
+                                                Err(e) => {
+                                                    log::error!("Failed to save the settings: {e}")
+                                                }
+                                            }
+                                        }
+                                    }
+                                });
+
+                            ui.separator();
+
+                            egui::CollapsingHeader::new("Camera path")
+                                .default_open(false)
+                                .show(ui, |ui| self.camera_path_ui(ui));
+
+                            ui.separator();
+
+                            egui::CollapsingHeader::new("Environment")
+                                .default_open(false)
+                                .show(ui, |ui| {
+                                    self.geometry_dirty |= self.scene.background.ui(ui);
+                                    ui.separator();
+                                    self.geometry_dirty |= Medium::ui(&mut self.scene.medium, ui);
+                                    ui.separator();
+                                    ui.horizontal(|ui| {
+                                        ui.label("Ambient:");
+                                        let mut rgb = self.scene.ambient.into();
+                                        if ui
+                                            .color_edit_button_rgb(&mut rgb)
+                                            .on_hover_text(
+                                                "Constant light added to every diffuse hit, \
+                                                 black disables it",
+                                            )
+                                            .changed()
+                                        {
+                                            self.scene.ambient = rgb.into();
+                                            self.geometry_dirty = true;
+                                        }
+                                    });
+                                });
+
+                            ui.separator();
+
+                            egui::CollapsingHeader::new("Materials")
+                                .default_open(false)
+                                .show(ui, |ui| {
+                                    egui::ComboBox::from_label("Material")
+                                        .selected_text(format!(
+                                            "{}: {}",
+                                            self.selected_material,
+                                            self.scene.materials[self.selected_material].name()
+                                        ))
+                                        .show_ui(ui, |ui| {
+                                            for (i, material) in
+                                                self.scene.materials.iter().enumerate()
+                                            {
+                                                ui.selectable_value(
+                                                    &mut self.selected_material,
+                                                    i,
+                                                    format!("{}: {}", i, material.name()),
+                                                );
+                                            }
+                                        });
+
+                                    ui.separator();
+
+                                    self.materials_dirty |=
+                                        self.scene.materials[self.selected_material].ui(ui);
+                                });
+
+                            ui.separator();
+
+                            egui::CollapsingHeader::new("Lights")
+                                .default_open(false)
+                                .show(ui, |ui| {
+                                    for i in 0..self.scene.lights.len() {
+                                        let id = self.scene.lights[i].id;
+                                        let Some(material_idx) = self.scene.object_material(id)
+                                        else {
+                                            continue;
+                                        };
+                                        ui.label(format!("Light {i}: object {id}"));
+                                        let material =
+                                            &mut self.scene.materials[material_idx as usize];
+                                        self.materials_dirty |=
+                                            ui.push_id(i, |ui| material.ui(ui)).inner;
+                                        ui.separator();
+                                    }
+
+                                    let mut removed = None;
+                                    for (i, light) in
+                                        self.scene.point_lights.iter_mut().enumerate()
+                                    {
+                                        ui.horizontal(|ui| {
+                                            ui.label(format!("Point light {i}"));
+                                            if ui.button("Remove").clicked() {
+                                                removed = Some(i);
+                                            }
+                                        });
+                                        self.lights_dirty |=
+                                            ui.push_id(("point", i), |ui| light.ui(ui)).inner;
+                                        ui.separator();
+                                    }
+                                    if let Some(i) = removed {
+                                        self.scene.point_lights.remove(i);
+                                        self.lights_dirty = true;
+                                    }
+                                    if ui
+                                        .button("Add point light")
+                                        .on_hover_text("At the camera")
+                                        .clicked()
+                                    {
+                                        self.scene.point_lights.push(PointLight {
+                                            position: self.scene.camera.eye_pos,
+                                            color: glm::vec3(1.0, 1.0, 1.0),
+                                            intensity: 10.0,
+                                        });
+                                        self.lights_dirty = true;
+                                    }
+
+                                    let can_add = self.selected.is_some_and(|id| {
+                                        self.scene.lights.iter().all(|l| l.id != id)
+                                    });
+                                    if ui
+                                        .add_enabled(
+                                            can_add,
+                                            egui::Button::new("Make selected object a light"),
+                                        )
+                                        .clicked()
+                                    {
+                                        if let Some(id) = self.selected {
+                                            if self.scene.add_light(id) {
+                                                self.rebuild_scene();
+                                            }
+                                        }
+                                    }
                                 });
 
                             ui.separator();
@@ -771,18 +2540,67 @@ impl<'a> RenderContext<'a> {
                             egui::CollapsingHeader::new("Object Scene")
                                 .default_open(false)
                                 .show(ui, |ui| {
+                                    match self.selected {
+                                        Some(id) => {
+                                            ui.horizontal(|ui| {
+                                                ui.label(format!("Selected: {}", id));
+                                                if ui.button("Deselect").clicked() {
+                                                    self.selected = None;
+                                                }
+                                                if ui.button("Delete").clicked()
+                                                    && self.scene.remove_object(id)
+                                                {
+                                                    self.selected = None;
+                                                    self.rebuild_scene();
+                                                }
+                                            });
+                                        }
+                                        None => {
+                                            ui.label("Left click an object to select it");
+                                        }
+                                    }
+                                    if ui.button("Add sphere").clicked() {
+                                        self.add_sphere();
+                                    }
+                                    ui.separator();
                                     self.scene.object_list.ui(ui);
                                 });
 
                             ui.separator();
+
+                            egui::CollapsingHeader::new("Stats")
+                                .default_open(false)
+                                .show(ui, |ui| {
+                                    let stats =
+                                        *self.stats.get_or_insert_with(|| self.scene.stats());
+                                    egui::Grid::new("stats").show(ui, |ui| {
+                                        for (label, count) in [
+                                            ("Spheres", stats.sphere_count),
+                                            ("Triangles", stats.triangle_count),
+                                            ("Materials", stats.material_count),
+                                            ("Lights", stats.light_count),
+                                            ("BVH nodes", stats.bvh_nodes),
+                                        ] {
+                                            ui.label(label);
+                                            ui.label(count.to_string());
+                                            ui.end_row();
+                                        }
+                                    });
+                                });
+
+                            ui.separator();
                         });
                 });
 
+            self.draw_selection(&ctx);
+            #[cfg(target_arch = "wasm32")]
+            self.draw_touch_controls(&ctx);
+
             self.egui_renderer.end_frame_and_draw(
                 &self.device,
                 &self.queue,
                 &mut encoder,
-                &self.window,
+                self.window,
                 &view,
                 ScreenDescriptor {
                     size_in_pixels: self.size.into(),
@@ -791,10 +2609,154 @@ impl<'a> RenderContext<'a> {
             );
         }
 
+        if let Some(screenshot) = self.screenshot.as_mut().filter(|s| s.include_ui) {
+            screenshot.capture = Some(capture_texture(&self.device, &mut encoder, &output.texture));
+        }
+
         // submit will accept anything that implements IntoIter
         self.queue.submit(std::iter::once(encoder.finish()));
         output.present();
+        if self.surface_dirty {
+            self.surface_dirty = false;
+            self.surface.configure(&self.device, &self.config);
+        }
+
+        let render_param = self.scene.render_param;
+        if render_param.convergence_epsilon > 0.0
+            && !self.paused
+            && render_param.debug_view == DebugView::Beauty as u32
+            && self.convergence.update(
+                &self.device,
+                &self.queue,
+                (&self.image_buffer, trace_width),
+                &render_param,
+            )
+        {
+            log::info!("converged at {} samples", render_param.total_samples);
+            self.paused = true;
+            self.convergence.reset();
+        }
+
+        if let Some(callback) = self.frame_callback.as_mut() {
+            callback(&self.scene.frame_data, self.fps);
+        }
 
         Ok(())
     }
+
+    /// Calls `callback` at the end of every [`RenderContext::render`], once the frame was
+    /// submitted and presented. Replaces the previous one.
+    pub fn set_frame_callback(&mut self, callback: impl FnMut(&FrameData, f64) + 'static) {
+        self.frame_callback = Some(Box::new(callback));
+    }
+}
+
+/// The raytracing pass, rendering into the radiance texture.
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Render pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers: &[Vertex::desc()],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_trace"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: RADIANCE_FORMAT,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+            polygon_mode: wgpu::PolygonMode::Fill,
+            // Requires Features::DEPTH_CLIP_CONTROL
+            unclipped_depth: false,
+            // Requires Features::CONSERVATIVE_RASTERIZATION
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: None,
+    })
+}
+
+/// Scene file given with `--scene`, reloaded by the `hot-reload` feature.
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+struct SceneWatcher {
+    path: std::path::PathBuf,
+    watcher: crate::utils::FileWatcher,
+    /// Camera of the last version of the file.
+    loaded_camera: crate::scene::Camera,
+}
+
+/// Shader read back from the source tree by the `hot-reload` feature.
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+const SHADER_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader/raytracing.wgsl");
+
+/// Zeroed accumulation buffer for a frame of `size` in `format`.
+fn create_image_buffer(
+    device: &wgpu::Device,
+    size: winit::dpi::PhysicalSize<u32>,
+    format: AccumulationFormat,
+) -> StorageBuffer {
+    let buffer = vec![0_u8; size.width as usize * size.height as usize * format.bytes_per_pixel()];
+    StorageBuffer::new_from_bytes(device, &buffer, 3_u32, Some("image buffer"))
+}
+
+/// Splits a `width` x `height` frame in `tile_size` sided squares as `(x, y, width, height)`,
+/// cropped at the borders. A `tile_size` of 0 gives the whole frame.
+fn tiles((width, height): (u32, u32), tile_size: u32) -> Vec<(u32, u32, u32, u32)> {
+    if tile_size == 0 {
+        return vec![(0, 0, width, height)];
+    }
+    (0..height)
+        .step_by(tile_size as usize)
+        .flat_map(|y| {
+            (0..width)
+                .step_by(tile_size as usize)
+                .map(move |x| (x, y, tile_size.min(width - x), tile_size.min(height - y)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn impossible_limits_fail_to_create_a_device() {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter =
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()));
+        // Without a GPU there is no device to fail to create.
+        let Some(adapter) = adapter else {
+            return;
+        };
+
+        let required_limits = wgpu::Limits {
+            max_bind_groups: u32::MAX,
+            ..adapter.limits()
+        };
+        let error = pollster::block_on(request_device(&adapter, required_limits));
+        assert!(matches!(error, Err(RenderInitError::RequestDevice(_))));
+    }
 }
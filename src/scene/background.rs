@@ -0,0 +1,96 @@
+use super::Texture;
+
+/// What rays escaping the scene see.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Background {
+    SolidColor(glm::Vec3),
+    /// Blends from `bottom` when looking straight down to `top` when looking straight up.
+    Gradient {
+        top: glm::Vec3,
+        bottom: glm::Vec3,
+    },
+    /// Skybox made of the +x, -x, +y, -y, +z and -z faces of a cube around the scene,
+    /// oriented like OpenGL cubemaps.
+    Cubemap(Box<[Texture; 6]>),
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::SolidColor(glm::vec3(0.0, 0.0, 0.0))
+    }
+}
+
+impl Background {
+    /// The blue-white sky of "Ray Tracing in One Weekend".
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn sky() -> Self {
+        Background::Gradient {
+            top: glm::vec3(0.5, 0.7, 1.0),
+            bottom: glm::vec3(1.0, 1.0, 1.0),
+        }
+    }
+
+    /// Returns true when the background changed.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        let mut gradient = matches!(self, Background::Gradient { .. });
+        if !matches!(self, Background::Cubemap(_)) {
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut gradient, false, "Solid color");
+                ui.radio_value(&mut gradient, true, "Gradient");
+            });
+        }
+
+        match (&*self, gradient) {
+            (Background::SolidColor(color), true) => {
+                *self = Background::Gradient {
+                    top: *color,
+                    bottom: *color,
+                };
+                changed = true;
+            }
+            (Background::Gradient { top, .. }, false) => {
+                *self = Background::SolidColor(*top);
+                changed = true;
+            }
+            _ => {}
+        }
+
+        match self {
+            Background::SolidColor(color) => {
+                ui.horizontal(|ui| {
+                    ui.label("Color:");
+                    changed |= color_edit(ui, color);
+                });
+            }
+            Background::Gradient { top, bottom } => {
+                ui.horizontal(|ui| {
+                    ui.label("Top:");
+                    changed |= color_edit(ui, top);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Bottom:");
+                    changed |= color_edit(ui, bottom);
+                });
+            }
+            Background::Cubemap(faces) => {
+                let (width, height) = faces[0].dimensions();
+                ui.label(format!("Cubemap ({width}x{height})"));
+                if ui.button("Remove").clicked() {
+                    *self = Background::default();
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+}
+
+fn color_edit(ui: &mut egui::Ui, color: &mut glm::Vec3) -> bool {
+    let mut rgb = [color.x, color.y, color.z];
+    let changed = ui.color_edit_button_rgb(&mut rgb).changed();
+    if changed {
+        *color = glm::vec3(rgb[0], rgb[1], rgb[2]);
+    }
+    changed
+}
@@ -0,0 +1,150 @@
+use crate::object::{Light, Mesh, ObjectList, ObjectType, PointLight, Sphere};
+
+use super::{
+    Background, Camera, CameraController, FrameData, Material, Medium, RenderParam, Scene,
+};
+
+/// Assembles a [`Scene`] while keeping `materials`, `spheres`, `object_list` and `lights`
+/// in sync: every added object gets its own material slot.
+pub struct SceneBuilder {
+    materials: Vec<Material>,
+    spheres: Vec<Sphere>,
+    lights: Vec<Light>,
+    point_lights: Vec<PointLight>,
+    object_list: ObjectList,
+    camera: Option<Camera>,
+    camera_presets: Vec<(String, Camera)>,
+    background: Background,
+    medium: Option<Medium>,
+    render_param: RenderParam,
+    frame_data: FrameData,
+}
+
+impl SceneBuilder {
+    pub fn new(render_param: RenderParam, frame_data: FrameData) -> Self {
+        Self {
+            materials: Vec::new(),
+            spheres: Vec::new(),
+            lights: Vec::new(),
+            point_lights: Vec::new(),
+            object_list: ObjectList::new(),
+            camera: None,
+            camera_presets: Vec::new(),
+            background: Background::default(),
+            medium: None,
+            render_param,
+            frame_data,
+        }
+    }
+
+    fn push_material(&mut self, material: Material) -> u32 {
+        self.materials.push(material);
+        (self.materials.len() - 1) as u32
+    }
+
+    pub fn add_sphere(mut self, center: glm::Vec3, radius: f32, material: Material) -> Self {
+        let material_idx = self.push_material(material);
+        self.spheres.push(Sphere::new(center, radius, material_idx));
+        self.object_list.add_sphere(None);
+        self
+    }
+
+    /// A sphere moving by `velocity` while the shutter is open, blurred by motion.
+    pub fn add_moving_sphere(
+        mut self,
+        center: glm::Vec3,
+        radius: f32,
+        velocity: glm::Vec3,
+        material: Material,
+    ) -> Self {
+        let material_idx = self.push_material(material);
+        self.spheres
+            .push(Sphere::new(center, radius, material_idx).with_velocity(velocity));
+        self.object_list.add_sphere(None);
+        self
+    }
+
+    pub fn add_mesh(mut self, meshes: Vec<Mesh>, material: Material) -> Self {
+        let material_idx = self.push_material(material);
+        self.object_list
+            .add_mesh(Some(meshes.len()), meshes, material_idx);
+        self
+    }
+
+    /// Registers the last added object as a light source.
+    pub fn add_light(mut self) -> Self {
+        let object = self
+            .object_list
+            .objects
+            .last()
+            .expect("add_light must follow add_sphere or add_mesh");
+        let area = self.object_list.object_area(object, &self.spheres);
+        self.lights.push(Light::new(
+            object.id,
+            ObjectType::from(object.obj_type),
+            area,
+        ));
+        self
+    }
+
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub fn point_light(mut self, light: PointLight) -> Self {
+        self.point_lights.push(light);
+        self
+    }
+
+    pub fn camera(mut self, camera: Camera) -> Self {
+        self.camera = Some(camera);
+        self
+    }
+
+    /// Adds a named viewpoint to pick from in the UI, the scene starts from `camera`.
+    pub fn camera_preset(mut self, name: &str, camera: Camera) -> Self {
+        self.camera_presets.push((name.to_string(), camera));
+        self
+    }
+
+    /// Defaults to black.
+    pub fn background(mut self, background: Background) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Fills the scene with `medium`, there is none by default.
+    #[allow(dead_code)]
+    pub fn medium(mut self, medium: Medium) -> Self {
+        self.medium = Some(medium);
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn render_param(mut self, render_param: RenderParam) -> Self {
+        self.render_param = render_param;
+        self
+    }
+
+    pub fn build(mut self) -> Scene {
+        // Storage buffers can't be empty, keep a degenerate triangle around for sphere-only scenes.
+        if self.object_list.meshes.is_empty() {
+            self.object_list.meshes.push(Mesh::empty());
+        }
+
+        Scene {
+            camera: self
+                .camera
+                .expect("SceneBuilder::build called without a camera"),
+            camera_presets: self.camera_presets,
+            materials: self.materials,
+            spheres: self.spheres,
+            lights: self.lights,
+            point_lights: self.point_lights,
+            render_param: self.render_param,
+            frame_data: self.frame_data,
+            camera_controller: CameraController::new(4.0, 0.4),
+            object_list: self.object_list,
+            background: self.background,
+            medium: self.medium,
+            ambient: glm::Vec3::zeros(),
+        }
+    }
+}
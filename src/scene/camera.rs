@@ -1,20 +1,129 @@
 use instant::Duration;
 use winit::{
     dpi::PhysicalPosition,
-    event::{DeviceEvent, ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    event::{
+        DeviceEvent, ElementState, KeyEvent, MouseButton, MouseScrollDelta, Touch, TouchPhase,
+        WindowEvent,
+    },
     keyboard::{KeyCode, PhysicalKey},
 };
 
+use crate::utils::{bvh::Aabb, ray::Ray};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CameraProjection {
+    /// `vfov` is the vertical field of view in degrees.
+    Perspective { vfov: f32 },
+    /// `height` is the world-space height of the view volume, rays are parallel to `eye_dir`.
+    Orthographic { height: f32 },
+}
+
+/// Shape of the lens aperture, out-of-focus highlights take it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BokehShape {
+    #[default]
+    Circle,
+    Hexagon,
+    Pentagon,
+    /// A regular polygon with this many blades, at least 3.
+    Blade(u32),
+}
+
+impl BokehShape {
+    /// Sides of the aperture polygon, 0 for a circle.
+    pub fn blades(self) -> u32 {
+        match self {
+            BokehShape::Circle => 0,
+            BokehShape::Hexagon => 6,
+            BokehShape::Pentagon => 5,
+            BokehShape::Blade(n) => n.max(3),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Camera {
     pub eye_pos: glm::Vec3,
     pub eye_dir: glm::Vec3,
     pub up: glm::Vec3,
-    pub vfov: f32,
+    pub projection: CameraProjection,
     /// Aperture must be between 0..=1.
     pub aperture: f32,
+    pub bokeh: BokehShape,
     /// Focus distance must be a positive number.
     pub focus_distance: f32,
+    /// Part of the sphere velocities covered while the shutter is open, between 0..=1.
+    /// Moving spheres are blurred over it, 0 freezes them.
+    pub shutter: f32,
+}
+
+impl Camera {
+    /// Switches between perspective and orthographic projection, keeping the framing of the
+    /// focus plane.
+    pub fn set_orthographic(&mut self, orthographic: bool) {
+        self.projection = match (self.projection, orthographic) {
+            (CameraProjection::Perspective { vfov }, true) => CameraProjection::Orthographic {
+                height: 2.0 * self.focus_distance * (0.5 * vfov.to_radians()).tan(),
+            },
+            (CameraProjection::Orthographic { height }, false) => CameraProjection::Perspective {
+                vfov: (2.0 * (0.5 * height / self.focus_distance).atan())
+                    .to_degrees()
+                    .clamp(2.0, 179.0),
+            },
+            (projection, _) => projection,
+        };
+    }
+
+    /// Moves the camera back along its view direction until a sphere around `center` holding
+    /// `aabb` fits in the vertical field of view, and focuses on `center`. Orthographic
+    /// cameras get their height adjusted instead.
+    pub fn frame_bounds(&mut self, aabb: &Aabb, center: glm::Vec3) {
+        if aabb.min.x > aabb.max.x {
+            return;
+        }
+        let farthest_corner = glm::max2(&(center - aabb.min), &(aabb.max - center));
+        let radius = glm::length(&farthest_corner).max(1e-3);
+        let distance = match &mut self.projection {
+            CameraProjection::Perspective { vfov } => radius / (0.5 * vfov.to_radians()).sin(),
+            CameraProjection::Orthographic { height } => {
+                *height = 2.0 * radius;
+                2.0 * radius
+            }
+        };
+        self.eye_pos = center - distance * glm::normalize(&self.eye_dir);
+        self.focus_distance = distance;
+    }
+}
+
+/// Roll rate in radians per second while Q or E is held.
+const ROLL_SPEED: f32 = 1.0;
+/// Mouse motion a fully tilted right stick is worth, about 2 radians per second at the
+/// default sensitivity.
+const GAMEPAD_LOOK_SPEED: f32 = 5.0;
+/// Scroll per second while a shoulder button is held.
+const GAMEPAD_ZOOM_SPEED: f32 = 1000.0;
+
+/// Scroll worth of a pixel of pinch, spreading the fingers zooms in.
+const PINCH_SCROLL: f32 = 2.0;
+
+/// A finger on the screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TouchPoint {
+    id: u64,
+    position: PhysicalPosition<f64>,
+}
+
+/// State of the sticks, triggers and shoulder buttons, every value in `-1..=1`. Sticks are
+/// expected to be past their deadzone already.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct GamepadInput {
+    pub forward: f32,
+    pub right: f32,
+    pub up: f32,
+    pub look_horizontal: f32,
+    pub look_vertical: f32,
+    /// Positive zooms in.
+    pub zoom: f32,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -26,11 +135,22 @@ pub struct CameraController {
     amount_backward: f32,
     amount_up: f32,
     amount_down: f32,
+    amount_roll_left: f32,
+    amount_roll_right: f32,
     rotate_horizontal: f32,
     rotate_vertical: f32,
     scroll: f32,
-    speed: f32,
-    sensitivity: f32,
+    /// Scrolling adjusts the focus distance instead of the FOV while Ctrl is held.
+    scroll_focus: bool,
+    /// Held like keys, it adds to the keyboard and mouse instead of replacing them.
+    gamepad: GamepadInput,
+    /// One finger drags look around, two fingers pinch to zoom, more are ignored.
+    touches: [Option<TouchPoint>; 2],
+    /// Held on-screen arrows, forward and right.
+    amount_touch: [f32; 2],
+    /// World units per second.
+    pub speed: f32,
+    pub sensitivity: f32,
 }
 
 impl CameraController {
@@ -43,9 +163,15 @@ impl CameraController {
             amount_backward: 0.0,
             amount_up: 0.0,
             amount_down: 0.0,
+            amount_roll_left: 0.0,
+            amount_roll_right: 0.0,
             rotate_horizontal: 0.0,
             rotate_vertical: 0.0,
             scroll: 0.0,
+            scroll_focus: false,
+            gamepad: GamepadInput::default(),
+            touches: [None; 2],
+            amount_touch: [0.0; 2],
             speed,
             sensitivity,
         }
@@ -89,6 +215,14 @@ impl CameraController {
                 self.amount_down = amount;
                 true
             }
+            KeyCode::KeyQ => {
+                self.amount_roll_left = amount;
+                true
+            }
+            KeyCode::KeyE => {
+                self.amount_roll_right = amount;
+                true
+            }
             _ => false,
         };
         self.updated = s;
@@ -100,8 +234,64 @@ impl CameraController {
         self.rotate_vertical = mouse_dy as f32;
     }
 
+    #[allow(dead_code)]
+    pub fn process_gamepad(&mut self, input: GamepadInput) {
+        self.gamepad = input;
+    }
+
+    pub fn process_touch(&mut self, touch: &Touch) {
+        let slot = self
+            .touches
+            .iter()
+            .position(|t| t.is_some_and(|t| t.id == touch.id));
+        let point = TouchPoint {
+            id: touch.id,
+            position: touch.location,
+        };
+        match touch.phase {
+            TouchPhase::Started => {
+                if let Some(free) = self.touches.iter_mut().find(|t| t.is_none()) {
+                    *free = Some(point);
+                }
+            }
+            TouchPhase::Moved => {
+                let Some(slot) = slot else {
+                    return;
+                };
+                let before = self.touches;
+                self.touches[slot] = Some(point);
+                match (before, self.touches) {
+                    ([Some(a0), Some(b0)], [Some(a1), Some(b1)]) => {
+                        let distance = |a: TouchPoint, b: TouchPoint| {
+                            (a.position.x - b.position.x).hypot(a.position.y - b.position.y)
+                        };
+                        self.scroll += (distance(a0, b0) - distance(a1, b1)) as f32 * PINCH_SCROLL;
+                    }
+                    _ => {
+                        let previous = before[slot].map_or(point.position, |t| t.position);
+                        self.process_mouse(
+                            point.position.x - previous.x,
+                            point.position.y - previous.y,
+                        );
+                    }
+                }
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                if let Some(slot) = slot {
+                    self.touches[slot] = None;
+                }
+            }
+        }
+    }
+
+    /// Movement from on-screen buttons, each in `-1..=1`.
+    #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+    pub fn process_touch_buttons(&mut self, forward: f32, right: f32) {
+        self.amount_touch = [forward, right];
+    }
+
     pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
-        self.scroll = -match delta {
+        self.scroll -= match delta {
             // I'm assuming a line is about 100 pixels
             MouseScrollDelta::LineDelta(_, scroll) => scroll * 100.0,
             MouseScrollDelta::PixelDelta(PhysicalPosition { y: scroll, .. }) => *scroll as f32,
@@ -121,6 +311,15 @@ impl CameraController {
             } => {
                 self.process_keyboard(*key, *state);
             }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.scroll_focus = modifiers.state().control_key();
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.process_scroll(delta);
+            }
+            WindowEvent::Touch(touch) => {
+                self.process_touch(touch);
+            }
             WindowEvent::MouseInput {
                 state: ElementState::Pressed,
                 button: MouseButton::Right,
@@ -140,38 +339,55 @@ impl CameraController {
     }
 
     pub fn handle_mouse(&mut self, device_event: &DeviceEvent, mouse_pressed: bool) {
-        match device_event {
-            DeviceEvent::MouseMotion { delta } => {
-                if mouse_pressed {
-                    self.process_mouse(delta.0, delta.1);
-                }
+        if let DeviceEvent::MouseMotion { delta } = device_event {
+            if mouse_pressed {
+                self.process_mouse(delta.0, delta.1);
             }
-            // DeviceEvent::MouseWheel { delta } => {
-            //     // TODO: Not behaving as expected
-            //     self.process_scroll(delta);
-            // }
-            _ => {}
         }
     }
 
-    pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
-        let forward = self.amount_forward - self.amount_backward;
-        let right = self.amount_right - self.amount_left;
-        let up = self.amount_up - self.amount_down;
-        let rotate_horizontal = self.rotate_horizontal;
-        let rotate_vertical = self.rotate_vertical;
-        let scroll = self.scroll;
-
+    /// Applies the pending input to `camera`, returns false when there was none.
+    pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) -> bool {
         let dt = dt.as_secs_f32();
+        let gamepad = self.gamepad;
+        let [touch_forward, touch_right] = self.amount_touch;
+        let forward = self.amount_forward - self.amount_backward + gamepad.forward + touch_forward;
+        let right = self.amount_right - self.amount_left + gamepad.right + touch_right;
+        let up = self.amount_up - self.amount_down + gamepad.up;
+        let roll = self.amount_roll_right - self.amount_roll_left;
+        let rotate_horizontal =
+            self.rotate_horizontal + gamepad.look_horizontal * GAMEPAD_LOOK_SPEED;
+        let rotate_vertical = self.rotate_vertical + gamepad.look_vertical * GAMEPAD_LOOK_SPEED;
+        let scroll = self.scroll - gamepad.zoom * GAMEPAD_ZOOM_SPEED * dt;
+
+        let idle = [
+            forward,
+            right,
+            up,
+            roll,
+            rotate_horizontal,
+            rotate_vertical,
+            scroll,
+        ]
+        .iter()
+        .all(|amount| *amount == 0.0);
+        if idle {
+            self.clear();
+            return false;
+        }
+
         let speed = self.speed;
         let sensitivity = self.sensitivity;
 
-        let forward = forward * speed * dt;
-        let right = right * speed * dt;
-        let up = up * speed * dt;
+        // Holding two directions at once must not move faster than one.
+        let length = glm::length(&glm::vec3(forward, right, up));
+        let movement_scale = speed * dt / length.max(1.0);
+        let forward = forward * movement_scale;
+        let right = right * movement_scale;
+        let up = up * movement_scale;
+        let roll = roll * ROLL_SPEED * dt;
         let rotate_horizontal = rotate_horizontal * sensitivity * dt;
         let rotate_vertical = rotate_vertical * sensitivity * dt;
-        let scroll = scroll * speed * dt;
 
         let forward = camera.eye_dir * forward;
         let right = glm::cross(&camera.eye_dir, &camera.up) * right;
@@ -185,10 +401,28 @@ impl CameraController {
             &glm::cross(&camera.eye_dir, &camera.up),
         );
         camera.eye_dir = glm::normalize(&camera.eye_dir);
+        // Renormalize so the up vector doesn't drift after many small rolls.
+        camera.up = glm::normalize(&glm::rotate_vec3(&camera.up, roll, &camera.eye_dir));
 
-        camera.focus_distance -= scroll;
-        camera.focus_distance = camera.focus_distance.max(0.1);
+        // Scrolling is an impulse, not a rate, so it isn't scaled by dt.
+        if self.scroll_focus {
+            camera.focus_distance -= scroll * speed * 0.001;
+            camera.focus_distance = camera.focus_distance.max(0.1);
+        } else {
+            // Scrolling up (negative) narrows the view, i.e. zooms in.
+            match &mut camera.projection {
+                CameraProjection::Perspective { vfov } => {
+                    *vfov += scroll * sensitivity * 0.05;
+                    *vfov = vfov.clamp(2.0, 179.0);
+                }
+                CameraProjection::Orthographic { height } => {
+                    *height *= 1.0 + scroll * sensitivity * 0.0025;
+                    *height = height.max(0.01);
+                }
+            }
+        }
         self.clear();
+        true
     }
 }
 
@@ -196,9 +430,10 @@ impl CameraController {
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct GpuCamera {
     eye: glm::Vec3,
-    _padding1: f32,
+    projection: u32,
     horizontal: glm::Vec3,
-    _padding2: f32,
+    /// See [`BokehShape::blades`].
+    blades: u32,
     vertical: glm::Vec3,
     _padding3: f32,
     u: glm::Vec3,
@@ -207,30 +442,59 @@ pub struct GpuCamera {
     lens_radius: f32,
     lower_left_corner: glm::Vec3,
     _padding5: f32,
+    w: glm::Vec3,
+    shutter: f32,
 }
 
+const PROJECTION_PERSPECTIVE: u32 = 0;
+const PROJECTION_ORTHOGRAPHIC: u32 = 1;
+
 impl GpuCamera {
     pub fn new(camera: &Camera, viewport_size: (u32, u32)) -> Self {
-        let lens_radius = 0.5_f32 * camera.aperture;
-        let aspect = viewport_size.0 as f32 / viewport_size.1 as f32;
-        let theta = camera.vfov.to_radians();
-        let half_height = camera.focus_distance * (0.5_f32 * theta).tan();
-        let half_width = aspect * half_height;
+        // A canvas can briefly be 0 pixels tall on the web, a NaN here would end up in the
+        // accumulated image for good.
+        let aspect = viewport_size.0.max(1) as f32 / viewport_size.1.max(1) as f32;
+        debug_assert!(aspect.is_finite() && aspect > 0.0);
 
         let w = glm::normalize(&camera.eye_dir);
         let v = glm::normalize(&camera.up);
         let u = glm::cross(&w, &v);
 
-        let lower_left_corner =
-            camera.eye_pos + camera.focus_distance * w - half_width * u - half_height * v;
-        let horizontal = 2_f32 * half_width * u;
-        let vertical = 2_f32 * half_height * v;
+        let (projection, lens_radius, lower_left_corner, horizontal, vertical) = match camera
+            .projection
+        {
+            CameraProjection::Perspective { vfov } => {
+                let theta = vfov.to_radians();
+                let half_height = camera.focus_distance * (0.5_f32 * theta).tan();
+                let half_width = aspect * half_height;
+                (
+                    PROJECTION_PERSPECTIVE,
+                    0.5_f32 * camera.aperture,
+                    camera.eye_pos + camera.focus_distance * w - half_width * u - half_height * v,
+                    2_f32 * half_width * u,
+                    2_f32 * half_height * v,
+                )
+            }
+            // Rays start on the plane through the eye and all travel along `w`, there is
+            // no lens so no depth of field.
+            CameraProjection::Orthographic { height } => {
+                let half_height = 0.5_f32 * height;
+                let half_width = aspect * half_height;
+                (
+                    PROJECTION_ORTHOGRAPHIC,
+                    0_f32,
+                    camera.eye_pos - half_width * u - half_height * v,
+                    2_f32 * half_width * u,
+                    2_f32 * half_height * v,
+                )
+            }
+        };
 
         Self {
             eye: camera.eye_pos,
-            _padding1: 0_f32,
+            projection,
             horizontal,
-            _padding2: 0_f32,
+            blades: camera.bokeh.blades(),
             vertical,
             _padding3: 0_f32,
             u,
@@ -239,6 +503,130 @@ impl GpuCamera {
             lens_radius,
             lower_left_corner,
             _padding5: 0_f32,
+            w,
+            shutter: camera.shutter,
+        }
+    }
+
+    /// Ray through the viewport point `(u, v)` in `0..1`, `v` going up, ignoring the lens.
+    /// Mirrors `get_ray` in the shader.
+    pub fn ray(&self, u: f32, v: f32) -> Ray {
+        let point = self.lower_left_corner + u * self.horizontal + v * self.vertical;
+        match self.projection {
+            PROJECTION_ORTHOGRAPHIC => Ray::new(point, self.w),
+            _ => Ray::new(self.eye, point - self.eye),
+        }
+    }
+
+    /// Inverse of [`GpuCamera::ray`]: viewport coordinates of a world point, `None` when it
+    /// lies behind the camera.
+    pub fn project(&self, point: glm::Vec3) -> Option<glm::Vec2> {
+        let depth = glm::dot(&(point - self.eye), &self.w);
+        if depth <= 1e-4 {
+            return None;
+        }
+
+        let on_plane = match self.projection {
+            PROJECTION_ORTHOGRAPHIC => point,
+            _ => {
+                let center = self.lower_left_corner + 0.5 * (self.horizontal + self.vertical);
+                let plane_distance = glm::dot(&(center - self.eye), &self.w);
+                self.eye + (point - self.eye) * (plane_distance / depth)
+            }
+        };
+
+        let offset = on_plane - self.lower_left_corner;
+        Some(glm::vec2(
+            glm::dot(&offset, &self.horizontal) / glm::length2(&self.horizontal),
+            glm::dot(&offset, &self.vertical) / glm::length2(&self.vertical),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_height_viewport_stays_finite() {
+        let camera = Camera {
+            eye_pos: glm::vec3(0.0, 0.0, 5.0),
+            eye_dir: glm::vec3(0.0, 0.0, -1.0),
+            up: glm::vec3(0.0, 1.0, 0.0),
+            projection: CameraProjection::Perspective { vfov: 30.0 },
+            aperture: 0.0,
+            bokeh: BokehShape::Circle,
+            focus_distance: 10.0,
+            shutter: 0.0,
+        };
+        let gpu_camera = GpuCamera::new(&camera, (100, 0));
+
+        for basis in [
+            gpu_camera.horizontal,
+            gpu_camera.vertical,
+            gpu_camera.lower_left_corner,
+            gpu_camera.u,
+            gpu_camera.v,
+            gpu_camera.w,
+        ] {
+            assert!(basis.iter().all(|c| c.is_finite()), "{basis:?}");
+        }
+        // Clamped to a 100x1 viewport.
+        let aspect = glm::length(&gpu_camera.horizontal) / glm::length(&gpu_camera.vertical);
+        assert!((aspect - 100.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn diagonal_movement_is_not_faster() {
+        let camera = Camera {
+            eye_pos: glm::vec3(0.0, 0.0, 5.0),
+            eye_dir: glm::vec3(0.0, 0.0, -1.0),
+            up: glm::vec3(0.0, 1.0, 0.0),
+            projection: CameraProjection::Perspective { vfov: 30.0 },
+            aperture: 0.0,
+            bokeh: BokehShape::Circle,
+            focus_distance: 10.0,
+            shutter: 0.0,
+        };
+        let displacement = |keys: &[KeyCode]| {
+            let mut controller = CameraController::new(4.0, 0.4);
+            for key in keys {
+                controller.process_keyboard(*key, ElementState::Pressed);
+            }
+            let mut moved = camera;
+            controller.update_camera(&mut moved, Duration::from_millis(100));
+            glm::distance(&moved.eye_pos, &camera.eye_pos)
+        };
+
+        let forward = displacement(&[KeyCode::KeyW]);
+        assert!((forward - 0.4).abs() < 1e-5);
+        assert!((displacement(&[KeyCode::KeyW, KeyCode::KeyD]) - forward).abs() < 1e-5);
+        assert!(
+            (displacement(&[KeyCode::KeyW, KeyCode::KeyD, KeyCode::Space]) - forward).abs() < 1e-5
+        );
+    }
+
+    #[test]
+    fn up_stays_normalized_after_many_rolls() {
+        let mut camera = Camera {
+            eye_pos: glm::vec3(0.0, 0.0, 5.0),
+            eye_dir: glm::vec3(0.0, 0.0, -1.0),
+            up: glm::vec3(0.0, 1.0, 0.0),
+            projection: CameraProjection::Perspective { vfov: 30.0 },
+            aperture: 0.0,
+            bokeh: BokehShape::Circle,
+            focus_distance: 10.0,
+            shutter: 0.0,
+        };
+        let mut controller = CameraController::new(4.0, 0.4);
+
+        for key in [KeyCode::KeyQ, KeyCode::KeyE] {
+            controller.process_keyboard(key, ElementState::Pressed);
+            for _ in 0..5000 {
+                controller.update_camera(&mut camera, Duration::from_millis(16));
+            }
+            controller.process_keyboard(key, ElementState::Released);
+            assert!((glm::length(&camera.up) - 1.0).abs() < 1e-5);
         }
     }
 }
@@ -0,0 +1,98 @@
+use super::{Camera, CameraProjection};
+
+/// Keyframed camera flythrough. Positions follow a Catmull-Rom spline through the keyframes,
+/// directions are slerped and the rest is interpolated linearly.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CameraPath {
+    /// `(time in seconds, camera)`, sorted by time.
+    pub keyframes: Vec<(f32, Camera)>,
+}
+
+impl CameraPath {
+    /// Time of the last keyframe.
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |(time, _)| *time)
+    }
+
+    /// Inserts `camera` at `time`, replacing a keyframe already there.
+    pub fn add_keyframe(&mut self, time: f32, camera: Camera) {
+        let index = self.keyframes.partition_point(|(t, _)| *t < time);
+        match self.keyframes.get_mut(index) {
+            Some((t, existing)) if *t == time => *existing = camera,
+            _ => self.keyframes.insert(index, (time, camera)),
+        }
+    }
+
+    /// Camera at `time`, clamped to the ends of the path. `None` without keyframes.
+    pub fn sample(&self, time: f32) -> Option<Camera> {
+        let keyframes = &self.keyframes;
+        let (first, last) = (keyframes.first()?, keyframes.last()?);
+        if time <= first.0 {
+            return Some(first.1);
+        }
+        if time >= last.0 {
+            return Some(last.1);
+        }
+
+        // keyframes[i].0 < time < keyframes[i + 1].0
+        let i = keyframes.partition_point(|(t, _)| *t <= time) - 1;
+        let (t1, c1) = keyframes[i];
+        let (t2, c2) = keyframes[i + 1];
+        let u = (time - t1) / (t2 - t1);
+        // The ends repeat themselves so the curve still goes through them.
+        let c0 = keyframes[i.saturating_sub(1)].1;
+        let c3 = keyframes[(i + 2).min(keyframes.len() - 1)].1;
+
+        let lerp = |a: f32, b: f32| a + (b - a) * u;
+        Some(Camera {
+            eye_pos: catmull_rom(c0.eye_pos, c1.eye_pos, c2.eye_pos, c3.eye_pos, u),
+            eye_dir: slerp(&c1.eye_dir, &c2.eye_dir, u),
+            up: slerp(&c1.up, &c2.up, u),
+            projection: match (c1.projection, c2.projection) {
+                (
+                    CameraProjection::Perspective { vfov: a },
+                    CameraProjection::Perspective { vfov: b },
+                ) => CameraProjection::Perspective { vfov: lerp(a, b) },
+                (
+                    CameraProjection::Orthographic { height: a },
+                    CameraProjection::Orthographic { height: b },
+                ) => CameraProjection::Orthographic { height: lerp(a, b) },
+                (a, b) => match u < 0.5 {
+                    true => a,
+                    false => b,
+                },
+            },
+            aperture: lerp(c1.aperture, c2.aperture),
+            bokeh: match u < 0.5 {
+                true => c1.bokeh,
+                false => c2.bokeh,
+            },
+            focus_distance: lerp(c1.focus_distance, c2.focus_distance),
+            shutter: lerp(c1.shutter, c2.shutter),
+        })
+    }
+}
+
+/// Uniform Catmull-Rom spline between `p1` and `p2`.
+fn catmull_rom(p0: glm::Vec3, p1: glm::Vec3, p2: glm::Vec3, p3: glm::Vec3, u: f32) -> glm::Vec3 {
+    let u2 = u * u;
+    let u3 = u2 * u;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * u
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * u2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * u3)
+}
+
+/// Rotates the direction of `a` towards `b` at constant angular speed.
+fn slerp(a: &glm::Vec3, b: &glm::Vec3, u: f32) -> glm::Vec3 {
+    let a = glm::normalize(a);
+    let b = glm::normalize(b);
+    let cos_theta = glm::dot(&a, &b).clamp(-1.0, 1.0);
+    let theta = cos_theta.acos();
+    let sin_theta = theta.sin();
+    // Parallel or opposite, there is no single plane to rotate in.
+    if sin_theta < 1e-4 {
+        return if u < 0.5 { a } else { b };
+    }
+    ((1.0 - u) * theta).sin() / sin_theta * a + (u * theta).sin() / sin_theta * b
+}
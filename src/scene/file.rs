@@ -0,0 +1,734 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::object::{transform, Mesh, PointLight};
+
+use super::{
+    Background, BokehShape, Camera, CameraPath, CameraProjection, FrameData, Material, Medium,
+    RenderParam, Scene, SceneBuilder, SceneError, Settings, Texture, TextureFilter,
+};
+
+/// Root of a `.ron` scene file, for example:
+///
+/// ```ron
+/// (
+///     camera: (eye_pos: (0.0, 0.0, 5.0), eye_dir: (0.0, 0.0, -1.0), vfov: 30.0),
+///     background: Sky,
+///     objects: [
+///         (shape: Sphere(center: (0.0, 0.0, 0.0), radius: 1.0),
+///          material: Lambertian(albedo: (0.8, 0.3, 0.3))),
+///         (shape: Obj("mesh/suzanne.obj"), translate: (1.0, 0.0, 0.0),
+///          material: DiffuseLight(emit: (1.0, 1.0, 1.0), strength: 4.0), light: true),
+///     ],
+///     point_lights: [(position: (0.0, 3.0, 2.0), intensity: 20.0)],
+/// )
+/// ```
+#[derive(Deserialize)]
+struct SceneFile {
+    camera: CameraFile,
+    #[serde(default)]
+    background: BackgroundFile,
+    #[serde(default)]
+    medium: Option<MediumFile>,
+    objects: Vec<ObjectFile>,
+    #[serde(default)]
+    point_lights: Vec<PointLightFile>,
+}
+
+#[derive(Deserialize)]
+struct PointLightFile {
+    position: [f32; 3],
+    #[serde(default = "default_point_light_color")]
+    color: [f32; 3],
+    intensity: f32,
+}
+
+#[derive(Deserialize)]
+struct CameraFile {
+    eye_pos: [f32; 3],
+    eye_dir: [f32; 3],
+    #[serde(default = "default_up")]
+    up: [f32; 3],
+    /// Perspective vertical field of view in degrees, ignored when `ortho_height` is set.
+    #[serde(default = "default_vfov")]
+    vfov: f32,
+    #[serde(default)]
+    ortho_height: Option<f32>,
+    #[serde(default)]
+    aperture: f32,
+    #[serde(default)]
+    bokeh: BokehFile,
+    #[serde(default = "default_focus_distance")]
+    focus_distance: f32,
+    #[serde(default)]
+    shutter: f32,
+}
+
+#[derive(Deserialize, Default)]
+enum BackgroundFile {
+    #[default]
+    Black,
+    Sky,
+    SolidColor([f32; 3]),
+    Gradient {
+        top: [f32; 3],
+        bottom: [f32; 3],
+    },
+    /// Directory of the faces, relative to the scene file, see [`Background::load_cubemap`].
+    Cubemap(String),
+}
+
+#[derive(Deserialize)]
+struct MediumFile {
+    sigma_a: [f32; 3],
+    sigma_s: [f32; 3],
+}
+
+#[derive(Deserialize)]
+struct ObjectFile {
+    shape: ShapeFile,
+    material: MaterialFile,
+    /// Sample it as a light, it should have an emissive material.
+    #[serde(default)]
+    light: bool,
+    /// Applied to meshes as scale, then rotation in degrees about x, y and z, then translation.
+    /// Spheres only get translated.
+    #[serde(default)]
+    translate: [f32; 3],
+    #[serde(default)]
+    rotate: [f32; 3],
+    #[serde(default = "default_scale")]
+    scale: [f32; 3],
+}
+
+#[derive(Deserialize)]
+enum ShapeFile {
+    /// `velocity` is covered while the camera shutter is open.
+    Sphere {
+        center: [f32; 3],
+        radius: f32,
+        #[serde(default)]
+        velocity: [f32; 3],
+    },
+    Quad,
+    Cube,
+    /// Path relative to the scene file, every model in the file is merged.
+    Obj(String),
+    /// Path relative to the scene file.
+    Ply(String),
+    /// Path relative to the scene file.
+    Stl(String),
+}
+
+#[derive(Deserialize)]
+enum MaterialFile {
+    Lambertian {
+        albedo: [f32; 3],
+    },
+    Metal {
+        albedo: [f32; 3],
+        #[serde(default)]
+        fuzz: f32,
+    },
+    OrenNayar {
+        albedo: [f32; 3],
+        sigma: f32,
+    },
+    Dialectric {
+        ref_idx: f32,
+        #[serde(default)]
+        dispersion: f32,
+    },
+    Plastic {
+        albedo: [f32; 3],
+        #[serde(default = "default_plastic_ref_idx")]
+        ref_idx: f32,
+    },
+    CookTorrance {
+        albedo: [f32; 3],
+        #[serde(default)]
+        metallic: f32,
+        #[serde(default = "default_roughness")]
+        roughness: f32,
+    },
+    DiffuseLight {
+        emit: [f32; 3],
+        /// Multiplies `emit`, older files have the brightness in the color.
+        #[serde(default = "default_strength")]
+        strength: f32,
+        #[serde(default)]
+        two_sided: bool,
+        #[serde(default = "default_enabled")]
+        enabled: bool,
+    },
+}
+
+fn default_up() -> [f32; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn default_vfov() -> f32 {
+    40.0
+}
+
+fn default_focus_distance() -> f32 {
+    10.0
+}
+
+fn default_plastic_ref_idx() -> f32 {
+    1.5
+}
+
+fn default_roughness() -> f32 {
+    0.5
+}
+
+fn default_strength() -> f32 {
+    1.0
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_scale() -> [f32; 3] {
+    [1.0; 3]
+}
+
+fn default_point_light_color() -> [f32; 3] {
+    [1.0; 3]
+}
+
+impl From<MaterialFile> for Material {
+    fn from(material: MaterialFile) -> Self {
+        let texture = |c: [f32; 3]| Texture::new_from_color(c.into());
+        match material {
+            MaterialFile::Lambertian { albedo } => Material::Lambertian {
+                albedo: texture(albedo),
+            },
+            MaterialFile::Metal { albedo, fuzz } => Material::Metal {
+                albedo: texture(albedo),
+                fuzz,
+            },
+            MaterialFile::OrenNayar { albedo, sigma } => Material::OrenNayar {
+                albedo: texture(albedo),
+                sigma,
+            },
+            MaterialFile::Dialectric {
+                ref_idx,
+                dispersion,
+            } => Material::Dialectric {
+                ref_idx,
+                dispersion,
+            },
+            MaterialFile::Plastic { albedo, ref_idx } => Material::Plastic {
+                albedo: texture(albedo),
+                ref_idx,
+            },
+            MaterialFile::CookTorrance {
+                albedo,
+                metallic,
+                roughness,
+            } => Material::CookTorrance {
+                albedo: texture(albedo),
+                metallic,
+                roughness,
+            },
+            MaterialFile::DiffuseLight {
+                emit,
+                strength,
+                two_sided,
+                enabled,
+            } => Material::DiffuseLight {
+                emit: texture(emit),
+                strength,
+                two_sided,
+                enabled,
+            },
+        }
+    }
+}
+
+impl Scene {
+    /// Reads a `.ron` scene file, see `SceneFile` for the format. Render parameters are not
+    /// part of the file.
+    pub fn load(
+        path: &Path,
+        render_param: RenderParam,
+        frame_data: FrameData,
+    ) -> Result<Self, SceneError> {
+        let source = std::fs::read_to_string(path).map_err(|e| SceneError::Io {
+            path: path.to_owned(),
+            message: e.to_string(),
+        })?;
+        let file: SceneFile = ron::from_str(&source).map_err(|e| SceneError::Parse {
+            path: path.to_owned(),
+            message: e.to_string(),
+        })?;
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+
+        let camera = &file.camera;
+        let mut builder = SceneBuilder::new(render_param, frame_data).camera(Camera {
+            eye_pos: camera.eye_pos.into(),
+            eye_dir: camera.eye_dir.into(),
+            up: camera.up.into(),
+            projection: match camera.ortho_height {
+                Some(height) => CameraProjection::Orthographic { height },
+                None => CameraProjection::Perspective { vfov: camera.vfov },
+            },
+            aperture: camera.aperture,
+            bokeh: camera.bokeh.into(),
+            focus_distance: camera.focus_distance,
+            shutter: camera.shutter,
+        });
+        builder = builder.background(match file.background {
+            BackgroundFile::Black => Background::default(),
+            BackgroundFile::Sky => Background::sky(),
+            BackgroundFile::SolidColor(color) => Background::SolidColor(color.into()),
+            BackgroundFile::Gradient { top, bottom } => Background::Gradient {
+                top: top.into(),
+                bottom: bottom.into(),
+            },
+            BackgroundFile::Cubemap(relative) => {
+                Background::load_cubemap(&base_dir.join(relative))?
+            }
+        });
+        if let Some(medium) = file.medium {
+            builder = builder.medium(Medium {
+                sigma_a: medium.sigma_a.into(),
+                sigma_s: medium.sigma_s.into(),
+            });
+        }
+
+        for object in file.objects {
+            let translation = glm::Vec3::from(object.translate);
+            let material = Material::from(object.material);
+            let mut meshes = match object.shape {
+                ShapeFile::Sphere {
+                    center,
+                    radius,
+                    velocity,
+                } => {
+                    builder = builder.add_moving_sphere(
+                        glm::Vec3::from(center) + translation,
+                        radius,
+                        velocity.into(),
+                        material,
+                    );
+                    if object.light {
+                        builder = builder.add_light();
+                    }
+                    continue;
+                }
+                ShapeFile::Quad => Mesh::quad(),
+                ShapeFile::Cube => Mesh::cube(),
+                ShapeFile::Obj(relative) => load_obj(&base_dir.join(relative))?,
+                ShapeFile::Ply(relative) => Mesh::from_ply(&base_dir.join(relative))?,
+                ShapeFile::Stl(relative) => Mesh::from_stl(&base_dir.join(relative))?,
+            };
+
+            let [rx, ry, rz] = object.rotate.map(f32::to_radians);
+            transform(
+                &mut meshes,
+                glm::translation(&translation)
+                    * glm::rotation(rz, &glm::Vec3::z())
+                    * glm::rotation(ry, &glm::Vec3::y())
+                    * glm::rotation(rx, &glm::Vec3::x())
+                    * glm::scaling(&object.scale.into()),
+            );
+            builder = builder.add_mesh(meshes, material);
+            if object.light {
+                builder = builder.add_light();
+            }
+        }
+
+        for light in file.point_lights {
+            builder = builder.point_light(PointLight {
+                position: light.position.into(),
+                color: light.color.into(),
+                intensity: light.intensity,
+            });
+        }
+
+        Ok(builder.build())
+    }
+
+    /// A scene made of OBJ files, each one becoming a single object with its material.
+    pub fn from_objs(
+        objs: &[(&str, Material)],
+        camera: Camera,
+        render_param: RenderParam,
+        frame_data: FrameData,
+    ) -> Result<Self, SceneError> {
+        let mut builder = SceneBuilder::new(render_param, frame_data).camera(camera);
+        for (path, material) in objs {
+            builder = builder.add_mesh(load_obj(Path::new(path))?, material.clone());
+        }
+        Ok(builder.build())
+    }
+}
+
+/// Every model of an OBJ file, merged.
+fn load_obj(path: &Path) -> Result<Vec<Mesh>, SceneError> {
+    let options = tobj::LoadOptions {
+        triangulate: true,
+        ..Default::default()
+    };
+    let (models, _) = tobj::load_obj(path, &options).map_err(|e| SceneError::Parse {
+        path: path.to_owned(),
+        message: e.to_string(),
+    })?;
+    Ok(Mesh::merge(
+        models.into_iter().map(Mesh::from_tobj).collect(),
+    ))
+}
+
+/// One keyframe of a `.ron` camera path file, the file is a list of them.
+#[derive(Serialize, Deserialize)]
+struct KeyframeFile {
+    time: f32,
+    eye_pos: [f32; 3],
+    eye_dir: [f32; 3],
+    up: [f32; 3],
+    projection: ProjectionFile,
+    aperture: f32,
+    #[serde(default)]
+    bokeh: BokehFile,
+    focus_distance: f32,
+    /// Missing from files written before motion blur.
+    #[serde(default)]
+    shutter: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+enum ProjectionFile {
+    Perspective { vfov: f32 },
+    Orthographic { height: f32 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+enum BokehFile {
+    #[default]
+    Circle,
+    Hexagon,
+    Pentagon,
+    Blade(u32),
+}
+
+impl From<BokehShape> for BokehFile {
+    fn from(bokeh: BokehShape) -> Self {
+        match bokeh {
+            BokehShape::Circle => BokehFile::Circle,
+            BokehShape::Hexagon => BokehFile::Hexagon,
+            BokehShape::Pentagon => BokehFile::Pentagon,
+            BokehShape::Blade(n) => BokehFile::Blade(n),
+        }
+    }
+}
+
+impl From<BokehFile> for BokehShape {
+    fn from(bokeh: BokehFile) -> Self {
+        match bokeh {
+            BokehFile::Circle => BokehShape::Circle,
+            BokehFile::Hexagon => BokehShape::Hexagon,
+            BokehFile::Pentagon => BokehShape::Pentagon,
+            BokehFile::Blade(n) => BokehShape::Blade(n),
+        }
+    }
+}
+
+impl From<CameraProjection> for ProjectionFile {
+    fn from(projection: CameraProjection) -> Self {
+        match projection {
+            CameraProjection::Perspective { vfov } => ProjectionFile::Perspective { vfov },
+            CameraProjection::Orthographic { height } => ProjectionFile::Orthographic { height },
+        }
+    }
+}
+
+impl From<ProjectionFile> for CameraProjection {
+    fn from(projection: ProjectionFile) -> Self {
+        match projection {
+            ProjectionFile::Perspective { vfov } => CameraProjection::Perspective { vfov },
+            ProjectionFile::Orthographic { height } => CameraProjection::Orthographic { height },
+        }
+    }
+}
+
+impl From<&(f32, Camera)> for KeyframeFile {
+    fn from((time, camera): &(f32, Camera)) -> Self {
+        Self {
+            time: *time,
+            eye_pos: camera.eye_pos.into(),
+            eye_dir: camera.eye_dir.into(),
+            up: camera.up.into(),
+            projection: camera.projection.into(),
+            aperture: camera.aperture,
+            bokeh: camera.bokeh.into(),
+            focus_distance: camera.focus_distance,
+            shutter: camera.shutter,
+        }
+    }
+}
+
+impl From<KeyframeFile> for (f32, Camera) {
+    fn from(keyframe: KeyframeFile) -> Self {
+        let camera = Camera {
+            eye_pos: keyframe.eye_pos.into(),
+            eye_dir: keyframe.eye_dir.into(),
+            up: keyframe.up.into(),
+            projection: keyframe.projection.into(),
+            aperture: keyframe.aperture,
+            bokeh: keyframe.bokeh.into(),
+            focus_distance: keyframe.focus_distance,
+            shutter: keyframe.shutter,
+        };
+        (keyframe.time, camera)
+    }
+}
+
+impl CameraPath {
+    /// Writes the keyframes as a `.ron` list.
+    pub fn save(&self, path: &Path) -> Result<(), SceneError> {
+        let keyframes: Vec<KeyframeFile> = self.keyframes.iter().map(Into::into).collect();
+        let source = ron::ser::to_string_pretty(&keyframes, ron::ser::PrettyConfig::default())
+            .map_err(|e| SceneError::Parse {
+                path: path.to_owned(),
+                message: e.to_string(),
+            })?;
+        std::fs::write(path, source).map_err(|e| SceneError::Io {
+            path: path.to_owned(),
+            message: e.to_string(),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SceneError> {
+        let source = std::fs::read_to_string(path).map_err(|e| SceneError::Io {
+            path: path.to_owned(),
+            message: e.to_string(),
+        })?;
+        let keyframes: Vec<KeyframeFile> =
+            ron::from_str(&source).map_err(|e| SceneError::Parse {
+                path: path.to_owned(),
+                message: e.to_string(),
+            })?;
+        let mut camera_path = Self::default();
+        for keyframe in keyframes {
+            let (time, camera) = keyframe.into();
+            camera_path.add_keyframe(time, camera);
+        }
+        Ok(camera_path)
+    }
+}
+
+/// A settings file, for example:
+///
+/// ```ron
+/// (
+///     render_param: (samples_max_per_pixel: 500, max_depth: 8),
+///     camera: (eye_pos: (0.0, 0.0, 5.0), eye_dir: (0.0, 0.0, -1.0), up: (0.0, 1.0, 0.0),
+///              projection: Perspective(vfov: 30.0), aperture: 0.0, focus_distance: 10.0),
+///     speed: 4.0,
+///     sensitivity: 0.4,
+/// )
+/// ```
+#[derive(Serialize, Deserialize)]
+struct SettingsFile {
+    render_param: RenderParam,
+    camera: SettingsCameraFile,
+    speed: f32,
+    sensitivity: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SettingsCameraFile {
+    eye_pos: [f32; 3],
+    eye_dir: [f32; 3],
+    up: [f32; 3],
+    projection: ProjectionFile,
+    aperture: f32,
+    #[serde(default)]
+    bokeh: BokehFile,
+    focus_distance: f32,
+    /// Missing from files written before motion blur.
+    #[serde(default)]
+    shutter: f32,
+}
+
+impl From<&Camera> for SettingsCameraFile {
+    fn from(camera: &Camera) -> Self {
+        Self {
+            eye_pos: camera.eye_pos.into(),
+            eye_dir: camera.eye_dir.into(),
+            up: camera.up.into(),
+            projection: camera.projection.into(),
+            aperture: camera.aperture,
+            bokeh: camera.bokeh.into(),
+            focus_distance: camera.focus_distance,
+            shutter: camera.shutter,
+        }
+    }
+}
+
+impl From<SettingsCameraFile> for Camera {
+    fn from(camera: SettingsCameraFile) -> Self {
+        Self {
+            eye_pos: camera.eye_pos.into(),
+            eye_dir: camera.eye_dir.into(),
+            up: camera.up.into(),
+            projection: camera.projection.into(),
+            aperture: camera.aperture,
+            bokeh: camera.bokeh.into(),
+            focus_distance: camera.focus_distance,
+            shutter: camera.shutter,
+        }
+    }
+}
+
+impl Settings {
+    /// Writes the settings as `.ron`.
+    pub fn save(&self, path: &Path) -> Result<(), SceneError> {
+        let file = SettingsFile {
+            render_param: self.render_param,
+            camera: (&self.camera).into(),
+            speed: self.speed,
+            sensitivity: self.sensitivity,
+        };
+        let source =
+            ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default()).map_err(|e| {
+                SceneError::Parse {
+                    path: path.to_owned(),
+                    message: e.to_string(),
+                }
+            })?;
+        std::fs::write(path, source).map_err(|e| SceneError::Io {
+            path: path.to_owned(),
+            message: e.to_string(),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SceneError> {
+        let source = std::fs::read_to_string(path).map_err(|e| SceneError::Io {
+            path: path.to_owned(),
+            message: e.to_string(),
+        })?;
+        let file: SettingsFile = ron::from_str(&source).map_err(|e| SceneError::Parse {
+            path: path.to_owned(),
+            message: e.to_string(),
+        })?;
+        Ok(Self {
+            render_param: file.render_param,
+            camera: file.camera.into(),
+            speed: file.speed,
+            sensitivity: file.sensitivity,
+        })
+    }
+}
+
+/// Reads an 8 or 16-bit PNG as an sRGB texture, alpha is dropped.
+fn load_png(path: &Path) -> Result<Texture, SceneError> {
+    let parse_error = |e: png::DecodingError| SceneError::Parse {
+        path: path.to_owned(),
+        message: e.to_string(),
+    };
+    let file = std::fs::File::open(path).map_err(|e| SceneError::Io {
+        path: path.to_owned(),
+        message: e.to_string(),
+    })?;
+    let mut decoder = png::Decoder::new(std::io::BufReader::new(file));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(parse_error)?;
+    let mut bytes = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut bytes).map_err(parse_error)?;
+
+    let channels = info.color_type.samples();
+    let data = bytes[..info.buffer_size()]
+        .chunks_exact(channels)
+        .map(|pixel| match channels {
+            1 | 2 => [pixel[0]; 3],
+            _ => [pixel[0], pixel[1], pixel[2]],
+        })
+        .map(|rgb| rgb.map(|c| c as f32 / 255.0))
+        .collect();
+    Ok(Texture::new_from_data(info.width, info.height, data).with_srgb(true))
+}
+
+impl Background {
+    /// Reads the faces of a cubemap from `px.png`, `nx.png`, `py.png`, `ny.png`, `pz.png` and
+    /// `nz.png` in `dir`.
+    pub fn load_cubemap(dir: &Path) -> Result<Self, SceneError> {
+        let mut faces = Vec::with_capacity(6);
+        for name in ["px", "nx", "py", "ny", "pz", "nz"] {
+            let face = load_png(&dir.join(format!("{name}.png")))?;
+            faces.push(face.with_filter(TextureFilter::Bilinear));
+        }
+        let faces: [Texture; 6] = faces.try_into().expect("six faces");
+        Ok(Background::Cubemap(Box::new(faces)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_objs_offsets_follow_the_triangle_counts() {
+        let write = |name: &str, obj: &str| {
+            let path = std::env::temp_dir().join(format!("{name}-{}.obj", std::process::id()));
+            std::fs::write(&path, obj).unwrap();
+            path.to_str().unwrap().to_owned()
+        };
+        // `from_tobj` reads normals by position index, give every vertex its own.
+        let normals = "vn 0 0 1\n".repeat(4);
+        // A quad, two triangles once triangulated.
+        let quad = write(
+            "quad",
+            &format!("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n{normals}f 1//1 2//2 3//3 4//4\n"),
+        );
+        // Four triangles, the faces of a tetrahedron.
+        let tetrahedron = write(
+            "tetrahedron",
+            &format!(
+                "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\n{normals}\
+                 f 1//1 3//3 2//2\nf 1//1 2//2 4//4\nf 1//1 4//4 3//3\nf 2//2 3//3 4//4\n"
+            ),
+        );
+        let white = Material::Lambertian {
+            albedo: Texture::new_from_color(glm::vec3(0.73, 0.73, 0.73)),
+        };
+        let camera = Camera {
+            eye_pos: glm::vec3(0.0, 0.0, 5.0),
+            eye_dir: glm::vec3(0.0, 0.0, -1.0),
+            up: glm::vec3(0.0, 1.0, 0.0),
+            projection: CameraProjection::Perspective { vfov: 30.0 },
+            aperture: 0.0,
+            bokeh: BokehShape::Circle,
+            focus_distance: 10.0,
+            shutter: 0.0,
+        };
+        let frame_data = FrameData {
+            width: 100,
+            height: 100,
+            index: 0,
+        };
+
+        let scene = Scene::from_objs(
+            &[
+                (quad.as_str(), white.clone()),
+                (tetrahedron.as_str(), white),
+            ],
+            camera,
+            RenderParam::default(),
+            frame_data,
+        );
+        std::fs::remove_file(&quad).unwrap();
+        std::fs::remove_file(&tetrahedron).unwrap();
+
+        let list = scene.unwrap().object_list;
+        assert_eq!(list.meshes.len(), 6);
+        assert_eq!(list.object_hashmap[&0], (0, 2));
+        assert_eq!(list.object_hashmap[&1], (2, 6));
+        assert_eq!(list.objects[1].offset, 2);
+    }
+}
@@ -1,7 +1,38 @@
+use super::Background;
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Texture {
     dimensions: (u32, u32),
     data: Vec<[f32; 3]>,
+    filter: TextureFilter,
+    /// The data is sRGB encoded, like most color images, and gets linearized when sampled.
+    /// Solid colors, emission and normal maps are linear.
+    is_srgb: bool,
+    /// Applied to the UVs as `uv * uv_scale + uv_offset` before sampling, the texture repeats
+    /// unless they are left at 1 and 0.
+    uv_scale: glm::Vec2,
+    uv_offset: glm::Vec2,
+    /// Set for [`Texture::new_noise`], the single texel tints the pattern.
+    noise: Option<Noise>,
+}
+
+/// Marble made of Perlin noise, evaluated in the shader at the hit point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Noise {
+    /// Frequency of the stripes along z.
+    scale: f32,
+    /// Layers of noise summed into the turbulence that bends the stripes.
+    octaves: u32,
+}
+
+/// How a texture is sampled between texel centers, edges are clamped unless the texture
+/// repeats.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum TextureFilter {
+    #[default]
+    Nearest,
+    /// Blends the four closest texels.
+    Bilinear,
 }
 
 #[repr(C)]
@@ -10,16 +41,156 @@ struct TextureDescriptor {
     width: u32,
     height: u32,
     offset: u32,
+    flags: u32,
+    uv_scale: [f32; 2],
+    uv_offset: [f32; 2],
+    noise_scale: f32,
+    noise_octaves: u32,
 }
 
+/// Bits of [`TextureDescriptor::flags`], mirrored in the shader.
+const TEXTURE_FLAG_BILINEAR: u32 = 1;
+const TEXTURE_FLAG_SRGB: u32 = 2;
+const TEXTURE_FLAG_REPEAT: u32 = 4;
+const TEXTURE_FLAG_NOISE: u32 = 8;
+
 impl Texture {
     pub fn new_from_color(color: glm::Vec3) -> Self {
         Self {
             dimensions: (1, 1),
             data: vec![[color.x, color.y, color.z]],
+            filter: TextureFilter::Nearest,
+            is_srgb: false,
+            uv_scale: glm::vec2(1.0, 1.0),
+            uv_offset: glm::Vec2::zeros(),
+            noise: None,
+        }
+    }
+
+    /// Samples `f(u, v)` at the center of every texel, row 0 being the top of the image (`v = 1`).
+    pub fn new_from_fn(width: u32, height: u32, f: impl Fn(f32, f32) -> glm::Vec3) -> Self {
+        let data = (0..height)
+            .flat_map(|i| (0..width).map(move |j| (i, j)))
+            .map(|(i, j)| {
+                let u = (j as f32 + 0.5) / width as f32;
+                let v = 1.0 - (i as f32 + 0.5) / height as f32;
+                let color = f(u, v);
+                [color.x, color.y, color.z]
+            })
+            .collect();
+        Self {
+            dimensions: (width, height),
+            data,
+            filter: TextureFilter::Nearest,
+            is_srgb: false,
+            uv_scale: glm::vec2(1.0, 1.0),
+            uv_offset: glm::Vec2::zeros(),
+            noise: None,
+        }
+    }
+
+    /// White marble veined by `octaves` of Perlin noise, computed from the hit position so it
+    /// needs no UVs. The noise is the same on every run.
+    pub fn new_noise(scale: f32, octaves: u32) -> Self {
+        Self {
+            noise: Some(Noise { scale, octaves }),
+            ..Self::new_from_color(glm::vec3(1.0, 1.0, 1.0))
+        }
+    }
+
+    pub fn with_filter(mut self, filter: TextureFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Marks the data as sRGB encoded.
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub fn with_srgb(mut self, is_srgb: bool) -> Self {
+        self.is_srgb = is_srgb;
+        self
+    }
+
+    /// Texture of `width * height` linear colors, given row by row from the top.
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub fn new_from_data(width: u32, height: u32, data: Vec<[f32; 3]>) -> Self {
+        assert_eq!(data.len(), (width * height) as usize);
+        Self {
+            dimensions: (width, height),
+            data,
+            ..Self::new_from_color(glm::Vec3::zeros())
+        }
+    }
+
+    /// Tiles the texture `uv_scale` times across the UVs, shifted by `uv_offset`.
+    pub fn with_uv_transform(mut self, uv_scale: glm::Vec2, uv_offset: glm::Vec2) -> Self {
+        self.uv_scale = uv_scale;
+        self.uv_offset = uv_offset;
+        self
+    }
+
+    fn repeats(&self) -> bool {
+        self.uv_scale != glm::vec2(1.0, 1.0) || self.uv_offset != glm::Vec2::zeros()
+    }
+
+    /// Linear color at `uv`, mirrors `texture_look_up` in the shader. Noise textures depend on
+    /// the hit point and give their tint.
+    pub fn sample(&self, uv: glm::Vec2) -> glm::Vec3 {
+        let (width, height) = self.dimensions;
+        let repeat = self.repeats();
+        let (u, v) = match repeat {
+            true => {
+                let uv = uv.component_mul(&self.uv_scale) + self.uv_offset;
+                (uv.x - uv.x.floor(), 1.0 - (uv.y - uv.y.floor()))
+            }
+            false => (uv.x.clamp(0.0, 1.0), 1.0 - uv.y.clamp(0.0, 1.0)),
+        };
+        let texel = |i: u32, j: u32| {
+            let color = glm::Vec3::from(self.data[(i * width + j) as usize]);
+            match self.is_srgb {
+                true => to_linear_rgb(color),
+                false => color,
+            }
+        };
+
+        match self.filter {
+            TextureFilter::Nearest => {
+                let j = ((u * width as f32) as u32).min(width - 1);
+                let i = ((v * height as f32) as u32).min(height - 1);
+                texel(i, j)
+            }
+            TextureFilter::Bilinear => {
+                // Texel centers sit at half integers, blend the four around the sample point.
+                let (x, y) = (u * width as f32 - 0.5, v * height as f32 - 0.5);
+                let index = |i: f32, n: u32| match repeat {
+                    true => (i - n as f32 * (i / n as f32).floor()) as u32 % n,
+                    false => i.clamp(0.0, (n - 1) as f32) as u32,
+                };
+                let (j0, j1) = (index(x.floor(), width), index(x.floor() + 1.0, width));
+                let (i0, i1) = (index(y.floor(), height), index(y.floor() + 1.0, height));
+                let top = glm::mix(&texel(i0, j0), &texel(i0, j1), x - x.floor());
+                let bottom = glm::mix(&texel(i1, j0), &texel(i1, j1), x - x.floor());
+                glm::mix(&top, &bottom, y - y.floor())
+            }
         }
     }
 
+    /// Tangent space normal map of the height field `height(u, v)`, using central differences.
+    /// `strength` scales the slopes, the field is assumed to wrap around.
+    pub fn normal_map_from_height(
+        width: u32,
+        height: u32,
+        strength: f32,
+        height_fn: impl Fn(f32, f32) -> f32,
+    ) -> Self {
+        let (du, dv) = (1.0 / width as f32, 1.0 / height as f32);
+        Self::new_from_fn(width, height, |u, v| {
+            let dh_du = (height_fn(u + du, v) - height_fn(u - du, v)) / (2.0 * du);
+            let dh_dv = (height_fn(u, v + dv) - height_fn(u, v - dv)) / (2.0 * dv);
+            let n = glm::normalize(&glm::vec3(-strength * dh_du, -strength * dh_dv, 1.0));
+            (n + glm::Vec3::repeat(1.0)) * 0.5
+        })
+    }
+
     pub fn as_slice(&self) -> &[[f32; 3]] {
         &self.data
     }
@@ -27,14 +198,316 @@ impl Texture {
     pub fn dimensions(&self) -> (u32, u32) {
         self.dimensions
     }
+
+    /// The color of a texture made with [`Texture::new_from_color`].
+    pub fn solid_color_mut(&mut self) -> Option<&mut [f32; 3]> {
+        match self.dimensions {
+            (1, 1) => self.data.first_mut(),
+            _ => None,
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        if let Some(noise) = self.noise.as_mut() {
+            let mut changed = ui.color_edit_button_rgb(&mut self.data[0]).changed();
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut noise.scale)
+                        .prefix("Scale: ")
+                        .speed(0.1)
+                        .range(0.0..=100.0),
+                )
+                .changed();
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut noise.octaves)
+                        .prefix("Octaves: ")
+                        .range(1..=10),
+                )
+                .changed();
+            return changed;
+        }
+        match self.solid_color_mut() {
+            Some(color) => ui.color_edit_button_rgb(color).changed(),
+            None => {
+                let mut changed = false;
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "Image texture ({}x{})",
+                        self.dimensions.0, self.dimensions.1
+                    ));
+                    changed |= ui
+                        .radio_value(&mut self.filter, TextureFilter::Nearest, "Nearest")
+                        .changed();
+                    changed |= ui
+                        .radio_value(&mut self.filter, TextureFilter::Bilinear, "Bilinear")
+                        .changed();
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Tiling:");
+                    for value in self.uv_scale.iter_mut() {
+                        changed |= ui
+                            .add(egui::DragValue::new(value).speed(0.05).range(0.01..=100.0))
+                            .changed();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Offset:");
+                    for value in self.uv_offset.iter_mut() {
+                        changed |= ui.add(egui::DragValue::new(value).speed(0.01)).changed();
+                    }
+                });
+                changed
+            }
+        }
+    }
+}
+
+/// Same as `to_linear_rgb` in the shader, decodes sRGB texels before filtering.
+fn to_linear_rgb(c: glm::Vec3) -> glm::Vec3 {
+    c.map(|c| match c <= 0.04045 {
+        true => c / 12.92,
+        false => ((c.max(0.0) + 0.055) / 1.055).powf(2.4),
+    })
 }
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum Material {
-    Lambertian { albedo: Texture },
-    Metal { albedo: Texture, fuzz: f32 },
-    Dialectric { ref_idx: f32 },
-    DiffuseLight { emit: Texture },
+    Lambertian {
+        albedo: Texture,
+    },
+    Metal {
+        albedo: Texture,
+        fuzz: f32,
+    },
+    /// Rough diffuse surface, `sigma` is the roughness in radians. A `sigma` of 0 is the same as
+    /// [`Material::Lambertian`].
+    OrenNayar {
+        albedo: Texture,
+        sigma: f32,
+    },
+    /// `ref_idx` is the index of refraction at 589.3 nm. A non zero `dispersion` is the `B`
+    /// coefficient of Cauchy's equation in µm², around 0.004 for crown glass and 0.01 for flint.
+    Dialectric {
+        ref_idx: f32,
+        dispersion: f32,
+    },
+    /// Diffuse `albedo` under a smooth dielectric of index `ref_idx`: rays reflect off the
+    /// surface with the Fresnel probability and bounce off the albedo otherwise.
+    Plastic {
+        albedo: Texture,
+        ref_idx: f32,
+    },
+    /// Metallic-roughness microfacet surface: GGX distribution, Smith geometry term and Schlick
+    /// Fresnel, over a Lambertian base for the non metallic part. `metallic` blends from a
+    /// dielectric with a reflectance of 0.04 to a metal tinted by `albedo`.
+    CookTorrance {
+        albedo: Texture,
+        metallic: f32,
+        roughness: f32,
+    },
+    /// Emits `emit * strength`, only from the front face unless `two_sided` is set. A light
+    /// that is not `enabled` emits nothing but is still sampled.
+    DiffuseLight {
+        emit: Texture,
+        strength: f32,
+        two_sided: bool,
+        enabled: bool,
+    },
+    /// Wraps another material and perturbs its shading normal with a tangent space normal map,
+    /// stored in `[0, 1]` as `0.5 * (n + 1)`.
+    NormalMapped {
+        base: Box<Material>,
+        normal_map: Texture,
+    },
+    /// Wraps another material under a thin smooth dielectric layer, like car paint. Rays
+    /// bounce off the coat with a probability of its Fresnel reflectance scaled by `strength`,
+    /// `roughness` blurs that reflection like the fuzz of [`Material::Metal`].
+    Clearcoat {
+        base: Box<Material>,
+        strength: f32,
+        roughness: f32,
+    },
+    /// Wraps another material and shifts the uvs it and its normal map are sampled at as if the
+    /// surface had the relief of `height_map`, white being the top. `height_scale` is the depth
+    /// of the relief in uv units, zero leaves the surface flat.
+    Parallax {
+        base: Box<Material>,
+        height_map: Texture,
+        height_scale: f32,
+    },
+    /// Wraps another material and removes the surface where `opacity` is below
+    /// `alpha_cutoff`, like leaves cut out of a quad. Rays go through the removed parts
+    /// unchanged. Textures have no alpha channel, the red one of `opacity` is used.
+    Cutout {
+        base: Box<Material>,
+        opacity: Texture,
+        alpha_cutoff: f32,
+    },
+}
+
+impl Material {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Material::Lambertian { .. } => "Lambertian",
+            Material::Metal { .. } => "Metal",
+            Material::OrenNayar { .. } => "OrenNayar",
+            Material::Dialectric { .. } => "Dialectric",
+            Material::Plastic { .. } => "Plastic",
+            Material::CookTorrance { .. } => "CookTorrance",
+            Material::DiffuseLight { .. } => "DiffuseLight",
+            Material::NormalMapped { base, .. }
+            | Material::Clearcoat { base, .. }
+            | Material::Parallax { base, .. }
+            | Material::Cutout { base, .. } => base.name(),
+        }
+    }
+
+    /// Editor for the material parameters, returns true when something changed.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+
+        // Both diffuse models share their parameters, allow switching between them.
+        let rough = matches!(self, Material::OrenNayar { .. });
+        if let Material::Lambertian { albedo } | Material::OrenNayar { albedo, .. } = self {
+            let mut switch_to_rough = rough;
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut switch_to_rough, false, "Lambertian");
+                ui.radio_value(&mut switch_to_rough, true, "Oren-Nayar");
+            });
+            if switch_to_rough != rough {
+                let albedo = albedo.clone();
+                *self = match switch_to_rough {
+                    true => Material::OrenNayar { albedo, sigma: 0.5 },
+                    false => Material::Lambertian { albedo },
+                };
+                changed = true;
+            }
+        }
+
+        match self {
+            Material::Lambertian { albedo } => {
+                ui.horizontal(|ui| {
+                    ui.label("Albedo:");
+                    changed |= albedo.ui(ui);
+                });
+            }
+            Material::Metal { albedo, fuzz } => {
+                ui.horizontal(|ui| {
+                    ui.label("Albedo:");
+                    changed |= albedo.ui(ui);
+                });
+                ui.label("Fuzz:");
+                changed |= ui.add(egui::Slider::new(fuzz, 0.0..=1.0)).changed();
+            }
+            Material::OrenNayar { albedo, sigma } => {
+                ui.horizontal(|ui| {
+                    ui.label("Albedo:");
+                    changed |= albedo.ui(ui);
+                });
+                ui.label("Roughness (sigma):");
+                changed |= ui
+                    .add(egui::Slider::new(sigma, 0.0..=std::f32::consts::FRAC_PI_2))
+                    .changed();
+            }
+            Material::Dialectric {
+                ref_idx,
+                dispersion,
+            } => {
+                ui.label("Refraction index:");
+                changed |= ui.add(egui::Slider::new(ref_idx, 1.0..=3.0)).changed();
+                ui.label("Dispersion:");
+                changed |= ui.add(egui::Slider::new(dispersion, 0.0..=0.05)).changed();
+            }
+            Material::Plastic { albedo, ref_idx } => {
+                ui.horizontal(|ui| {
+                    ui.label("Albedo:");
+                    changed |= albedo.ui(ui);
+                });
+                ui.label("Refraction index:");
+                changed |= ui.add(egui::Slider::new(ref_idx, 1.0..=3.0)).changed();
+            }
+            Material::CookTorrance {
+                albedo,
+                metallic,
+                roughness,
+            } => {
+                ui.horizontal(|ui| {
+                    ui.label("Albedo:");
+                    changed |= albedo.ui(ui);
+                });
+                ui.label("Metallic:");
+                changed |= ui.add(egui::Slider::new(metallic, 0.0..=1.0)).changed();
+                ui.label("Roughness:");
+                changed |= ui.add(egui::Slider::new(roughness, 0.0..=1.0)).changed();
+            }
+            Material::DiffuseLight {
+                emit,
+                strength,
+                two_sided,
+                enabled,
+            } => {
+                ui.horizontal(|ui| {
+                    changed |= ui.checkbox(enabled, "On").changed();
+                    changed |= ui.checkbox(two_sided, "Two sided").changed();
+                });
+                // The color picker stops at 1, move the brightness of older colors into the
+                // strength. The emission stays the same.
+                if let Some(emission) = emit.solid_color_mut() {
+                    let max = emission.iter().copied().fold(0.0_f32, f32::max);
+                    if max > 1.0 {
+                        *emission = emission.map(|c| c / max);
+                        *strength *= max;
+                    }
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Emission:");
+                    changed |= emit.ui(ui);
+                });
+                ui.label("Strength:");
+                changed |= ui.add(egui::Slider::new(strength, 0.0..=100.0)).changed();
+            }
+            Material::NormalMapped { base, normal_map } => {
+                let (width, height) = normal_map.dimensions();
+                ui.label(format!("Normal map ({width}x{height})"));
+                changed |= base.ui(ui);
+            }
+            Material::Clearcoat {
+                base,
+                strength,
+                roughness,
+            } => {
+                changed |= base.ui(ui);
+                ui.label("Clearcoat:");
+                changed |= ui.add(egui::Slider::new(strength, 0.0..=1.0)).changed();
+                ui.label("Clearcoat roughness:");
+                changed |= ui.add(egui::Slider::new(roughness, 0.0..=1.0)).changed();
+            }
+            Material::Parallax {
+                base,
+                height_map,
+                height_scale,
+            } => {
+                let (width, height) = height_map.dimensions();
+                ui.label(format!("Height map ({width}x{height})"));
+                changed |= base.ui(ui);
+                ui.label("Height scale:");
+                changed |= ui.add(egui::Slider::new(height_scale, 0.0..=0.1)).changed();
+            }
+            Material::Cutout {
+                base,
+                opacity,
+                alpha_cutoff,
+            } => {
+                let (width, height) = opacity.dimensions();
+                ui.label(format!("Opacity ({width}x{height})"));
+                changed |= base.ui(ui);
+                ui.label("Alpha cutoff:");
+                changed |= ui.add(egui::Slider::new(alpha_cutoff, 0.0..=1.0)).changed();
+            }
+        }
+        changed
+    }
 }
 
 #[repr(C)]
@@ -43,20 +516,107 @@ pub struct GpuMaterial {
     id: u32,
     descriptor: TextureDescriptor,
     x: f32,
+    y: f32,
+    flags: u32,
+    normal_map: TextureDescriptor,
+    clearcoat: f32,
+    clearcoat_roughness: f32,
+    height_map: TextureDescriptor,
+    height_scale: f32,
+    opacity: TextureDescriptor,
+    alpha_cutoff: f32,
 }
+
+/// Bits of [`GpuMaterial::flags`], mirrored in the shader.
+const MATERIAL_FLAG_TWO_SIDED: u32 = 1;
+const MATERIAL_FLAG_DISABLED: u32 = 2;
 impl GpuMaterial {
+    /// Descriptor of a material without a texture in that slot.
+    const NO_TEXTURE: TextureDescriptor = TextureDescriptor {
+        width: 0,
+        height: 0,
+        offset: 0xffffffff,
+        flags: 0,
+        uv_scale: [1.0, 1.0],
+        uv_offset: [0.0, 0.0],
+        noise_scale: 0.0,
+        noise_octaves: 0,
+    };
+
     fn append_to_global_texture_data(
         texture: &Texture,
         global_texture_data: &mut Vec<[f32; 3]>,
     ) -> TextureDescriptor {
-        let dimensions = texture.dimensions();
         let offset = global_texture_data.len() as u32;
         global_texture_data.extend_from_slice(texture.as_slice());
+        Self::descriptor(texture, offset)
+    }
+
+    /// Describes `texture` with its data at `offset` in the texture data.
+    fn descriptor(texture: &Texture, offset: u32) -> TextureDescriptor {
+        let dimensions = texture.dimensions();
         TextureDescriptor {
             width: dimensions.0,
             height: dimensions.1,
             offset,
+            flags: match texture.filter {
+                TextureFilter::Nearest => 0,
+                TextureFilter::Bilinear => TEXTURE_FLAG_BILINEAR,
+            } | match texture.is_srgb {
+                true => TEXTURE_FLAG_SRGB,
+                false => 0,
+            } | match texture.repeats() {
+                true => TEXTURE_FLAG_REPEAT,
+                false => 0,
+            } | match texture.noise {
+                Some(_) => TEXTURE_FLAG_NOISE,
+                None => 0,
+            },
+            uv_scale: texture.uv_scale.into(),
+            uv_offset: texture.uv_offset.into(),
+            noise_scale: texture.noise.map_or(0.0, |noise| noise.scale),
+            noise_octaves: texture.noise.map_or(0, |noise| noise.octaves),
+        }
+    }
+
+    /// Converts every material, returning them along with the texture data they index into.
+    /// The faces of a cubemap `background` come first in the texture data, see
+    /// [`GpuMaterial::cubemap_faces`].
+    pub fn from_materials(
+        materials: &[Material],
+        background: &Background,
+    ) -> (Vec<GpuMaterial>, Vec<[f32; 3]>) {
+        let mut global_texture_data = Vec::new();
+        if let Background::Cubemap(faces) = background {
+            for face in faces.iter() {
+                global_texture_data.extend_from_slice(face.as_slice());
+            }
         }
+        let material_data = materials
+            .iter()
+            .map(|material| GpuMaterial::new(material, &mut global_texture_data))
+            .collect();
+        (material_data, global_texture_data)
+    }
+
+    /// Width, height, offset and flags of the faces of a cubemap `background` in the texture
+    /// data of [`GpuMaterial::from_materials`], zeros for other backgrounds.
+    pub fn cubemap_faces(background: &Background) -> [[u32; 4]; 6] {
+        let mut faces = [[0; 4]; 6];
+        if let Background::Cubemap(textures) = background {
+            let mut offset = 0;
+            for (face, texture) in faces.iter_mut().zip(textures.iter()) {
+                let descriptor = Self::descriptor(texture, offset);
+                *face = [
+                    descriptor.width,
+                    descriptor.height,
+                    descriptor.offset,
+                    descriptor.flags,
+                ];
+                offset += texture.as_slice().len() as u32;
+            }
+        }
+        faces
     }
 
     pub fn new(material: &Material, global_texture_data: &mut Vec<[f32; 3]>) -> Self {
@@ -65,26 +625,277 @@ impl GpuMaterial {
                 id: 0,
                 descriptor: Self::append_to_global_texture_data(albedo, global_texture_data),
                 x: 0.0,
+                y: 0.0,
+                flags: 0,
+                normal_map: Self::NO_TEXTURE,
+                clearcoat: 0.0,
+                clearcoat_roughness: 0.0,
+                height_map: Self::NO_TEXTURE,
+                height_scale: 0.0,
+                opacity: Self::NO_TEXTURE,
+                alpha_cutoff: 0.0,
             },
             Material::Metal { albedo, fuzz } => Self {
                 id: 1,
                 descriptor: Self::append_to_global_texture_data(albedo, global_texture_data),
                 x: *fuzz,
+                y: 0.0,
+                flags: 0,
+                normal_map: Self::NO_TEXTURE,
+                clearcoat: 0.0,
+                clearcoat_roughness: 0.0,
+                height_map: Self::NO_TEXTURE,
+                height_scale: 0.0,
+                opacity: Self::NO_TEXTURE,
+                alpha_cutoff: 0.0,
+            },
+            Material::OrenNayar { albedo, sigma } => Self {
+                id: 4,
+                descriptor: Self::append_to_global_texture_data(albedo, global_texture_data),
+                x: *sigma,
+                y: 0.0,
+                flags: 0,
+                normal_map: Self::NO_TEXTURE,
+                clearcoat: 0.0,
+                clearcoat_roughness: 0.0,
+                height_map: Self::NO_TEXTURE,
+                height_scale: 0.0,
+                opacity: Self::NO_TEXTURE,
+                alpha_cutoff: 0.0,
             },
-            Material::Dialectric { ref_idx } => Self {
+            Material::Dialectric {
+                ref_idx,
+                dispersion,
+            } => Self {
                 id: 2,
-                descriptor: TextureDescriptor {
-                    width: 0,
-                    height: 0,
-                    offset: 0xffffffff,
-                },
+                descriptor: Self::NO_TEXTURE,
+                x: *ref_idx,
+                y: *dispersion,
+                flags: 0,
+                normal_map: Self::NO_TEXTURE,
+                clearcoat: 0.0,
+                clearcoat_roughness: 0.0,
+                height_map: Self::NO_TEXTURE,
+                height_scale: 0.0,
+                opacity: Self::NO_TEXTURE,
+                alpha_cutoff: 0.0,
+            },
+            Material::Plastic { albedo, ref_idx } => Self {
+                id: 5,
+                descriptor: Self::append_to_global_texture_data(albedo, global_texture_data),
                 x: *ref_idx,
+                y: 0.0,
+                flags: 0,
+                normal_map: Self::NO_TEXTURE,
+                clearcoat: 0.0,
+                clearcoat_roughness: 0.0,
+                height_map: Self::NO_TEXTURE,
+                height_scale: 0.0,
+                opacity: Self::NO_TEXTURE,
+                alpha_cutoff: 0.0,
+            },
+            Material::CookTorrance {
+                albedo,
+                metallic,
+                roughness,
+            } => Self {
+                id: 6,
+                descriptor: Self::append_to_global_texture_data(albedo, global_texture_data),
+                x: *metallic,
+                y: *roughness,
+                flags: 0,
+                normal_map: Self::NO_TEXTURE,
+                clearcoat: 0.0,
+                clearcoat_roughness: 0.0,
+                height_map: Self::NO_TEXTURE,
+                height_scale: 0.0,
+                opacity: Self::NO_TEXTURE,
+                alpha_cutoff: 0.0,
             },
-            Material::DiffuseLight { emit } => Self {
+            Material::DiffuseLight {
+                emit,
+                strength,
+                two_sided,
+                enabled,
+            } => Self {
                 id: 3,
                 descriptor: Self::append_to_global_texture_data(emit, global_texture_data),
-                x: 0.0,
+                x: *strength,
+                y: 0.0,
+                flags: match two_sided {
+                    true => MATERIAL_FLAG_TWO_SIDED,
+                    false => 0,
+                } | match enabled {
+                    true => 0,
+                    false => MATERIAL_FLAG_DISABLED,
+                },
+                normal_map: Self::NO_TEXTURE,
+                clearcoat: 0.0,
+                clearcoat_roughness: 0.0,
+                height_map: Self::NO_TEXTURE,
+                height_scale: 0.0,
+                opacity: Self::NO_TEXTURE,
+                alpha_cutoff: 0.0,
+            },
+            Material::NormalMapped { base, normal_map } => Self {
+                normal_map: Self::append_to_global_texture_data(normal_map, global_texture_data),
+                ..Self::new(base, global_texture_data)
             },
+            Material::Clearcoat {
+                base,
+                strength,
+                roughness,
+            } => Self {
+                clearcoat: *strength,
+                clearcoat_roughness: *roughness,
+                ..Self::new(base, global_texture_data)
+            },
+            Material::Parallax {
+                base,
+                height_map,
+                height_scale,
+            } => Self {
+                height_map: Self::append_to_global_texture_data(height_map, global_texture_data),
+                height_scale: *height_scale,
+                ..Self::new(base, global_texture_data)
+            },
+            Material::Cutout {
+                base,
+                opacity,
+                alpha_cutoff,
+            } => Self {
+                opacity: Self::append_to_global_texture_data(opacity, global_texture_data),
+                alpha_cutoff: *alpha_cutoff,
+                ..Self::new(base, global_texture_data)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bilinear_filter_ramps_between_texels() {
+        // Black on the left, white on the right.
+        let data = vec![[0.0; 3], [1.0; 3], [0.0; 3], [1.0; 3]];
+        let nearest = Texture::new_from_data(2, 2, data);
+        let bilinear = nearest.clone().with_filter(TextureFilter::Bilinear);
+
+        let samples = |texture: &Texture| {
+            (0..=32)
+                .map(|k| texture.sample(glm::vec2(k as f32 / 32.0, 0.5)).x)
+                .collect::<Vec<_>>()
+        };
+        let blocks = samples(&nearest);
+        assert!(blocks.iter().all(|&c| c == 0.0 || c == 1.0));
+
+        let ramp = samples(&bilinear);
+        // Flat up to the texel centers, clamped at the edges, linear between them.
+        assert_eq!(ramp[0], 0.0);
+        assert_eq!(ramp[8], 0.0);
+        assert!((ramp[16] - 0.5).abs() < 1e-6);
+        assert_eq!(ramp[24], 1.0);
+        assert_eq!(ramp[32], 1.0);
+        for pair in ramp.windows(2) {
+            assert!(pair[1] >= pair[0] && pair[1] - pair[0] <= 1.0 / 16.0 + 1e-6);
+        }
+    }
+
+    #[test]
+    fn uv_scale_tiles_the_texture() {
+        // One black and one white square, tiled 4 times along u with a quarter tile of offset.
+        let checker = Texture::new_from_data(2, 1, vec![[0.0; 3], [1.0; 3]])
+            .with_uv_transform(glm::vec2(4.0, 1.0), glm::vec2(0.25, 0.0));
+
+        let row = (0..64)
+            .map(|k| checker.sample(glm::vec2((k as f32 + 0.5) / 64.0, 0.5)).x)
+            .collect::<Vec<_>>();
+        let rising = row
+            .windows(2)
+            .filter(|p| p[0] == 0.0 && p[1] == 1.0)
+            .count();
+        assert_eq!(rising, 4);
+        // The offset shifts the pattern by half a square.
+        assert_eq!(row[..4], [0.0; 4]);
+        assert_eq!(row[4..12], [1.0; 8]);
+    }
+
+    #[test]
+    fn srgb_texels_are_decoded() {
+        let grey = |is_srgb| {
+            Texture::new_from_data(1, 1, vec![[0.5; 3]])
+                .with_srgb(is_srgb)
+                .sample(glm::vec2(0.5, 0.5))
+        };
+        assert!((grey(true) - glm::Vec3::repeat(0.214)).amax() < 1e-3);
+        assert_eq!(grey(false), glm::Vec3::repeat(0.5));
+    }
+
+    /// Throughput of one `scatter_cook_torrance` sample of the shader, off a white surface
+    /// facing +z and seen from `wo`. Samples the shader drops weigh nothing.
+    fn cook_torrance_throughput(
+        metallic: f32,
+        roughness: f32,
+        wo: glm::Vec3,
+        rng: &mut impl rand::Rng,
+    ) -> f32 {
+        let fresnel = |cosine: f32, f0: f32| f0 + (1.0 - f0) * (1.0 - cosine).powi(5);
+        let smith_g1 = |cosine: f32, alpha: f32| {
+            let a2 = alpha * alpha;
+            2.0 * cosine / (cosine + (a2 + (1.0 - a2) * cosine * cosine).sqrt())
+        };
+        // Every channel behaves the same with an albedo of 1.
+        let f0 = 0.04 + (1.0 - 0.04) * metallic;
+        let n_dot_v = wo.z.clamp(1e-4, 1.0);
+        let fresnel_view = fresnel(n_dot_v, f0);
+        let specular_probability = fresnel_view + (1.0 - fresnel_view) * metallic;
+        if rng.gen::<f32>() >= specular_probability {
+            // Cosine sampled, the Lambertian BRDF and the pdf cancel out. What is left,
+            // albedo * (1 - F) / (1 - mean(F)), is the albedo for a grey F.
+            return 1.0;
+        }
+
+        let alpha = (roughness * roughness).max(0.001);
+        let (u1, u2) = (rng.gen::<f32>(), rng.gen::<f32>());
+        let cos_theta = ((1.0 - u2) / (1.0 + (alpha * alpha - 1.0) * u2)).sqrt();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * std::f32::consts::PI * u1;
+        let h = glm::vec3(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        let wi = 2.0 * wo.dot(&h) * h - wo;
+        let (n_dot_l, v_dot_h) = (wi.z, wo.dot(&h));
+        if n_dot_l <= 0.0 || v_dot_h <= 0.0 {
+            return 0.0;
+        }
+        let g = smith_g1(n_dot_v, alpha) * smith_g1(n_dot_l, alpha);
+        fresnel(v_dot_h, f0) * g * v_dot_h / (n_dot_v * cos_theta) / specular_probability
+    }
+
+    #[test]
+    fn white_cook_torrance_does_not_add_energy() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let samples = 50_000;
+        for metallic in [0.0, 0.5, 1.0] {
+            for roughness in [0.05, 0.3, 0.6, 1.0] {
+                for n_dot_v in [1.0_f32, 0.5, 0.1] {
+                    let wo = glm::vec3((1.0 - n_dot_v * n_dot_v).sqrt(), 0.0, n_dot_v);
+                    let weights = (0..samples)
+                        .map(|_| cook_torrance_throughput(metallic, roughness, wo, &mut rng))
+                        .collect::<Vec<_>>();
+                    let mean = weights.iter().sum::<f32>() / samples as f32;
+                    let variance =
+                        weights.iter().map(|w| (w - mean).powi(2)).sum::<f32>() / samples as f32;
+                    // Four standard errors of slack for the Monte Carlo noise.
+                    let tolerance = 4.0 * (variance / samples as f32).sqrt();
+                    assert!(
+                        mean <= 1.0 + tolerance,
+                        "metallic {metallic}, roughness {roughness}, cos {n_dot_v}: {mean}"
+                    );
+                }
+            }
         }
     }
 }
@@ -0,0 +1,57 @@
+/// Homogeneous participating medium filling the whole scene, coefficients are per unit of
+/// scene length.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Medium {
+    pub sigma_a: glm::Vec3,
+    pub sigma_s: glm::Vec3,
+}
+
+impl Medium {
+    /// Grey fog scattering 80% of what it attenuates.
+    pub fn fog(density: f32) -> Self {
+        Self {
+            sigma_a: glm::Vec3::repeat(0.2 * density),
+            sigma_s: glm::Vec3::repeat(0.8 * density),
+        }
+    }
+
+    /// Mean extinction coefficient.
+    pub fn density(&self) -> f32 {
+        (self.sigma_a + self.sigma_s).mean()
+    }
+
+    /// Scales both coefficients so that the mean extinction becomes `density`.
+    pub fn set_density(&mut self, density: f32) {
+        let current = self.density();
+        if current > 0.0 {
+            self.sigma_a *= density / current;
+            self.sigma_s *= density / current;
+        } else {
+            *self = Self::fog(density);
+        }
+    }
+
+    /// Returns true when the medium changed.
+    pub fn ui(medium: &mut Option<Medium>, ui: &mut egui::Ui) -> bool {
+        let mut enabled = medium.is_some();
+        let mut changed = ui.checkbox(&mut enabled, "Fog").changed();
+        match (enabled, medium.as_mut()) {
+            (true, None) => *medium = Some(Self::fog(0.001)),
+            (false, Some(_)) => *medium = None,
+            _ => {}
+        }
+
+        if let Some(medium) = medium.as_mut() {
+            let mut density = medium.density();
+            ui.label("Density:");
+            if ui
+                .add(egui::Slider::new(&mut density, 1e-5..=1.0).logarithmic(true))
+                .changed()
+            {
+                medium.set_density(density);
+                changed = true;
+            }
+        }
+        changed
+    }
+}
@@ -1,11 +1,41 @@
 mod camera;
-pub use camera::{Camera, CameraController, GpuCamera};
+#[allow(unused_imports)]
+pub use camera::{BokehShape, Camera, CameraController, CameraProjection, GamepadInput, GpuCamera};
+
+mod camera_path;
+pub use camera_path::CameraPath;
+
+mod settings;
+pub use settings::Settings;
 
 mod material;
-pub use material::{GpuMaterial, Material, Texture};
+pub use material::{GpuMaterial, Material, Texture, TextureFilter};
+
+mod builder;
+pub use builder::SceneBuilder;
+
+mod background;
+pub use background::Background;
+
+mod medium;
+pub use medium::Medium;
 
-use crate::object::{
-    self, rotate, scale, translate, Light, Mesh, Object, ObjectList, ObjectType, Sphere,
+#[cfg(not(target_arch = "wasm32"))]
+mod file;
+
+mod validation;
+#[allow(unused_imports)]
+pub use validation::SceneError;
+
+use crate::{
+    object::{
+        center_surface, rotate, scale, transform, translate, Light, Mesh, ObjectList, ObjectType,
+        PointLight, Sphere,
+    },
+    utils::{
+        bvh::{build_bvh_flat, Aabb, Bounded},
+        ray::{Intersect, Ray},
+    },
 };
 
 pub type SceneCreator = fn(RenderParam, FrameData) -> Scene;
@@ -13,58 +43,338 @@ pub type SceneCreator = fn(RenderParam, FrameData) -> Scene;
 pub struct SceneDescriptor {
     pub name: &'static str,
     pub creator: SceneCreator,
+    /// Recommended [`RenderParam::max_depth`], see [`RenderParam::default_for_scene`].
+    pub max_depth: u32,
+    /// Recommended [`RenderParam::samples_max_per_pixel`].
+    pub samples_max_per_pixel: u32,
 }
 
 pub const AVAILABLE_SCENES: &[SceneDescriptor] = &[
+    // Closed box lit from a small light, most paths bounce off several walls before reaching
+    // it.
     SceneDescriptor {
         name: "Cornell Box (No Suzanne)",
-        creator: Scene::cornell_scene_without_suzanne,
+        creator: |render_param, frame_data| {
+            Scene::cornell_scene(CornellOptions::WITHOUT_SUZANNE, render_param, frame_data)
+        },
+        max_depth: 20,
+        samples_max_per_pixel: 500,
     },
     SceneDescriptor {
         name: "Cornell Box (with Suzanne, a bit heavy)",
-        creator: Scene::cornell_scene,
+        creator: |render_param, frame_data| {
+            Scene::cornell_scene(CornellOptions::default(), render_param, frame_data)
+        },
+        max_depth: 20,
+        samples_max_per_pixel: 500,
     },
+    // Open sky, paths escape after a few bounces but the glass spheres need some more.
     SceneDescriptor {
         name: "Raytracing One Week (heavy scene)",
         creator: Scene::raytracing_scene_oneweek,
+        max_depth: 10,
+        samples_max_per_pixel: 200,
+    },
+    // Motion blur takes more samples to smooth the streaks.
+    SceneDescriptor {
+        name: "Raytracing One Week, bouncing spheres (heavy scene)",
+        creator: Scene::raytracing_scene_bouncing,
+        max_depth: 10,
+        samples_max_per_pixel: 400,
+    },
+    SceneDescriptor {
+        name: "Normal mapping",
+        creator: Scene::normal_mapping_scene,
+        max_depth: 8,
+        samples_max_per_pixel: 200,
     },
 ];
 
+/// Variations of [`Scene::cornell_scene`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CornellOptions {
+    /// Load the suzanne mesh next to the boxes.
+    pub load_suzanne: bool,
+    /// Give suzanne its own gold metal material instead of the white walls one.
+    pub extra_material: bool,
+    /// Marble made of noise on the floor instead of the white walls material.
+    pub marble_floor: bool,
+    /// A blue plastic sphere in front of the boxes.
+    pub plastic_sphere: bool,
+    /// A rough gold Cook-Torrance sphere in front of the boxes.
+    pub pbr_sphere: bool,
+    /// Bricks with parallax relief on the back wall instead of the white walls material.
+    pub brick_wall: bool,
+    /// A leaf cut out of a quad with an opacity texture, left of the boxes.
+    pub cutout_leaf: bool,
+}
+
+impl CornellOptions {
+    pub const WITHOUT_SUZANNE: Self = Self {
+        load_suzanne: false,
+        extra_material: false,
+        marble_floor: false,
+        plastic_sphere: false,
+        pbr_sphere: false,
+        brick_wall: false,
+        cutout_leaf: false,
+    };
+}
+
+impl Default for CornellOptions {
+    fn default() -> Self {
+        Self {
+            load_suzanne: true,
+            extra_material: true,
+            marble_floor: true,
+            plastic_sphere: true,
+            pbr_sphere: true,
+            brick_wall: true,
+            cutout_leaf: true,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Scene {
     pub materials: Vec<Material>,
     pub spheres: Vec<Sphere>,
+    /// Emissive objects sampled as lights.
     pub lights: Vec<Light>,
+    pub point_lights: Vec<PointLight>,
     pub camera: Camera,
+    /// Named viewpoints to jump to, empty for scenes loaded from a file.
+    pub camera_presets: Vec<(String, Camera)>,
     pub camera_controller: CameraController,
     pub render_param: RenderParam,
     pub frame_data: FrameData,
     pub object_list: ObjectList,
+    pub background: Background,
+    /// Fog filling the scene, if any.
+    pub medium: Option<Medium>,
+    /// Constant light every diffuse hit receives on top of the traced one, a cheap fill for
+    /// previews. Black disables it.
+    pub ambient: glm::Vec3,
 }
 
-impl PartialEq for Scene {
-    fn eq(&self, other: &Self) -> bool {
-        self.materials == other.materials
-            && self.spheres == other.spheres
-            && self.camera == other.camera
-            && self.frame_data == other.frame_data
-            && self.camera_controller == other.camera_controller
-    }
+/// Primitive and buffer counts of a [`Scene`], see [`Scene::stats`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SceneStats {
+    pub sphere_count: usize,
+    pub triangle_count: usize,
+    pub material_count: usize,
+    pub light_count: usize,
+    pub bvh_nodes: usize,
+}
+
+/// Closest intersection found by [`Scene::intersect`].
+#[derive(Clone, Copy, Debug)]
+pub struct SceneHit {
+    pub t: f32,
+    pub point: glm::Vec3,
+    /// Id of the [`crate::object::Object`] that was hit.
+    pub object_id: u32,
 }
 
+// Same bounds as MIN_T / MAX_T in the shader.
+const MIN_T: f32 = 0.001;
+const MAX_T: f32 = 1000.0;
+
 impl Scene {
+    /// Casts `ray` against every sphere and triangle on the CPU, for picking.
+    pub fn intersect(&self, ray: &Ray) -> Option<SceneHit> {
+        fn closest<T: Bounded + Intersect>(primitives: &[T], ray: &Ray, t_max: f32) -> Option<f32> {
+            primitives
+                .iter()
+                .filter(|p| p.aabb().hit(ray, MIN_T, t_max))
+                .filter_map(|p| p.intersect(ray, MIN_T, t_max))
+                .min_by(|a, b| a.total_cmp(b))
+        }
+
+        let mut hit: Option<SceneHit> = None;
+        for object in &self.object_list.objects {
+            let range = object.offset as usize..(object.offset + object.count) as usize;
+            let t_max = hit.map_or(MAX_T, |h| h.t);
+            let t = match ObjectType::from(object.obj_type) {
+                ObjectType::Sphere => closest(&self.spheres[range], ray, t_max),
+                ObjectType::Mesh => closest(&self.object_list.meshes[range], ray, t_max),
+            };
+            if let Some(t) = t {
+                hit = Some(SceneHit {
+                    t,
+                    point: ray.at(t),
+                    object_id: object.id,
+                });
+            }
+        }
+        hit
+    }
+
+    /// Adds a sphere with a material of its own, returns the id of the new object.
+    pub fn add_sphere(&mut self, center: glm::Vec3, radius: f32, material: Material) -> u32 {
+        self.materials.push(material);
+        let material_idx = (self.materials.len() - 1) as u32;
+        self.spheres.push(Sphere::new(center, radius, material_idx));
+        self.object_list.add_sphere(None);
+        self.object_list.counter - 1
+    }
+
+    /// Removes the object `id` along with its primitives and lights. Its material stays, other
+    /// objects may share it.
+    ///
+    /// Refuses to remove the last sphere or the last light since storage buffers can't be empty.
+    pub fn remove_object(&mut self, id: u32) -> bool {
+        let Some(object) = self.object_list.objects.iter().find(|o| o.id == id) else {
+            return false;
+        };
+
+        if ObjectType::from(object.obj_type) == ObjectType::Sphere
+            && object.count as usize == self.spheres.len()
+        {
+            log::warn!("Can't remove object {}, it holds the last spheres", id);
+            return false;
+        }
+        if !self.lights.is_empty() && self.lights.iter().all(|l| l.id == id) {
+            log::warn!("Can't remove object {}, it is the last light", id);
+            return false;
+        }
+
+        let Some(removed) = self.object_list.remove(id) else {
+            return false;
+        };
+
+        if ObjectType::from(removed.obj_type) == ObjectType::Sphere {
+            let start = removed.offset as usize;
+            self.spheres.drain(start..start + removed.count as usize);
+        }
+        if self.object_list.meshes.is_empty() {
+            self.object_list.meshes.push(Mesh::empty());
+        }
+
+        self.lights.retain(|l| l.id != id);
+        for light in self.lights.iter_mut() {
+            if light.id > id {
+                light.id -= 1;
+            }
+        }
+        true
+    }
+
+    /// Samples the object `id` as a light, giving it a white emissive material if it has none.
+    /// Returns false if there is no such object or it already is a light.
+    pub fn add_light(&mut self, id: u32) -> bool {
+        let Some(object) = self.object_list.objects.iter().find(|o| o.id == id) else {
+            return false;
+        };
+        if self.lights.iter().any(|l| l.id == id) {
+            return false;
+        }
+        let area = self.object_list.object_area(object, &self.spheres);
+        self.lights
+            .push(Light::new(id, ObjectType::from(object.obj_type), area));
+
+        if let Some(material_idx) = self.object_material(id) {
+            let material = &mut self.materials[material_idx as usize];
+            if !matches!(material, Material::DiffuseLight { .. }) {
+                *material = Material::DiffuseLight {
+                    emit: Texture::new_from_color(glm::vec3(1.0, 1.0, 1.0)),
+                    strength: 4.0,
+                    two_sided: false,
+                    enabled: true,
+                };
+            }
+        }
+        true
+    }
+
+    /// Material used by the object `id`, every primitive of an object shares it.
+    pub fn object_material(&self, id: u32) -> Option<u32> {
+        let object = self.object_list.objects.iter().find(|o| o.id == id)?;
+        let offset = object.offset as usize;
+        match ObjectType::from(object.obj_type) {
+            ObjectType::Sphere => self.spheres.get(offset).map(|s| s.material_idx),
+            ObjectType::Mesh => self.object_list.meshes.get(offset).map(|m| m.material_idx),
+        }
+    }
+
+    /// Contents of the lights buffer: the emissive objects followed by the point lights.
+    pub fn gpu_lights(&self) -> Vec<Light> {
+        let mut lights = self.lights.clone();
+        lights.extend(self.point_lights.iter().map(Light::from));
+        // Storage buffers can't be empty, a point light without intensity lights nothing.
+        if lights.is_empty() {
+            lights.push(Light::from(&PointLight {
+                position: glm::Vec3::zeros(),
+                color: glm::Vec3::zeros(),
+                intensity: 0.0,
+            }));
+        }
+        lights
+    }
+
+    /// Counts of what gets uploaded. Builds the BVH to count its nodes, so cache the result.
+    pub fn stats(&self) -> SceneStats {
+        SceneStats {
+            sphere_count: self.spheres.len(),
+            triangle_count: self.object_list.meshes.len(),
+            material_count: self.materials.len(),
+            light_count: self.lights.len(),
+            bvh_nodes: build_bvh_flat(&self.spheres, &self.object_list.meshes).len(),
+        }
+    }
+
+    /// Bounding box of every primitive belonging to the object `id`.
+    pub fn object_aabb(&self, id: u32) -> Option<Aabb> {
+        let object = self.object_list.objects.iter().find(|o| o.id == id)?;
+        Some(self.object_list.object_aabb(object, &self.spheres))
+    }
+
+    /// Bounding box of the whole scene.
+    pub fn bounds(&self) -> Aabb {
+        self.object_list.scene_aabb(&self.spheres)
+    }
+
+    /// Point to frame the scene around: the center of the mesh surfaces, which unlike the
+    /// center of the bounds is not pulled away by a few outlying triangles. The center of the
+    /// bounds for scenes made of spheres only.
+    pub fn framing_center(&self) -> glm::Vec3 {
+        let meshes = &self.object_list.meshes;
+        match meshes.iter().any(|m| m.area() > 0.0) {
+            true => center_surface(meshes),
+            false => self.bounds().center(),
+        }
+    }
+
     pub fn raytracing_scene_oneweek(render_param: RenderParam, frame_data: FrameData) -> Self {
-        let mut spheres = Vec::new();
-        let mut materials = Vec::new();
-        let mut lights = Vec::new();
-        let mut object_list = ObjectList::new_empty_mesh();
+        Self::oneweek_scene(false, render_param, frame_data)
+    }
+
+    /// [`Scene::raytracing_scene_oneweek`] with the small diffuse spheres bouncing up while
+    /// the shutter is open.
+    pub fn raytracing_scene_bouncing(render_param: RenderParam, frame_data: FrameData) -> Self {
+        Self::oneweek_scene(true, render_param, frame_data)
+    }
 
+    fn oneweek_scene(bouncing: bool, render_param: RenderParam, frame_data: FrameData) -> Self {
+        let camera = Camera {
+            eye_pos: glm::vec3(-10.5, 2.73, -5.83),
+            eye_dir: glm::vec3(0.9086872, -0.15932521, 0.3858796),
+            up: glm::vec3(0.0, 1.0, 0.0),
+            projection: CameraProjection::Perspective { vfov: 20.0 },
+            aperture: 0.0,
+            bokeh: BokehShape::Circle,
+            focus_distance: 10.0,
+            shutter: if bouncing { 1.0 } else { 0.0 },
+        };
         let ground_material = Material::Lambertian {
             albedo: Texture::new_from_color(glm::vec3(0.5, 0.5, 0.5)),
         };
 
-        materials.push(ground_material);
-        spheres.push(Sphere::new(glm::vec3(0.0, -1000.0, 0.0), 1000.0, 0));
+        let mut builder = SceneBuilder::new(render_param, frame_data).add_sphere(
+            glm::vec3(0.0, -1000.0, 0.0),
+            1000.0,
+            ground_material,
+        );
 
         for (a, b) in (-11..11).flat_map(|a| (-11..11).map(move |b| (a, b))) {
             let choose_mat = rand::random::<f32>();
@@ -93,76 +403,220 @@ impl Scene {
                         fuzz: rand::random::<f32>() * 0.5,
                     }
                 } else {
-                    Material::Dialectric { ref_idx: 1.5 }
+                    Material::Dialectric {
+                        ref_idx: 1.5,
+                        dispersion: 0.0,
+                    }
                 };
 
-                materials.push(sphere_material);
-                spheres.push(Sphere::new(center, 0.2, (materials.len() - 1) as u32));
+                builder = match (bouncing, &sphere_material) {
+                    (true, Material::Lambertian { .. }) => {
+                        let velocity = glm::vec3(0.0, 0.5 * rand::random::<f32>(), 0.0);
+                        builder.add_moving_sphere(center, 0.2, velocity, sphere_material)
+                    }
+                    _ => builder.add_sphere(center, 0.2, sphere_material),
+                };
             }
         }
 
-        materials.push(Material::Dialectric { ref_idx: 1.5 });
-        spheres.push(Sphere::new(
-            glm::vec3(0.0, 1.0, 0.0),
-            1.0,
-            (materials.len() - 1) as u32,
-        ));
-
-        materials.push(Material::DiffuseLight {
-            emit: Texture::new_from_color(glm::vec3(10.0, 10.0, 10.0)),
-        });
-        spheres.push(Sphere::new(
-            glm::vec3(-4.0, 1.0, 0.0),
-            1.0,
-            (materials.len() - 1) as u32,
-        ));
-        lights.push(Light::new(spheres.len() as u32 - 1, ObjectType::Sphere));
-
-        materials.push(Material::Metal {
-            albedo: Texture::new_from_color(glm::vec3(0.7, 0.6, 0.5)),
-            fuzz: 0.0,
-        });
-        spheres.push(Sphere::new(
-            glm::vec3(4.0, 1.0, 0.0),
-            1.0,
-            (materials.len() - 1) as u32,
-        ));
+        builder
+            .add_sphere(
+                glm::vec3(0.0, 1.0, 0.0),
+                1.0,
+                Material::Dialectric {
+                    ref_idx: 1.5,
+                    dispersion: 0.0,
+                },
+            )
+            .add_sphere(
+                glm::vec3(-4.0, 1.0, 0.0),
+                1.0,
+                Material::DiffuseLight {
+                    emit: Texture::new_from_color(glm::vec3(1.0, 1.0, 1.0)),
+                    strength: 10.0,
+                    two_sided: false,
+                    enabled: true,
+                },
+            )
+            .add_light()
+            .add_sphere(
+                glm::vec3(4.0, 1.0, 0.0),
+                1.0,
+                Material::Metal {
+                    albedo: Texture::new_from_color(glm::vec3(0.7, 0.6, 0.5)),
+                    fuzz: 0.0,
+                },
+            )
+            .camera(camera)
+            .camera_preset("Side", camera)
+            .camera_preset(
+                "Cover",
+                Camera {
+                    eye_pos: glm::vec3(13.0, 2.0, 3.0),
+                    eye_dir: glm::normalize(&glm::vec3(-13.0, -2.0, -3.0)),
+                    up: glm::vec3(0.0, 1.0, 0.0),
+                    projection: CameraProjection::Perspective { vfov: 20.0 },
+                    aperture: 0.1,
+                    bokeh: BokehShape::Circle,
+                    focus_distance: 10.0,
+                    shutter: camera.shutter,
+                },
+            )
+            // Focused on the metal sphere, the highlights behind it blur into hexagons.
+            .camera_preset(
+                "Bokeh",
+                Camera {
+                    eye_pos: glm::vec3(13.0, 2.0, 3.0),
+                    eye_dir: glm::normalize(&glm::vec3(-9.0, -1.0, -3.0)),
+                    up: glm::vec3(0.0, 1.0, 0.0),
+                    projection: CameraProjection::Perspective { vfov: 20.0 },
+                    aperture: 1.0,
+                    bokeh: BokehShape::Hexagon,
+                    focus_distance: 9.5,
+                    shutter: camera.shutter,
+                },
+            )
+            .build()
+    }
 
+    /// A flat textured quad whose bumps only come from its normal map, lit from the side so
+    /// they cast visible shading. A clearcoated diffuse sphere sits on it.
+    pub fn normal_mapping_scene(render_param: RenderParam, frame_data: FrameData) -> Self {
+        let tau = std::f32::consts::TAU;
+        let checker = Texture::new_from_fn(2, 2, |u, v| {
+            match ((u * 2.0) as u32 + (v * 2.0) as u32) % 2 {
+                0 => glm::vec3(0.8, 0.8, 0.8),
+                _ => glm::vec3(0.3, 0.35, 0.5),
+            }
+        })
+        .with_uv_transform(glm::vec2(4.0, 4.0), glm::Vec2::zeros());
+        let bumps = Texture::normal_map_from_height(256, 256, 0.02, |u, v| {
+            (tau * 8.0 * u).sin() * (tau * 8.0 * v).sin()
+        })
+        .with_filter(TextureFilter::Bilinear);
         let camera = Camera {
-            eye_pos: glm::vec3(-10.5, 2.73, -5.83),
-            eye_dir: glm::vec3(0.9086872, -0.15932521, 0.3858796),
+            eye_pos: glm::vec3(0.0, 3.0, 4.0),
+            eye_dir: glm::normalize(&glm::vec3(0.0, -3.0, -4.0)),
             up: glm::vec3(0.0, 1.0, 0.0),
-            vfov: 20.0,
+            projection: CameraProjection::Perspective { vfov: 40.0 },
             aperture: 0.0,
-            focus_distance: 10.0,
+            bokeh: BokehShape::Circle,
+            focus_distance: 5.0,
+            shutter: 0.0,
         };
 
-        let objects: Vec<Object> = spheres
-            .iter()
-            .enumerate()
-            .map(|(i, _)| Object::new(i as u32, object::ObjectType::Sphere, None, Some(i as u32)))
-            .collect();
+        let mut ground = Mesh::quad();
+        rotate(&mut ground, -90., glm::vec3(1.0, 0.0, 0.0));
+        scale(&mut ground, glm::vec3(2.0, 1.0, 2.0));
 
-        object_list.objects = objects;
+        SceneBuilder::new(render_param, frame_data)
+            .add_mesh(
+                ground,
+                Material::NormalMapped {
+                    base: Box::new(Material::Lambertian { albedo: checker }),
+                    normal_map: bumps,
+                },
+            )
+            .add_sphere(
+                glm::vec3(0.8, 0.5, 0.4),
+                0.5,
+                Material::Clearcoat {
+                    base: Box::new(Material::Lambertian {
+                        albedo: Texture::new_from_color(glm::vec3(0.6, 0.05, 0.05)),
+                    }),
+                    strength: 1.0,
+                    roughness: 0.02,
+                },
+            )
+            .add_sphere(
+                glm::vec3(-4.0, 0.6, 0.0),
+                0.4,
+                Material::DiffuseLight {
+                    emit: Texture::new_from_color(glm::vec3(1.0, 0.93, 0.83)),
+                    strength: 30.0,
+                    two_sided: false,
+                    enabled: true,
+                },
+            )
+            .add_light()
+            .background(Background::SolidColor(glm::vec3(0.02, 0.02, 0.03)))
+            .camera(camera)
+            .camera_preset("Above", camera)
+            .camera_preset(
+                "Grazing",
+                Camera {
+                    eye_pos: glm::vec3(0.0, 0.4, 3.0),
+                    eye_dir: glm::normalize(&glm::vec3(0.0, -0.4, -3.0)),
+                    up: glm::vec3(0.0, 1.0, 0.0),
+                    projection: CameraProjection::Perspective { vfov: 40.0 },
+                    aperture: 0.0,
+                    bokeh: BokehShape::Circle,
+                    focus_distance: 3.0,
+                    shutter: 0.0,
+                },
+            )
+            .build()
+    }
 
-        Self {
-            camera,
-            // meshes: vec![Mesh::empty()],
-            materials,
-            spheres,
-            lights,
-            render_param,
-            frame_data,
-            camera_controller: CameraController::new(4.0, 0.4),
-            object_list,
+    /// Rows of bricks with sunken mortar, relief both from the normal map and parallax.
+    fn brick_material() -> Material {
+        let relief = 0.03;
+        // 1 on the bricks, beveled down to 0 in the mortar.
+        let height = |u: f32, v: f32| {
+            let row = (v * 8.0).floor();
+            let x = (u * 4.0 + 0.5 * row).fract();
+            let y = (v * 8.0).fract();
+            let edge = (x.min(1.0 - x) / 4.0).min(y.min(1.0 - y) / 8.0);
+            ((edge - 0.004) / 0.008).clamp(0.0, 1.0)
+        };
+        let albedo = Texture::new_from_fn(256, 256, |u, v| match height(u, v) > 0.0 {
+            true => glm::vec3(0.55, 0.2, 0.12),
+            false => glm::vec3(0.6, 0.6, 0.55),
+        })
+        .with_filter(TextureFilter::Bilinear);
+        let normal_map = Texture::normal_map_from_height(256, 256, relief, height)
+            .with_filter(TextureFilter::Bilinear);
+        let height_map = Texture::new_from_fn(256, 256, |u, v| glm::Vec3::repeat(height(u, v)))
+            .with_filter(TextureFilter::Bilinear);
+        Material::Parallax {
+            base: Box::new(Material::NormalMapped {
+                base: Box::new(Material::Lambertian { albedo }),
+                normal_map,
+            }),
+            height_map,
+            height_scale: relief,
+        }
+    }
+
+    /// Green leaf with the corners of its quad cut out and slits between the veins.
+    fn leaf_material() -> Material {
+        let pi = std::f32::consts::PI;
+        let albedo = Texture::new_from_fn(64, 64, |u, _| match (u - 0.5).abs() < 0.02 {
+            true => glm::vec3(0.5, 0.6, 0.2),
+            false => glm::vec3(0.15, 0.4, 0.08),
+        });
+        let opacity = Texture::new_from_fn(128, 128, |u, v| {
+            let side = (u - 0.5).abs();
+            let inside = side < 0.45 * (pi * v).sin();
+            // Slanted veins every 0.125 in v, the slits run between them.
+            let slit = side > 0.1 && ((v - side) * 8.0).fract() > 0.8;
+            glm::Vec3::repeat(match inside && !slit {
+                true => 1.0,
+                false => 0.0,
+            })
+        });
+        Material::Cutout {
+            base: Box::new(Material::Lambertian { albedo }),
+            opacity,
+            alpha_cutoff: 0.5,
         }
     }
-    pub fn cornell_scene_without_suzanne(render_param: RenderParam, frame_data: FrameData) -> Self {
-        let mut materials = Vec::new();
-        let mut object_list = ObjectList::new();
-        let mut spheres = Vec::new();
-        let mut lights = Vec::new();
 
+    pub fn cornell_scene(
+        opts: CornellOptions,
+        render_param: RenderParam,
+        frame_data: FrameData,
+    ) -> Self {
         let red = Material::Lambertian {
             albedo: Texture::new_from_color(glm::vec3(0.65, 0.05, 0.05)),
         };
@@ -173,7 +627,10 @@ impl Scene {
             albedo: Texture::new_from_color(glm::vec3(0.12, 0.45, 0.15)),
         };
         let light = Material::DiffuseLight {
-            emit: Texture::new_from_color(glm::vec3(15.0, 15.0, 15.0)),
+            emit: Texture::new_from_color(glm::vec3(1.0, 1.0, 1.0)),
+            strength: 15.0,
+            two_sided: false,
+            enabled: true,
         };
 
         let metal = Material::Metal {
@@ -181,20 +638,15 @@ impl Scene {
             fuzz: 0.0,
         };
 
-        materials.push(white.clone());
-        materials.push(green);
-        materials.push(red);
-        materials.push(white.clone());
-        materials.push(white.clone());
-        materials.push(light);
-        materials.push(white.clone());
-        materials.push(metal.clone());
-        // materials.push(gold_metal);
-        materials.push(Material::Dialectric { ref_idx: 1.5 });
+        let gold_metal = Material::Metal {
+            albedo: Texture::new_from_color(glm::vec3(0.8, 0.6, 0.2)),
+            fuzz: 0.4,
+        };
 
+        // Quads face +z, every wall is turned so that its winding faces into the box, which
+        // backface culling relies on.
         let mut back_wall = Mesh::quad();
         translate(&mut back_wall, glm::vec3(0.0, 0.0, -1.0));
-        object_list.add_mesh(Some(back_wall.len()), back_wall);
 
         let mut left_wall = Mesh::quad();
         rotate(&mut left_wall, 90., glm::vec3(0.0, 1.0, 0.0));
@@ -206,10 +658,9 @@ impl Scene {
                 glm::vec4(0.5, 0.0, 0.0, 1.0),
             ]
         }
-        object_list.add_mesh(Some(left_wall.len()), left_wall);
 
         let mut right_wall: Vec<Mesh> = Mesh::quad();
-        rotate(&mut right_wall, 90., glm::vec3(0.0, 1.0, 0.0));
+        rotate(&mut right_wall, -90., glm::vec3(0.0, 1.0, 0.0));
         translate(&mut right_wall, glm::vec3(1.0, 0.0, 0.0));
         for v in right_wall.iter_mut() {
             v.normals = [
@@ -218,7 +669,6 @@ impl Scene {
                 glm::vec4(-0.5, 0.0, 0.0, 1.0),
             ]
         }
-        object_list.add_mesh(Some(right_wall.len()), right_wall);
 
         let mut ceiling = Mesh::quad();
         rotate(&mut ceiling, 90., glm::vec3(1.0, 0.0, 0.0));
@@ -230,10 +680,9 @@ impl Scene {
                 glm::vec4(0.0, -0.5, 0.0, 1.0),
             ]
         }
-        object_list.add_mesh(Some(ceiling.len()), ceiling);
 
         let mut floor = Mesh::quad();
-        rotate(&mut floor, 90., glm::vec3(1.0, 0.0, 0.0));
+        rotate(&mut floor, -90., glm::vec3(1.0, 0.0, 0.0));
         translate(&mut floor, glm::vec3(0.0, -1.0, 0.0));
         for v in floor.iter_mut() {
             v.normals = [
@@ -242,7 +691,6 @@ impl Scene {
                 glm::vec4(0.0, 0.5, 0.0, 1.0),
             ]
         }
-        object_list.add_mesh(Some(floor.len()), floor);
 
         let mut ceiling_light = Mesh::quad();
         rotate(&mut ceiling_light, 90., glm::vec3(1.0, 0.0, 0.0));
@@ -255,225 +703,360 @@ impl Scene {
                 glm::vec4(0.0, -0.5, 0.0, 1.0),
             ]
         }
-        object_list.add_mesh(Some(ceiling_light.len()), ceiling_light);
-        lights.push(Light::new(5, ObjectType::Mesh));
 
         let mut box1 = Mesh::cube();
         scale(&mut box1, glm::vec3(0.3, 0.3, 0.3));
         rotate(&mut box1, 70., glm::vec3(0.0, 1.0, 0.0));
         translate(&mut box1, glm::vec3(0.3, -0.699, 0.3));
-        object_list.add_mesh(Some(box1.len()), box1);
 
         let mut rectangle_box = Mesh::cube();
         scale(&mut rectangle_box, glm::vec3(0.3, 0.6, 0.3));
         rotate(&mut rectangle_box, 15., glm::vec3(0.0, 1.0, 0.0));
         translate(&mut rectangle_box, glm::vec3(-0.3, -0.399, -0.35));
-        object_list.add_mesh(Some(rectangle_box.len()), rectangle_box);
 
-        spheres.push(Sphere::new(glm::vec3(-0.5, -0.8, 0.3), 0.2, 8));
-        object_list.add_sphere(None);
+        // One object per wall in the order of the original scene, the options only swap
+        // materials so object ids, offsets and the light id stay put.
+        let back_wall_material = match opts.brick_wall {
+            true => Self::brick_material(),
+            false => white.clone(),
+        };
+        let floor_material = match opts.marble_floor {
+            true => Material::Lambertian {
+                albedo: Texture::new_noise(6.0, 7),
+            },
+            false => white.clone(),
+        };
+        let mut builder = SceneBuilder::new(render_param, frame_data)
+            .add_mesh(back_wall, back_wall_material)
+            .add_mesh(left_wall, green)
+            .add_mesh(right_wall, red)
+            .add_mesh(ceiling, white.clone())
+            .add_mesh(floor, floor_material)
+            .add_mesh(ceiling_light, light)
+            .add_light()
+            .add_mesh(box1, white.clone())
+            .add_mesh(rectangle_box, metal);
+        if opts.plastic_sphere {
+            let plastic = Material::Plastic {
+                albedo: Texture::new_from_color(glm::vec3(0.1, 0.2, 0.6)),
+                ref_idx: 1.5,
+            };
+            builder = builder.add_sphere(glm::vec3(-0.05, -0.85, 0.8), 0.15, plastic);
+        }
+        if opts.pbr_sphere {
+            let gold = Material::CookTorrance {
+                albedo: Texture::new_from_color(glm::vec3(1.0, 0.78, 0.34)),
+                metallic: 1.0,
+                roughness: 0.35,
+            };
+            builder = builder.add_sphere(glm::vec3(0.5, -0.85, 0.8), 0.15, gold);
+        }
+        if opts.cutout_leaf {
+            let mut leaf = Mesh::quad();
+            scale(&mut leaf, glm::vec3(0.25, 0.35, 1.0));
+            rotate(&mut leaf, 25., glm::vec3(0.0, 1.0, 0.0));
+            translate(&mut leaf, glm::vec3(-0.65, -0.6, 0.4));
+            builder = builder.add_mesh(leaf, Self::leaf_material());
+        }
 
-        let camera = Camera {
+        if opts.load_suzanne {
+            let mesh_bytes = include_bytes!("../../assets/mesh/suzanne.obj");
+            let options = tobj::LoadOptions {
+                triangulate: true,
+                ..Default::default()
+            };
+
+            let s = tobj::load_obj_buf(&mut std::io::Cursor::new(mesh_bytes), &options, |_| {
+                Ok(Default::default())
+            })
+            .unwrap()
+            .0[0]
+                .clone();
+
+            let mut sdsd = Mesh::from_tobj(s);
+            transform(
+                &mut sdsd,
+                glm::translation(&glm::vec3(0.3, -0.30, 0.3))
+                    * glm::rotation((-30.0_f32).to_radians(), &glm::vec3(0.0, 1.0, 0.0))
+                    * glm::rotation((-35.0_f32).to_radians(), &glm::vec3(1.0, 0.0, 0.0))
+                    * glm::scaling(&glm::vec3(0.2, 0.2, 0.2)),
+            );
+
+            let suzanne_material = match opts.extra_material {
+                true => gold_metal,
+                false => white,
+            };
+            builder = builder.add_mesh(sdsd, suzanne_material);
+        }
+
+        let front = Camera {
             eye_pos: glm::vec3(0.0, 0.0, 5.),
             eye_dir: glm::vec3(0.0, 0.0, -1.0),
             up: glm::vec3(0.0, 1.0, 0.0),
-            vfov: 30.0,
+            projection: CameraProjection::Perspective { vfov: 30.0 },
             aperture: 0.0,
+            bokeh: BokehShape::Circle,
             focus_distance: 10.0,
+            shutter: 0.0,
         };
 
-        Self {
-            camera,
-            materials,
-            spheres,
-            lights,
-            render_param,
-            frame_data,
-            camera_controller: CameraController::new(4.0, 0.4),
-            object_list,
-        }
+        builder
+            .add_sphere(
+                glm::vec3(-0.5, -0.8, 0.3),
+                0.2,
+                Material::Dialectric {
+                    ref_idx: 1.5,
+                    dispersion: 0.0,
+                },
+            )
+            .camera(front)
+            .camera_preset("Front", front)
+            // Just under the ceiling, looking down with the back wall at the top.
+            .camera_preset(
+                "Top",
+                Camera {
+                    eye_pos: glm::vec3(0.0, 0.9, 0.0),
+                    eye_dir: glm::vec3(0.0, -1.0, 0.0),
+                    up: glm::vec3(0.0, 0.0, -1.0),
+                    projection: CameraProjection::Perspective { vfov: 60.0 },
+                    aperture: 0.0,
+                    bokeh: BokehShape::Circle,
+                    focus_distance: 10.0,
+                    shutter: 0.0,
+                },
+            )
+            .build()
     }
+}
 
-    pub fn cornell_scene(render_param: RenderParam, frame_data: FrameData) -> Self {
-        let mut materials = Vec::new();
-        let mut object_list = ObjectList::new();
-        let mut spheres = Vec::new();
-        let mut lights = Vec::new();
-
-        let red = Material::Lambertian {
-            albedo: Texture::new_from_color(glm::vec3(0.65, 0.05, 0.05)),
-        };
-        let white = Material::Lambertian {
-            albedo: Texture::new_from_color(glm::vec3(0.73, 0.73, 0.73)),
-        };
-        let green = Material::Lambertian {
-            albedo: Texture::new_from_color(glm::vec3(0.12, 0.45, 0.15)),
-        };
-        let light = Material::DiffuseLight {
-            emit: Texture::new_from_color(glm::vec3(15.0, 15.0, 15.0)),
-        };
-
-        let metal = Material::Metal {
-            albedo: Texture::new_from_color(glm::vec3(0.8, 0.85, 0.88)),
-            fuzz: 0.0,
-        };
-
-        let gold_metal = Material::Metal {
-            albedo: Texture::new_from_color(glm::vec3(0.8, 0.6, 0.2)),
-            fuzz: 0.4,
-        };
-
-        materials.push(white.clone());
-        materials.push(green);
-        materials.push(red);
-        materials.push(white.clone());
-        materials.push(white.clone());
-        materials.push(light);
-        materials.push(white.clone());
-        // materials.push(white.clone());
-        materials.push(metal.clone());
-        materials.push(gold_metal);
-        materials.push(Material::Dialectric { ref_idx: 1.5 });
+/// Saved with the [`Settings`], fields missing from the file keep their default and the
+/// sample counters are not written.
+#[repr(C)]
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    bytemuck::Pod,
+    bytemuck::Zeroable,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(default)]
+pub struct RenderParam {
+    pub samples_max_per_pixel: u32,
+    #[serde(skip)]
+    pub samples_per_pixel: u32,
+    #[serde(skip)]
+    pub total_samples: u32,
+    #[serde(skip)]
+    pub clear_samples: u32,
+    pub max_depth: u32,
+    /// Bounces every path makes before Russian roulette may end it.
+    pub min_depth: u32,
+    /// How far rays leaving a surface start off it, so they don't hit it again (shadow acne).
+    /// Scales with the scene, too large and light leaks through thin walls: the default
+    /// suits scenes a few units across like the Cornell box, a model a few millimeters
+    /// across wants around `1e-6`.
+    pub ray_epsilon: f32,
+    /// A [`SamplerKind`].
+    pub sampler: u32,
+    /// A [`DebugView`].
+    pub debug_view: u32,
+    /// What `samples_per_pixel` goes back to when sampling resumes, `samples_per_pixel` drops
+    /// to 0 once `samples_max_per_pixel` is reached.
+    pub requested_samples_per_pixel: u32,
+    /// Samples one pass of the shader traces per pixel, a frame adding more than that is split
+    /// in several passes submitted one after the other. 0 traces the whole frame in one pass.
+    pub samples_per_pass: u32,
+    /// Rendering pauses once the image changes by less than this between checks, relative
+    /// to its value. 0 disables it.
+    pub convergence_epsilon: f32,
+    /// Side in pixels of the tiles a frame is rendered in, each one in its own submission.
+    /// 0 renders the whole frame at once.
+    pub tile_size: u32,
+    /// An [`AccumulationFormat`].
+    pub accumulation_format: u32,
+    /// Frames whose luminance lands further than this many standard deviations from the
+    /// average of a pixel are left out of it, trading a little bias for fewer fireflies.
+    /// Only with [`AccumulationFormat::F32`], 0 disables it.
+    pub firefly_sigma: f32,
+    /// 1 to reproject the accumulated image into the new view when the camera moves instead
+    /// of starting over. Only with [`AccumulationFormat::F32`].
+    pub taa: u32,
+    /// Distance within which a hit occludes, for [`DebugView::AmbientOcclusion`].
+    pub ao_radius: f32,
+    /// Occlusion rays per sample, for [`DebugView::AmbientOcclusion`].
+    pub ao_samples: u32,
+    /// 1 ignores triangles hit from behind their winding, except dielectric ones which are
+    /// crossed from both sides.
+    pub cull_backfaces: u32,
+    /// A [`ShadingMode`].
+    pub shading: u32,
+    /// How much of the glow around pixels brighter than 1 is added to the image, 0 skips
+    /// the bloom passes.
+    pub bloom_intensity: f32,
+    /// How much the corners are darkened, 0 disables it.
+    pub vignette: f32,
+    /// Contrast around middle gray, 1 leaves the image as is.
+    pub contrast: f32,
+    /// 0 is grayscale, 1 leaves the image as is.
+    pub saturation: f32,
+    /// Color temperature in Kelvin of the white the image is tinted with, 6500 leaves it as is.
+    pub white_balance_k: f32,
+    /// Fraction of the resolution traced at while the camera moves, 1 disables it.
+    pub motion_scale: f32,
+}
 
-        let mut back_wall = Mesh::quad();
-        translate(&mut back_wall, glm::vec3(0.0, 0.0, -1.0));
-        object_list.add_mesh(Some(back_wall.len()), back_wall);
+/// How the samples of a pixel are placed, see `sample_pixel` in the shader.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SamplerKind {
+    Random = 0,
+    /// One sample per cell of a `sqrt(samples_per_pixel)` sided grid, only has an effect
+    /// from 4 samples per pixel per frame.
+    Stratified = 1,
+}
 
-        let mut left_wall = Mesh::quad();
-        rotate(&mut left_wall, 90., glm::vec3(0.0, 1.0, 0.0));
-        translate(&mut left_wall, glm::vec3(-1.0, 0.0, 0.0));
-        for v in left_wall.iter_mut() {
-            v.normals = [
-                glm::vec4(0.5, 0.0, 0.0, 1.0),
-                glm::vec4(0.5, 0.0, 0.0, 1.0),
-                glm::vec4(0.5, 0.0, 0.0, 1.0),
-            ]
-        }
-        object_list.add_mesh(Some(left_wall.len()), left_wall);
+/// Which normal triangles are shaded with, see `hit_triangle` in the shader.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ShadingMode {
+    /// The vertex normals, interpolated across the triangle.
+    Smooth = 0,
+    /// The geometric normal of the triangle, facing the side its vertices are
+    /// counter-clockwise from.
+    Flat = 1,
+}
 
-        let mut right_wall: Vec<Mesh> = Mesh::quad();
-        rotate(&mut right_wall, 90., glm::vec3(0.0, 1.0, 0.0));
-        translate(&mut right_wall, glm::vec3(1.0, 0.0, 0.0));
-        for v in right_wall.iter_mut() {
-            v.normals = [
-                glm::vec4(-0.5, 0.0, 0.0, 1.0),
-                glm::vec4(-0.5, 0.0, 0.0, 1.0),
-                glm::vec4(-0.5, 0.0, 0.0, 1.0),
-            ]
-        }
-        object_list.add_mesh(Some(right_wall.len()), right_wall);
+/// How `image_buffer` holds the accumulated image, see `accumulate` in the shader.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AccumulationFormat {
+    /// Running average of every pixel as three `f32`, followed by the average of its squared
+    /// luminance for [`RenderParam::firefly_sigma`], the view depth of its first hit and its
+    /// sample count for [`RenderParam::taa`], 24 bytes per pixel.
+    F32 = 0,
+    /// Running average of every pixel as four packed `f16`, 8 bytes per pixel. With 11 bits
+    /// of mantissa a new sample barely moves the average past a couple thousand samples, so
+    /// noise stops going down there, and radiance above 65504 is clamped.
+    F16 = 1,
+}
 
-        let mut ceiling = Mesh::quad();
-        rotate(&mut ceiling, 90., glm::vec3(1.0, 0.0, 0.0));
-        translate(&mut ceiling, glm::vec3(0.0, 1.0, 0.0));
-        for v in ceiling.iter_mut() {
-            v.normals = [
-                glm::vec4(0.0, -0.5, 0.0, 1.0),
-                glm::vec4(0.0, -0.5, 0.0, 1.0),
-                glm::vec4(0.0, -0.5, 0.0, 1.0),
-            ]
+impl From<u32> for AccumulationFormat {
+    fn from(item: u32) -> Self {
+        match item {
+            1 => AccumulationFormat::F16,
+            _ => AccumulationFormat::F32,
         }
-        object_list.add_mesh(Some(ceiling.len()), ceiling);
+    }
+}
 
-        let mut floor = Mesh::quad();
-        rotate(&mut floor, 90., glm::vec3(1.0, 0.0, 0.0));
-        translate(&mut floor, glm::vec3(0.0, -1.0, 0.0));
-        for v in floor.iter_mut() {
-            v.normals = [
-                glm::vec4(0.0, 0.5, 0.0, 1.0),
-                glm::vec4(0.0, 0.5, 0.0, 1.0),
-                glm::vec4(0.0, 0.5, 0.0, 1.0),
-            ]
+impl AccumulationFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            AccumulationFormat::F32 => 24,
+            AccumulationFormat::F16 => 8,
         }
-        object_list.add_mesh(Some(floor.len()), floor);
+    }
 
-        let mut ceiling_light = Mesh::quad();
-        rotate(&mut ceiling_light, 90., glm::vec3(1.0, 0.0, 0.0));
-        translate(&mut ceiling_light, glm::vec3(0.0, 0.99, 0.));
-        scale(&mut ceiling_light, glm::vec3(0.20, 1.0, 0.2));
-        for v in ceiling_light.iter_mut() {
-            v.normals = [
-                glm::vec4(0.0, -0.5, 0.0, 1.0),
-                glm::vec4(0.0, -0.5, 0.0, 1.0),
-                glm::vec4(0.0, -0.5, 0.0, 1.0),
-            ]
+    /// Average radiance of every pixel from the raw content of `image_buffer`.
+    pub fn decode(self, words: &[u32]) -> Vec<[f32; 3]> {
+        match self {
+            AccumulationFormat::F32 => words
+                .chunks_exact(6)
+                .map(|mean| [0, 1, 2].map(|c| f32::from_bits(mean[c])))
+                .collect(),
+            AccumulationFormat::F16 => words
+                .chunks_exact(2)
+                .map(|mean| {
+                    [
+                        f16_to_f32(mean[0] as u16),
+                        f16_to_f32((mean[0] >> 16) as u16),
+                        f16_to_f32(mean[1] as u16),
+                    ]
+                })
+                .collect(),
         }
-        object_list.add_mesh(Some(ceiling_light.len()), ceiling_light);
-        lights.push(Light::new(5, ObjectType::Mesh));
-
-        let mut box1 = Mesh::cube();
-        scale(&mut box1, glm::vec3(0.3, 0.3, 0.3));
-        rotate(&mut box1, 70., glm::vec3(0.0, 1.0, 0.0));
-        translate(&mut box1, glm::vec3(0.3, -0.699, 0.3));
-        object_list.add_mesh(Some(box1.len()), box1);
+    }
+}
 
-        let mut rectangle_box = Mesh::cube();
-        scale(&mut rectangle_box, glm::vec3(0.3, 0.6, 0.3));
-        rotate(&mut rectangle_box, 15., glm::vec3(0.0, 1.0, 0.0));
-        translate(&mut rectangle_box, glm::vec3(-0.3, -0.399, -0.35));
+/// Same as WGSL's `unpack2x16float` for one half.
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((half >> 10) & 0x1f) as i32;
+    let mantissa = (half & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
 
-        object_list.add_mesh(Some(rectangle_box.len()), rectangle_box);
+/// What the shader outputs, every view but `Beauty` and `AmbientOcclusion` traces one ray per
+/// pixel without accumulating.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DebugView {
+    Beauty = 0,
+    Normals = 1,
+    Depth = 2,
+    Albedo = 3,
+    /// BVH nodes visited by the primary ray, blue for few and red for many.
+    BvhHeatmap = 4,
+    /// Unoccluded fraction of the hemisphere around the first hit, light independent.
+    AmbientOcclusion = 5,
+}
 
-        let mesh_bytes = include_bytes!("../../assets/mesh/suzanne.obj");
-        let options = tobj::LoadOptions {
-            triangulate: true,
-            ..Default::default()
-        };
+impl DebugView {
+    pub const ALL: [DebugView; 6] = [
+        DebugView::Beauty,
+        DebugView::Normals,
+        DebugView::Depth,
+        DebugView::Albedo,
+        DebugView::BvhHeatmap,
+        DebugView::AmbientOcclusion,
+    ];
+}
 
-        let s = tobj::load_obj_buf(&mut std::io::Cursor::new(mesh_bytes), &options, |_| {
-            Ok(Default::default())
-        })
-        .unwrap()
-        .0[0]
-            .clone();
-
-        let mut sdsd = Mesh::from_tobj(s);
-        scale(&mut sdsd, glm::vec3(0.2, 0.2, 0.2));
-        rotate(&mut sdsd, -35.0, glm::vec3(1.0, 0.0, 0.0));
-        rotate(&mut sdsd, -30.0, glm::vec3(0.0, 1.0, 0.0));
-        translate(&mut sdsd, glm::vec3(0.3, -0.30, 0.3));
-        object_list.add_mesh(Some(sdsd.len()), sdsd);
-
-        spheres.push(Sphere::new(glm::vec3(-0.5, -0.8, 0.3), 0.2, 9));
-        object_list.add_sphere(None);
-        let camera = Camera {
-            eye_pos: glm::vec3(0.0, 0.0, 5.),
-            eye_dir: glm::vec3(0.0, 0.0, -1.0),
-            up: glm::vec3(0.0, 1.0, 0.0),
-            vfov: 30.0,
-            aperture: 0.0,
-            focus_distance: 10.0,
-        };
+impl From<u32> for DebugView {
+    fn from(item: u32) -> Self {
+        match item {
+            1 => DebugView::Normals,
+            2 => DebugView::Depth,
+            3 => DebugView::Albedo,
+            4 => DebugView::BvhHeatmap,
+            5 => DebugView::AmbientOcclusion,
+            _ => DebugView::Beauty,
+        }
+    }
+}
 
-        Self {
-            camera,
-            materials,
-            spheres,
-            lights,
-            render_param,
-            frame_data,
-            camera_controller: CameraController::new(4.0, 0.4),
-            object_list,
+impl From<u32> for ShadingMode {
+    fn from(item: u32) -> Self {
+        match item {
+            1 => ShadingMode::Flat,
+            _ => ShadingMode::Smooth,
         }
     }
 }
 
-#[repr(C)]
-#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable, PartialEq)]
-pub struct RenderParam {
-    pub samples_max_per_pixel: u32,
-    pub samples_per_pixel: u32,
-    pub total_samples: u32,
-    pub clear_samples: u32,
-    pub max_depth: u32,
+impl From<u32> for SamplerKind {
+    fn from(item: u32) -> Self {
+        match item {
+            1 => SamplerKind::Stratified,
+            _ => SamplerKind::Random,
+        }
+    }
 }
 
 impl RenderParam {
+    /// Advances the sample counters by one frame. Accumulation stops at
+    /// `samples_max_per_pixel` and picks up again if it is raised.
     pub fn update(&mut self) {
         if self.total_samples == 0 {
+            self.samples_per_pixel = self.requested_samples_per_pixel;
             self.total_samples += self.samples_per_pixel;
             self.clear_samples = 1;
         } else if self.total_samples <= self.samples_max_per_pixel {
+            self.samples_per_pixel = self.requested_samples_per_pixel;
             self.total_samples += self.samples_per_pixel;
             self.clear_samples = 0;
         } else {
@@ -481,6 +1064,145 @@ impl RenderParam {
             self.clear_samples = 0;
         }
     }
+
+    /// Splits the frame `update` advanced to into passes of at most `samples_per_pass` samples,
+    /// each accumulating on top of the previous one.
+    pub fn passes(&self) -> Vec<RenderParam> {
+        let per_pass = match self.samples_per_pass {
+            0 => self.samples_per_pixel,
+            n => n.min(self.samples_per_pixel),
+        };
+        if per_pass == 0 {
+            return vec![*self];
+        }
+        let mut total = self.total_samples - self.samples_per_pixel;
+        (0..self.samples_per_pixel.div_ceil(per_pass))
+            .map(|i| {
+                let samples = per_pass.min(self.samples_per_pixel - i * per_pass);
+                total += samples;
+                RenderParam {
+                    samples_per_pixel: samples,
+                    total_samples: total,
+                    clear_samples: match i {
+                        0 => self.clear_samples,
+                        _ => 0,
+                    },
+                    ..*self
+                }
+            })
+            .collect()
+    }
+
+    /// Default params with the depth and samples recommended for the built-in scene called
+    /// `name`, see [`AVAILABLE_SCENES`]. The plain defaults for any other name.
+    pub fn default_for_scene(name: &str) -> Self {
+        let mut render_param = Self::default();
+        if let Some(scene) = AVAILABLE_SCENES.iter().find(|scene| scene.name == name) {
+            render_param.apply_scene_defaults(scene);
+        }
+        render_param
+    }
+
+    /// Sets the params `scene` recommends, leaving the others as they are.
+    pub fn apply_scene_defaults(&mut self, scene: &SceneDescriptor) {
+        self.max_depth = scene.max_depth;
+        self.samples_max_per_pixel = scene.samples_max_per_pixel;
+    }
+
+    /// Discards the accumulated samples, the next frame starts over.
+    pub fn reset_accumulation(&mut self) {
+        self.total_samples = 0;
+        self.samples_per_pixel = self.requested_samples_per_pixel;
+    }
+}
+
+impl Default for RenderParam {
+    fn default() -> Self {
+        Self {
+            samples_per_pixel: 1,
+            max_depth: 15,
+            min_depth: 3,
+            ray_epsilon: 1e-3,
+            samples_max_per_pixel: 200,
+            total_samples: 0,
+            clear_samples: 0,
+            sampler: SamplerKind::Stratified as u32,
+            debug_view: DebugView::Beauty as u32,
+            requested_samples_per_pixel: 1,
+            samples_per_pass: 0,
+            convergence_epsilon: 0.0,
+            tile_size: 0,
+            accumulation_format: AccumulationFormat::F32 as u32,
+            firefly_sigma: 0.0,
+            taa: 0,
+            ao_radius: 1.0,
+            ao_samples: 4,
+            cull_backfaces: 0,
+            shading: ShadingMode::Smooth as u32,
+            bloom_intensity: 0.0,
+            vignette: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            white_balance_k: 6500.0,
+            motion_scale: 0.5,
+        }
+    }
+}
+
+const BACKGROUND_SOLID_COLOR: u32 = 0;
+const BACKGROUND_GRADIENT: u32 = 1;
+const BACKGROUND_CUBEMAP: u32 = 2;
+
+/// Scene wide shading parameters, bound next to the camera in the shader.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuSceneParam {
+    background_top: glm::Vec3,
+    background_kind: u32,
+    background_bottom: glm::Vec3,
+    _padding: f32,
+    medium_sigma_a: glm::Vec3,
+    _padding2: f32,
+    medium_sigma_s: glm::Vec3,
+    _padding3: f32,
+    ambient: glm::Vec3,
+    /// Emissive objects at the start of the lights buffer, the point lights follow.
+    light_count: u32,
+    /// See [`GpuMaterial::cubemap_faces`].
+    cubemap_faces: [[u32; 4]; 6],
+}
+
+impl GpuSceneParam {
+    pub fn new(scene: &Scene) -> Self {
+        let (background_kind, background_top, background_bottom) = match &scene.background {
+            Background::SolidColor(color) => (BACKGROUND_SOLID_COLOR, *color, *color),
+            Background::Gradient { top, bottom } => (BACKGROUND_GRADIENT, *top, *bottom),
+            Background::Cubemap(_) => {
+                let black = glm::Vec3::zeros();
+                (BACKGROUND_CUBEMAP, black, black)
+            }
+        };
+
+        // A zero medium is no medium.
+        let medium = scene.medium.unwrap_or(Medium {
+            sigma_a: glm::Vec3::zeros(),
+            sigma_s: glm::Vec3::zeros(),
+        });
+
+        Self {
+            background_top,
+            background_kind,
+            background_bottom,
+            _padding: 0.0,
+            medium_sigma_a: medium.sigma_a,
+            _padding2: 0.0,
+            medium_sigma_s: medium.sigma_s,
+            _padding3: 0.0,
+            ambient: scene.ambient,
+            light_count: scene.lights.len() as u32,
+            cubemap_faces: GpuMaterial::cubemap_faces(&scene.background),
+        }
+    }
 }
 
 #[repr(C)]
@@ -496,3 +1218,162 @@ impl PartialEq for FrameData {
         self.width == other.width && self.height == other.height
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cornell_scene_matches_the_original_layout() {
+        let frame_data = FrameData {
+            width: 100,
+            height: 100,
+            index: 0,
+        };
+        let scene = Scene::cornell_scene(
+            CornellOptions::WITHOUT_SUZANNE,
+            RenderParam::default(),
+            frame_data,
+        );
+
+        // Taken from the hand written `cornell_scene_without_suzanne` the builder replaced.
+        let lambertian = |r, g, b| Material::Lambertian {
+            albedo: Texture::new_from_color(glm::vec3(r, g, b)),
+        };
+        let white = lambertian(0.73, 0.73, 0.73);
+        let expected_materials = vec![
+            white.clone(),
+            lambertian(0.12, 0.45, 0.15),
+            lambertian(0.65, 0.05, 0.05),
+            white.clone(),
+            white.clone(),
+            Material::DiffuseLight {
+                emit: Texture::new_from_color(glm::vec3(1.0, 1.0, 1.0)),
+                strength: 15.0,
+                two_sided: false,
+                enabled: true,
+            },
+            white,
+            Material::Metal {
+                albedo: Texture::new_from_color(glm::vec3(0.8, 0.85, 0.88)),
+                fuzz: 0.0,
+            },
+            Material::Dialectric {
+                ref_idx: 1.5,
+                dispersion: 0.0,
+            },
+        ];
+        assert_eq!(scene.materials, expected_materials);
+
+        // Back wall, left wall, right wall, ceiling, floor, light, box, rectangle box.
+        let mesh_ranges = [
+            (0, 2),
+            (2, 4),
+            (4, 6),
+            (6, 8),
+            (8, 10),
+            (10, 12),
+            (12, 24),
+            (24, 36),
+        ];
+        let list = &scene.object_list;
+        assert_eq!(list.objects.len(), 9);
+        assert_eq!(list.meshes.len(), 36);
+        for (id, &(start, end)) in mesh_ranges.iter().enumerate() {
+            let id = id as u32;
+            assert_eq!(list.object_hashmap[&id], (start, end), "object {id}");
+            assert!(list.meshes[start as usize..end as usize]
+                .iter()
+                .all(|m| m.material_idx == id));
+        }
+        assert!(ObjectType::from(list.objects[8].obj_type) == ObjectType::Sphere);
+        assert_eq!(scene.spheres.len(), 1);
+        assert_eq!(scene.spheres[0].material_idx, 8);
+
+        let light_ids: Vec<u32> = scene.lights.iter().map(|l| l.id).collect();
+        assert_eq!(light_ids, vec![5]);
+    }
+
+    #[test]
+    fn cornell_scene_bounds_are_the_unit_box() {
+        let frame_data = FrameData {
+            width: 100,
+            height: 100,
+            index: 0,
+        };
+        let scene = Scene::cornell_scene(
+            CornellOptions::default(),
+            RenderParam::default(),
+            frame_data,
+        );
+        let bounds = scene.bounds();
+
+        // The walls are the `[-1, 1]` quads, everything else is inside.
+        assert!(glm::distance(&bounds.min, &glm::vec3(-1.0, -1.0, -1.0)) < 1e-3);
+        assert!(glm::distance(&bounds.max, &glm::vec3(1.0, 1.0, 1.0)) < 1e-3);
+    }
+
+    #[test]
+    fn render_param_resumes_when_the_cap_is_raised() {
+        let mut render_param = RenderParam {
+            samples_max_per_pixel: 2,
+            requested_samples_per_pixel: 1,
+            ..RenderParam::default()
+        };
+
+        // The cap is checked before adding, one frame goes over it.
+        for total_samples in 1..=3 {
+            render_param.update();
+            assert_eq!(render_param.samples_per_pixel, 1);
+            assert_eq!(render_param.total_samples, total_samples);
+        }
+        for _ in 0..2 {
+            render_param.update();
+            assert_eq!(render_param.samples_per_pixel, 0);
+            assert_eq!(render_param.total_samples, 3);
+        }
+
+        render_param.samples_max_per_pixel = 5;
+        render_param.update();
+        assert_eq!(render_param.samples_per_pixel, 1);
+        assert_eq!(render_param.total_samples, 4);
+        assert_eq!(render_param.clear_samples, 0);
+
+        render_param.reset_accumulation();
+        render_param.update();
+        assert_eq!(render_param.samples_per_pixel, 1);
+        assert_eq!(render_param.total_samples, 1);
+        assert_eq!(render_param.clear_samples, 1);
+    }
+
+    #[test]
+    fn f16_to_f32_decodes_known_halves() {
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0x3800), 0.5);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0x7bff), 65504.0);
+        assert_eq!(f16_to_f32(0x7c00), f32::INFINITY);
+        assert!(f16_to_f32(0x7e00).is_nan());
+    }
+
+    #[test]
+    fn decode_reads_the_mean_of_each_pixel() {
+        // Mean, squared luminance, depth and sample count, two pixels.
+        let f32_words: Vec<u32> = [1.0, 2.0, 3.0, 9.0, 7.0, 5.0, 0.25, 0.5, 0.75, 1.0, 2.0, 6.0]
+            .iter()
+            .map(|v: &f32| v.to_bits())
+            .collect();
+        assert_eq!(
+            AccumulationFormat::F32.decode(&f32_words),
+            vec![[1.0, 2.0, 3.0], [0.25, 0.5, 0.75]]
+        );
+
+        // `pack2x16float` puts the first value in the low half, the fourth half is padding.
+        let f16_words = [0x3800_3c00, 0x0000_4000, 0x3c00_0000, 0x0000_c000];
+        assert_eq!(
+            AccumulationFormat::F16.decode(&f16_words),
+            vec![[1.0, 0.5, 2.0], [0.0, 1.0, -2.0]]
+        );
+    }
+}
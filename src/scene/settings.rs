@@ -0,0 +1,33 @@
+use super::{Camera, RenderParam, Scene};
+
+/// What is kept between sessions, see `Settings::save` and `Settings::load`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    /// The sample counters are not kept.
+    pub render_param: RenderParam,
+    pub camera: Camera,
+    /// Of the camera controller, in world units per second.
+    pub speed: f32,
+    /// Of the camera controller.
+    pub sensitivity: f32,
+}
+
+impl Settings {
+    pub fn from_scene(scene: &Scene) -> Self {
+        Self {
+            render_param: scene.render_param,
+            camera: scene.camera,
+            speed: scene.camera_controller.speed,
+            sensitivity: scene.camera_controller.sensitivity,
+        }
+    }
+
+    /// Replaces the settings of `scene`, its accumulated samples are dropped.
+    pub fn apply(&self, scene: &mut Scene) {
+        scene.render_param = self.render_param;
+        scene.render_param.reset_accumulation();
+        scene.camera = self.camera;
+        scene.camera_controller.speed = self.speed;
+        scene.camera_controller.sensitivity = self.sensitivity;
+    }
+}
@@ -0,0 +1,177 @@
+use std::{fmt, path::PathBuf};
+
+use super::Scene;
+
+/// Why a [`Scene`] could not be loaded or would make the shader read garbage.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SceneError {
+    /// A scene or mesh file could not be read.
+    Io { path: PathBuf, message: String },
+    /// A scene or mesh file is malformed.
+    Parse { path: PathBuf, message: String },
+    /// A sphere or triangle uses a material that does not exist.
+    InvalidMaterialIndex {
+        primitive: &'static str,
+        index: usize,
+        material_idx: u32,
+        material_count: usize,
+    },
+    /// A light refers to an object that does not exist.
+    InvalidLightId {
+        light: usize,
+        id: u32,
+        object_count: usize,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, message } | SceneError::Parse { path, message } => {
+                write!(f, "{}: {message}", path.display())
+            }
+            SceneError::InvalidMaterialIndex {
+                primitive,
+                index,
+                material_idx,
+                material_count,
+            } => write!(
+                f,
+                "{primitive} {index} uses material {material_idx} but there are only {material_count} materials"
+            ),
+            SceneError::InvalidLightId {
+                light,
+                id,
+                object_count,
+            } => write!(
+                f,
+                "light {light} refers to object {id} but there are only {object_count} objects"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl Scene {
+    /// Checks that every material index and light id points to something.
+    pub fn validate(&self) -> Result<(), SceneError> {
+        let material_count = self.materials.len();
+        let check_material =
+            |primitive, index, material_idx: u32| match (material_idx as usize) < material_count {
+                true => Ok(()),
+                false => Err(SceneError::InvalidMaterialIndex {
+                    primitive,
+                    index,
+                    material_idx,
+                    material_count,
+                }),
+            };
+        for (index, sphere) in self.spheres.iter().enumerate() {
+            check_material("sphere", index, sphere.material_idx)?;
+        }
+        for (index, mesh) in self.object_list.meshes.iter().enumerate() {
+            check_material("triangle", index, mesh.material_idx)?;
+        }
+
+        let object_count = self.object_list.objects.len();
+        for (light, l) in self.lights.iter().enumerate() {
+            if l.id as usize >= object_count {
+                return Err(SceneError::InvalidLightId {
+                    light,
+                    id: l.id,
+                    object_count,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes a scene failing [`Scene::validate`] safe to upload: out of range materials become
+    /// the last one and lights of missing objects are dropped.
+    pub fn clamp_indices(&mut self) {
+        let last_material = self.materials.len().saturating_sub(1) as u32;
+        for sphere in self.spheres.iter_mut() {
+            sphere.material_idx = sphere.material_idx.min(last_material);
+        }
+        for mesh in self.object_list.meshes.iter_mut() {
+            mesh.material_idx = mesh.material_idx.min(last_material);
+        }
+
+        let object_count = self.object_list.objects.len();
+        self.lights.retain(|l| (l.id as usize) < object_count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        object::Mesh,
+        scene::{
+            BokehShape, Camera, CameraProjection, FrameData, Material, RenderParam, SceneBuilder,
+            Texture,
+        },
+    };
+
+    #[test]
+    fn out_of_range_indices_are_reported() {
+        let frame_data = FrameData {
+            width: 100,
+            height: 100,
+            index: 0,
+        };
+        let mut scene = SceneBuilder::new(RenderParam::default(), frame_data)
+            .add_sphere(
+                glm::vec3(0.0, 0.0, 0.0),
+                1.0,
+                Material::Lambertian {
+                    albedo: Texture::new_from_color(glm::vec3(0.5, 0.5, 0.5)),
+                },
+            )
+            .add_mesh(
+                Mesh::quad(),
+                Material::DiffuseLight {
+                    emit: Texture::new_from_color(glm::vec3(1.0, 1.0, 1.0)),
+                    strength: 1.0,
+                    two_sided: false,
+                    enabled: true,
+                },
+            )
+            .add_light()
+            .camera(Camera {
+                eye_pos: glm::vec3(0.0, 0.0, 5.0),
+                eye_dir: glm::vec3(0.0, 0.0, -1.0),
+                up: glm::vec3(0.0, 1.0, 0.0),
+                projection: CameraProjection::Perspective { vfov: 30.0 },
+                aperture: 0.0,
+                bokeh: BokehShape::Circle,
+                focus_distance: 10.0,
+                shutter: 0.0,
+            })
+            .build();
+        assert_eq!(scene.validate(), Ok(()));
+
+        scene.spheres[0].material_idx = 7;
+        assert_eq!(
+            scene.validate(),
+            Err(SceneError::InvalidMaterialIndex {
+                primitive: "sphere",
+                index: 0,
+                material_idx: 7,
+                material_count: 2,
+            })
+        );
+
+        scene.spheres[0].material_idx = 0;
+        scene.lights[0].id = 9;
+        assert_eq!(
+            scene.validate(),
+            Err(SceneError::InvalidLightId {
+                light: 0,
+                id: 9,
+                object_count: 2,
+            })
+        );
+    }
+}
@@ -0,0 +1,82 @@
+// Bloom chain: the bright parts of the radiance are downsampled into a few mip levels, then
+// each level is blurred back up and added to the one above it. Mip 0 ends up holding the
+// glow, added to the image by `post.wgsl`.
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+struct BloomParams {
+    // Part of the source holding the image, only the radiance can be partly used.
+    source_scale: vec2<f32>,
+    threshold: f32,
+    _padding: f32,
+};
+
+@group(0) @binding(0) var<uniform> params: BloomParams;
+@group(0) @binding(1) var source: texture_2d<f32>;
+@group(0) @binding(2) var source_sampler: sampler;
+
+// Keeps single fireflies from turning into big blobs.
+const MAX_BLOOM = 64.0;
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    return VertexOutput(vec4<f32>(model.position, 0.0, 1.0), model.tex_coords);
+}
+
+// Texture coordinates start at the top, `tex_coords` at the bottom.
+fn target_uv(in: VertexOutput) -> vec2<f32> {
+    return vec2(in.tex_coords.x, 1.0 - in.tex_coords.y);
+}
+
+fn tap(uv: vec2<f32>) -> vec3<f32> {
+    // Stay inside the used part of the source, the rest holds stale pixels.
+    let half_texel = 0.5 / vec2<f32>(textureDimensions(source));
+    let clamped = clamp(uv, half_texel, params.source_scale - half_texel);
+    return textureSampleLevel(source, source_sampler, clamped, 0.0).rgb;
+}
+
+// Four bilinear taps, each averaging 2x2 texels, cover a 4x4 box.
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source));
+    return 0.25 * (
+        tap(uv + texel * vec2(-1.0, -1.0)) + tap(uv + texel * vec2(1.0, -1.0))
+        + tap(uv + texel * vec2(-1.0, 1.0)) + tap(uv + texel * vec2(1.0, 1.0))
+    );
+}
+
+@fragment
+fn fs_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = min(downsample(target_uv(in) * params.source_scale), vec3(MAX_BLOOM));
+    // Scale rather than subtract so the hue of the bright parts is kept.
+    let brightness = max(color.r, max(color.g, color.b));
+    let contribution = max(brightness - params.threshold, 0.0) / max(brightness, 1e-4);
+    return vec4(color * contribution, 1.0);
+}
+
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(downsample(target_uv(in)), 1.0);
+}
+
+// 3x3 tent filter on the smaller level, added to the target by the blend state.
+@fragment
+fn fs_upsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = target_uv(in);
+    let texel = 1.0 / vec2<f32>(textureDimensions(source));
+    var sum = 4.0 * tap(uv);
+    sum += 2.0 * (
+        tap(uv + texel * vec2(-1.0, 0.0)) + tap(uv + texel * vec2(1.0, 0.0))
+        + tap(uv + texel * vec2(0.0, -1.0)) + tap(uv + texel * vec2(0.0, 1.0))
+    );
+    sum += tap(uv + texel * vec2(-1.0, -1.0)) + tap(uv + texel * vec2(1.0, -1.0))
+        + tap(uv + texel * vec2(-1.0, 1.0)) + tap(uv + texel * vec2(1.0, 1.0));
+    return vec4(sum / 16.0, 1.0);
+}
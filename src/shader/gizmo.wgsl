@@ -0,0 +1,63 @@
+// World space lines drawn over the render with the camera the raytracer uses, for the ground
+// grid and the axes.
+
+struct Camera {
+    eye: vec3<f32>,
+    projection: u32,
+    horizontal: vec3<f32>,
+    blades: u32,
+    vertical: vec3<f32>,
+    u: vec3<f32>,
+    v: vec3<f32>,
+    lensRadius: f32,
+    lowerLeftCorner: vec3<f32>,
+    w: vec3<f32>,
+    shutter: f32,
+}
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+const PROJECTION_ORTHOGRAPHIC = 1u;
+
+// View depths lines are clipped to.
+const NEAR = 0.001;
+const FAR = 10000.0;
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+// Same as `project` in wireframe.wgsl.
+fn project(p: vec3<f32>) -> vec4<f32> {
+    let h = camera.horizontal;
+    let v = camera.vertical;
+    let hv = vec2(dot(h, h), dot(v, v));
+    if camera.projection == PROJECTION_ORTHOGRAPHIC {
+        let offset = p - camera.lowerLeftCorner;
+        let uv = vec2(dot(offset, h), dot(offset, v)) / hv;
+        return vec4(2.0 * uv - 1.0, dot(offset, camera.w) / FAR, 1.0);
+    }
+    let direction = p - camera.eye;
+    let depth = dot(direction, camera.w);
+    let focus = dot(camera.lowerLeftCorner - camera.eye, camera.w);
+    let corner = camera.eye - camera.lowerLeftCorner;
+    let uv = (depth * vec2(dot(corner, h), dot(corner, v))
+        + focus * vec2(dot(direction, h), dot(direction, v))) / hv;
+    return vec4(2.0 * uv - depth, (depth - NEAR) * FAR / (FAR - NEAR), depth);
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    return VertexOutput(project(in.position), in.color);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(in.color, 1.0);
+}
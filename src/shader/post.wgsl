@@ -0,0 +1,94 @@
+// Shows the radiance traced by `raytracing.wgsl` on the surface, after the post effects.
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+struct PostParam {
+    // Part of the radiance texture holding the image.
+    radiance_scale: vec2<f32>,
+    bloom_intensity: f32,
+    vignette: f32,
+    // Computed from the temperature on the CPU.
+    white_balance: vec3<f32>,
+    contrast: f32,
+    saturation: f32,
+};
+
+@group(0) @binding(0) var<uniform> post: PostParam;
+@group(0) @binding(1) var radiance: texture_2d<f32>;
+@group(0) @binding(2) var bloom: texture_2d<f32>;
+@group(0) @binding(3) var linear_sampler: sampler;
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    return VertexOutput(vec4<f32>(model.position, 0.0, 1.0), model.tex_coords);
+}
+
+fn apply_transfer_function(x: f32) -> u32 {
+    let a = 0.055;
+    var y: f32;
+    let xc = clamp(x, 0.0, 1.0);
+    if xc > 0.0031308 {
+        y = (1.0 + a) * pow(xc, 1.0 / 2.4) - a;
+    } else {
+        y = 12.92 * xc;
+    }
+    return u32(round(y * 255.0));
+}
+
+fn from_linear_rgb(c: vec3<f32>) -> vec3<f32> {
+    let r = apply_transfer_function(c.x);
+    let g = apply_transfer_function(c.y);
+    let b = apply_transfer_function(c.z);
+
+    return vec3<f32>(f32(r), f32(g), f32(b)) / 255.0;
+}
+
+const MIDDLE_GRAY = 0.18;
+
+// Linear color of the pixel with the post effects applied.
+fn shade(in: VertexOutput) -> vec3<f32> {
+    // Texture coordinates start at the top, `tex_coords` at the bottom.
+    let uv = vec2(in.tex_coords.x, 1.0 - in.tex_coords.y);
+    var color = textureSampleLevel(radiance, linear_sampler, uv * post.radiance_scale, 0.0).rgb;
+    if post.bloom_intensity > 0.0 {
+        color += post.bloom_intensity * textureSampleLevel(bloom, linear_sampler, uv, 0.0).rgb;
+    }
+    color *= post.white_balance;
+
+    // The grading works on what the display can show.
+    color = clamp(color, vec3(0.0), vec3(1.0));
+    if post.saturation != 1.0 {
+        let luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+        color = max(mix(vec3(luminance), color, post.saturation), vec3(0.0));
+    }
+    if post.contrast != 1.0 {
+        // A power curve in linear space, which keeps black and middle gray in place.
+        color = MIDDLE_GRAY * pow(color / MIDDLE_GRAY, vec3(post.contrast));
+    }
+    if post.vignette > 0.0 {
+        // In pixels so the falloff stays round, 1 in the corners.
+        let size = vec2<f32>(textureDimensions(radiance)) * post.radiance_scale;
+        let distance = length((uv - 0.5) * size) / length(0.5 * size);
+        color *= max(1.0 - post.vignette * distance * distance, 0.0);
+    }
+    return color;
+}
+
+// for webgpu
+@fragment
+fn fs_main_rgb(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(from_linear_rgb(shade(in)), 1.0);
+}
+
+@fragment
+fn fs_main_srgb(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(shade(in), 1.0);
+}
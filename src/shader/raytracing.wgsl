@@ -14,13 +14,15 @@ const FRAC_1_PI = 0.31830987f;
 const FRAC_PI_2 = 1.5707964f;
 const THRESHOLD = 1e-6f;
 
-const MIN_T = 0.001f;
 const MAX_T = 1000f;
+// Distance used for rays escaping into a medium.
+const MEDIUM_FAR = 1e30f;
 
 @group(0) @binding(0) var<uniform> camera: Camera;
 @group(0) @binding(1) var<uniform> frame_data: Frame;
 @group(0) @binding(2) var<uniform> render_param: RenderParam;
-@group(0) @binding(3) var<storage, read_write> image_buffer: array<array<f32, 3>>;
+@group(0) @binding(3) var<storage, read_write> image_buffer: array<u32>;
+@group(0) @binding(4) var<uniform> scene_param: SceneParam;
 
 @group(1) @binding(0) var<storage, read> objects: array<Object>;
 @group(1) @binding(1) var<storage, read> spheres: array<Sphere>;
@@ -67,29 +69,99 @@ fn vs_main(
     );
 }
 
-fn apply_transfer_function(x: f32) -> u32 {
-    let a = 0.055;
-    var y: f32;
-    let xc = clamp(x, 0.0, 1.0);
-    if xc > 0.0031308 {
-        y = (1.0 + a) * pow(xc, 1.0 / 2.4) - a;
-    } else {
-        y = 12.92 * xc;
+// Inverse of the sRGB transfer function, for textures authored in sRGB.
+fn to_linear_rgb(c: vec3<f32>) -> vec3<f32> {
+    let low = c / 12.92;
+    let high = pow((max(c, vec3(0.0)) + 0.055) / 1.055, vec3(2.4));
+    return select(high, low, c <= vec3(0.04045));
+}
+
+const ACCUMULATION_F32 = 0u;
+const ACCUMULATION_F16 = 1u;
+// Largest finite f16.
+const F16_MAX = 65504.0;
+
+// Adds `rgb`, the sum of this frame's samples, to pixel `i` and returns the average so far.
+// In f32 the buffer holds the running average (Welford), the average squared luminance, the
+// view depth of the first hit and the sample count, 6 words per pixel. Counting the samples of
+// every pixel lets reprojection (reproject.wgsl) keep some pixels and start others over. In f16
+// it holds the running average only, 2 words per pixel.
+fn accumulate(i: u32, rgb: vec3<f32>) -> vec3<f32> {
+    let clear = render_param.clear_samples == 1u;
+
+    if render_param.accumulation_format == ACCUMULATION_F16 {
+        // Avoid divide-by-zero if uniforms are ever out of sync.
+        let total = max(1.0, f32(render_param.total_samples));
+        var mean = vec3(0.0);
+        if !clear {
+            let rg = unpack2x16float(image_buffer[2u * i]);
+            let b = unpack2x16float(image_buffer[2u * i + 1u]);
+            mean = vec3(rg, b.x);
+        }
+        // Nothing was traced while paused or once the max is reached.
+        if render_param.samples_per_pixel > 0u {
+            let spp = f32(render_param.samples_per_pixel);
+            mean = min(mix(mean, rgb / spp, spp / total), vec3(F16_MAX));
+            image_buffer[2u * i] = pack2x16float(mean.rg);
+            image_buffer[2u * i + 1u] = pack2x16float(vec2(mean.b, 0.0));
+        }
+        return mean;
+    }
+
+    var mean = vec3(0.0);
+    var mean_sq = 0.0;
+    var samples = 0.0;
+    if !clear {
+        mean = vec3(
+            bitcast<f32>(image_buffer[6u * i]),
+            bitcast<f32>(image_buffer[6u * i + 1u]),
+            bitcast<f32>(image_buffer[6u * i + 2u])
+        );
+        mean_sq = bitcast<f32>(image_buffer[6u * i + 3u]);
+        samples = bitcast<f32>(image_buffer[6u * i + 5u]);
     }
-    return u32(round(y * 255.0));
+    if render_param.samples_per_pixel == 0u {
+        return mean;
+    }
+
+    // The frame is weighted by its sample count, as `spp` samples all equal to its average.
+    let spp = f32(render_param.samples_per_pixel);
+    let weight = spp / (samples + spp);
+    var sample = rgb / spp;
+    let sample_luminance = luminance(sample);
+    if render_param.firefly_sigma > 0.0 && samples >= FIREFLY_MIN_SAMPLES {
+        // A rejected frame counts as the average so far. The deviation still includes it,
+        // or rejecting would narrow it until most frames get rejected.
+        let mean_luminance = luminance(mean);
+        let sigma = sqrt(max(mean_sq - mean_luminance * mean_luminance, 0.0));
+        if abs(sample_luminance - mean_luminance) > render_param.firefly_sigma * sigma {
+            sample = mean;
+        }
+    }
+    mean += weight * (sample - mean);
+    mean_sq += weight * (sample_luminance * sample_luminance - mean_sq);
+
+    image_buffer[6u * i] = bitcast<u32>(mean.r);
+    image_buffer[6u * i + 1u] = bitcast<u32>(mean.g);
+    image_buffer[6u * i + 2u] = bitcast<u32>(mean.b);
+    image_buffer[6u * i + 3u] = bitcast<u32>(mean_sq);
+    image_buffer[6u * i + 4u] = bitcast<u32>(primary_depth);
+    image_buffer[6u * i + 5u] = bitcast<u32>(samples + spp);
+    return mean;
 }
-fn from_linear_rgb(c: vec3<f32>) -> vec3<f32> {
 
-    let r = apply_transfer_function(c.x);
-    let g = apply_transfer_function(c.y);
-    let b = apply_transfer_function(c.z);
+// Samples a pixel needs before any is rejected as a firefly, the deviation of fewer would
+// reject good ones.
+const FIREFLY_MIN_SAMPLES = 16.0;
 
-    return vec3<f32>(f32(r), f32(g), f32(b)) / 255.0;
+fn luminance(rgb: vec3<f32>) -> f32 {
+    return dot(rgb, vec3(0.2126, 0.7152, 0.0722));
 }
 
-// for webgpu
+// Adds this frame's samples to the pixel and writes the average so far, in linear space, to
+// the radiance texture that `post.wgsl` shows.
 @fragment
-fn fs_main_rgb(in: VertexOutput) -> @location(0) vec4<f32> {
+fn fs_trace(in: VertexOutput) -> @location(0) vec4<f32> {
     // Clamp to avoid the last pixel hitting exactly `width`/`height` due to interpolation.
     let u = clamp(in.tex_coords.x, 0.0, 0.99999994);
     let v = clamp(in.tex_coords.y, 0.0, 0.99999994);
@@ -104,59 +176,12 @@ fn fs_main_rgb(in: VertexOutput) -> @location(0) vec4<f32> {
         frame_data.frame_idx
     );
 
-    // Accumulate in linear space in the storage buffer.
-    var pixel = vec3(image_buffer[i][0], image_buffer[i][1], image_buffer[i][2]);
-
-    if render_param.clear_samples == 1u {
-        pixel = vec3(0.0);
-    }
-
-    let rgb = sample_pixel(&rngState, f32(x), f32(y));
-    pixel += rgb;
-    image_buffer[i] = array<f32, 3>(pixel.r, pixel.g, pixel.b);
-
-    // Avoid divide-by-zero if uniforms are ever out of sync.
-    let denom = max(1.0, f32(render_param.total_samples));
-    let linear_out = pixel / denom;
-    let srgb_out = from_linear_rgb(linear_out);
-    return vec4<f32>(srgb_out, 1.0);
-
-    // var noiseState: u32 = init_rng(vec2<u32>(u32(u), u32(v)), vec2<u32>(512u, 512u), 0u);
-    // return vec4<f32>(rng_next_float(&rngState), rng_next_float(&rngState), rng_next_float(&rngState), 1.0);
-}
-
-@fragment
-fn fs_main_srgb(in: VertexOutput) -> @location(0) vec4<f32> {
-    let u = in.tex_coords.x;
-    let v = in.tex_coords.y;
-
-    let x = u32(u * f32(frame_data.width));
-    let y = u32(v * f32(frame_data.height));
-    let i = y * frame_data.width + x;
-
-    var rngState: u32 = init_rng(
-        vec2<u32>(u32(x), u32(y)),
-        vec2<u32>(frame_data.width, frame_data.height),
-        frame_data.frame_idx
-    );
-
-    var pixel = vec3(image_buffer[i][0], image_buffer[i][1], image_buffer[i][2]);
-
-    if render_param.clear_samples == 1u {
-        pixel = vec3(0.0);
+    if render_param.debug_view != DEBUG_VIEW_BEAUTY
+        && render_param.debug_view != DEBUG_VIEW_AMBIENT_OCCLUSION {
+        return vec4<f32>(debug_color(f32(x), f32(y)), 1.0);
     }
 
-    let rgb = sample_pixel(&rngState, f32(x), f32(y));
-    pixel += rgb;
-    image_buffer[i] = array<f32, 3>(pixel.r, pixel.g, pixel.b);
-
-    return vec4<f32>(
-        pixel / f32(render_param.total_samples),
-        1.0
-    );
-
-    // var noiseState: u32 = init_rng(vec2<u32>(u32(u), u32(v)), vec2<u32>(512u, 512u), 0u);
-    // return vec4<f32>(rng_next_float(&rngState), rng_next_float(&rngState), rng_next_float(&rngState), 1.0);
+    return vec4<f32>(accumulate(i, sample_pixel(&rngState, f32(x), f32(y))), 1.0);
 }
 
 struct RenderParam {
@@ -165,8 +190,53 @@ struct RenderParam {
     total_samples: u32,
     clear_samples: u32,
     max_depth: u32,
+    min_depth: u32,
+    ray_epsilon: f32,
+    // `sampler` is a reserved word in WGSL.
+    sampler_kind: u32,
+    debug_view: u32,
+    // Only used on the CPU side.
+    requested_samples_per_pixel: u32,
+    samples_per_pass: u32,
+    convergence_epsilon: f32,
+    tile_size: u32,
+    accumulation_format: u32,
+    firefly_sigma: f32,
+    // Only used on the CPU side.
+    taa: u32,
+    ao_radius: f32,
+    ao_samples: u32,
+    cull_backfaces: u32,
+    shading: u32,
+    // Only used by the post passes.
+    bloom_intensity: f32,
+    vignette: f32,
+    contrast: f32,
+    saturation: f32,
+    white_balance_k: f32,
+    // Only used on the CPU side.
+    motion_scale: f32,
+};
+
+struct SceneParam {
+    background_top: vec3<f32>,
+    background_kind: u32,
+    background_bottom: vec3<f32>,
+    // Homogeneous medium filling the scene, zero when there is none.
+    medium_sigma_a: vec3<f32>,
+    medium_sigma_s: vec3<f32>,
+    // Added to every diffuse hit, black when disabled.
+    ambient: vec3<f32>,
+    // Emissive objects at the start of `lights`, the point lights follow.
+    light_count: u32,
+    // Width, height, offset and flags of the cubemap faces in `textures`.
+    cubemap_faces: array<vec4<u32>, 6>,
 };
 
+const BACKGROUND_SOLID_COLOR = 0u;
+const BACKGROUND_GRADIENT = 1u;
+const BACKGROUND_CUBEMAP = 2u;
+
 struct Frame {
     width: u32,
     height: u32,
@@ -175,14 +245,47 @@ struct Frame {
 
 struct Camera {
     eye: vec3<f32>,
+    projection: u32,
     horizontal: vec3<f32>,
+    // Sides of the aperture, 0 for a circle.
+    blades: u32,
     vertical: vec3<f32>,
     u: vec3<f32>,
     v: vec3<f32>,
     lensRadius: f32,
     lowerLeftCorner: vec3<f32>,
+    w: vec3<f32>,
+    shutter: f32,
 }
 
+const PROJECTION_PERSPECTIVE = 0u;
+const PROJECTION_ORTHOGRAPHIC = 1u;
+
+const SAMPLER_RANDOM = 0u;
+const SAMPLER_STRATIFIED = 1u;
+
+const SHADING_SMOOTH = 0u;
+const SHADING_FLAT = 1u;
+
+const DEBUG_VIEW_BEAUTY = 0u;
+const DEBUG_VIEW_NORMALS = 1u;
+const DEBUG_VIEW_DEPTH = 2u;
+const DEBUG_VIEW_ALBEDO = 3u;
+const DEBUG_VIEW_BVH_HEATMAP = 4u;
+const DEBUG_VIEW_AMBIENT_OCCLUSION = 5u;
+
+// Node visits at which the heatmap saturates.
+const BVH_HEATMAP_MAX_VISITS = 64.0;
+
+// Nodes popped by check_intersection, for the BVH heatmap.
+var<private> bvh_visits: u32 = 0u;
+
+// View depth of the first hit of the last camera ray, 0 when it missed.
+var<private> primary_depth: f32 = 0.0;
+
+// Time of the sample in 0..camera.shutter, picked by get_ray and shared by the whole path.
+var<private> ray_time: f32 = 0.0;
+
 struct Object {
     id: u32,
     obj_type: u32,
@@ -202,39 +305,102 @@ struct Ray {
 struct Sphere {
     center: vec4<f32>,
     radius: f32,
-    material_index: u32,
+    material_idx: u32,
+    velocity: vec4<f32>,
 };
 
 struct Surface {
     vertices: array<vec4<f32>, 3>,
     normals: array<vec4<f32>, 3>,
+    // w is the handedness of the bitangent, zero when the mesh has no usable uvs.
+    tangents: array<vec4<f32>, 3>,
+    uvs: array<vec2<f32>, 3>,
     material_index: u32,
-    padding: array<u32, 3>,
+    padding: u32,
 };
 
 const MAT_LAMBERTIAN = 0u;
 const MAT_METAL = 1u;
 const MAT_DIELECTRIC = 2u;
 const MAT_DIFFUSE_LIGHT = 3u;
+const MAT_OREN_NAYAR = 4u;
+const MAT_PLASTIC = 5u;
+const MAT_COOK_TORRANCE = 6u;
 
 struct Material {
     id: u32,
     desc: TextureDescriptor,
+    // Metal: fuzz, dielectric and plastic: index of refraction, Oren-Nayar: sigma,
+    // diffuse light: strength, Cook-Torrance: metallic.
     fuzz: f32,
+    // Dielectric: Cauchy dispersion coefficient, Cook-Torrance: roughness.
+    y: f32,
+    flags: u32,
+    // Tangent space normal map, offset is NO_TEXTURE when there is none.
+    normal_map: TextureDescriptor,
+    // Strength and roughness of the dielectric coat over the material, 0 when uncoated.
+    clearcoat: f32,
+    clearcoat_roughness: f32,
+    // Relief the uvs are shifted along, offset is NO_TEXTURE when there is none.
+    height_map: TextureDescriptor,
+    // Depth of the relief in uv units.
+    height_scale: f32,
+    // The surface is missing where the red channel is below alpha_cutoff, offset is
+    // NO_TEXTURE when there is none.
+    opacity: TextureDescriptor,
+    alpha_cutoff: f32,
 };
 
+const NO_TEXTURE = 0xffffffffu;
+
+const MATERIAL_FLAG_TWO_SIDED = 1u;
+const MATERIAL_FLAG_DISABLED = 2u;
+
+// Index of refraction of the clearcoat layer.
+const CLEARCOAT_IOR = 1.5;
+// Reflectance at normal incidence of the non metallic part of a Cook-Torrance material.
+const DIELECTRIC_F0 = 0.04;
+// Lowest GGX alpha, perfectly smooth surfaces make the distribution a Dirac.
+const GGX_MIN_ALPHA = 0.001;
+
+// Steps through the height map when looking straight at it and at grazing angles.
+const PARALLAX_MIN_LAYERS = 8.0;
+const PARALLAX_MAX_LAYERS = 32.0;
+
 struct TextureDescriptor {
     width: u32,
     height: u32,
     offset: u32,
+    flags: u32,
+    // uv * uv_scale + uv_offset is sampled. Scalars keep the struct packed like on the CPU.
+    uv_scale_x: f32,
+    uv_scale_y: f32,
+    uv_offset_x: f32,
+    uv_offset_y: f32,
+    // Marble noise, see `marble`.
+    noise_scale: f32,
+    noise_octaves: u32,
 }
 
+const TEXTURE_FLAG_BILINEAR = 1u;
+const TEXTURE_FLAG_SRGB = 2u;
+const TEXTURE_FLAG_REPEAT = 4u;
+const TEXTURE_FLAG_NOISE = 8u;
+
+// Fixed so the noise textures look the same on every run.
+const NOISE_SEED = 0x2545f491u;
+
 struct HitRecord {
     p: vec3<f32>,
     normal: vec3<f32>,
     t: f32,
     material_index: u32,
     front_face: bool,
+    uv: vec2<f32>,
+    tangent: vec4<f32>,
+    // Normal of the actual surface, unlike `normal` it is neither interpolated nor mapped.
+    // Either side.
+    geometric_normal: vec3<f32>,
 };
 
 
@@ -249,11 +415,25 @@ struct Light {
     id: u32,
     // sphere or mesh
     light_type: u32,
+    // Surface area of the object.
+    area: f32,
+    // Point lights only.
+    intensity: f32,
+    position: vec4<f32>,
+    color: vec4<f32>,
 }
 
+// Squared distance under which a point light stops getting brighter.
+const POINT_LIGHT_MIN_DISTANCE_SQ = 1e-4;
+
 const PDF_NONE = 0u;
 const PDF_COSINE = 1u;
 
+// Where the sphere is at `ray_time`.
+fn sphere_center(sphere: Sphere) -> vec3<f32> {
+    return sphere.center.xyz + ray_time * sphere.velocity.xyz;
+}
+
 fn hit_sphere(
     sphere_index: u32,
     ray: Ray,
@@ -263,40 +443,64 @@ fn hit_sphere(
 ) -> bool {
     let sphere = spheres[sphere_index];
 
-    let oc = ray.origin - sphere.center.xyz;
+    // Numerically stable form, see Ray Tracing Gems chapter 7. With the half b, the
+    // discriminant comes from the distance between the center and the line, which does not
+    // cancel out for large spheres seen from far away like the naive b * b - a * c does.
+    let oc = ray.origin - sphere_center(sphere);
     let a = dot(ray.direction, ray.direction);
     let b = dot(ray.direction, oc);
     let c = dot(oc, oc) - sphere.radius * sphere.radius;
-    let discriminant = b * b - a * c;
+    let l = oc - (b / a) * ray.direction;
+    let discriminant = a * (sphere.radius * sphere.radius - dot(l, l));
 
     if discriminant < 0.0 {
         return false;
     }
 
-    let sqrtd = sqrt(discriminant);
+    // q adds two terms of the same sign, the other root comes from the product of both, c / a.
+    let q = -(b + select(-1.0, 1.0, b >= 0.0) * sqrt(discriminant));
+    let t0 = q / a;
+    var t1 = t0;
+    if q != 0.0 {
+        t1 = c / q;
+    }
 
-    var root = (-b - sqrtd) / a;
+    var root = min(t0, t1);
     if root < ray_min || root > ray_max {
-        root = (-b + sqrtd) / a;
+        root = max(t0, t1);
         if root < ray_min || root > ray_max {
             return false;
         }
     }
 
 
-    *hit = sphereIntersection(ray, sphere, root, sphere.material_index);
+    *hit = sphereIntersection(ray, sphere, root, sphere.material_idx);
     return true;
 }
 
 fn sphereIntersection(ray: Ray, sphere: Sphere, t: f32, material_index: u32) -> HitRecord {
     let p = ray.origin + t * ray.direction;
-    var normal = (p - sphere.center.xyz) / sphere.radius;
+    var normal = (p - sphere_center(sphere)) / sphere.radius;
+    let geometric_normal = normal;
+
+    // Spherical coordinates of the outward normal, with the seam on -x.
+    let uv = vec2(
+        (atan2(-normal.z, normal.x) + PI) / (2.0 * PI),
+        acos(clamp(-normal.y, -1.0, 1.0)) / PI,
+    );
+    // Direction of increasing u, undefined at the poles.
+    var tangent = vec4(1.0, 0.0, 0.0, 1.0);
+    let tangent_xz = vec3(normal.z, 0.0, -normal.x);
+    if dot(tangent_xz, tangent_xz) > EPSILON {
+        tangent = vec4(normalize(tangent_xz), 1.0);
+    }
+
     var front_face = true;
     if dot(ray.direction, normal) > 0.0 {
         normal = -normal;
         front_face = false;
     }
-    return HitRecord(p, normal, t, material_index, front_face);
+    return HitRecord(p, normal, t, material_index, front_face, uv, tangent, geometric_normal);
 }
 
 fn hit_triangle(
@@ -316,6 +520,11 @@ fn hit_triangle(
     if a > -EPSILON && a < EPSILON {
         return false;
     }
+    // `a` is minus the dot of the ray direction with the geometric normal `cross(e1, e2)`.
+    if a < 0.0 && render_param.cull_backfaces == 1u
+        && materials[surface.material_index].id != MAT_DIELECTRIC {
+        return false;
+    }
 
     let f = 1.0 / a;
     let s = ray.origin - surface.vertices[0].xyz;
@@ -336,15 +545,33 @@ fn hit_triangle(
     if t > ray_min && t < ray_max {
         let p = ray.origin + t * ray.direction;
         let b = vec3(1.0 - u - v, u, v);
-        let n = b.x * surface.normals[0].xyz + b.y * surface.normals[1].xyz + b.z * surface.normals[2].xyz;
+        var n = cross(e1, e2);
+        if render_param.shading == SHADING_SMOOTH {
+            n = b.x * surface.normals[0].xyz + b.y * surface.normals[1].xyz + b.z * surface.normals[2].xyz;
+        }
         let front_face = dot(ray.direction, n) < 0.0;
-        *hit = HitRecord(p, normalize(n), t, surface.material_index, front_face);
+        let uv = b.x * surface.uvs[0] + b.y * surface.uvs[1] + b.z * surface.uvs[2];
+        let tangent = b.x * surface.tangents[0] + b.y * surface.tangents[1] + b.z * surface.tangents[2];
+        *hit = HitRecord(
+            p, normalize(n), t, surface.material_index, front_face, uv, tangent,
+            normalize(cross(e1, e2)),
+        );
         return true;
     }
 
     return false;
 }
 
+// Whether `hit` is on a transparent texel of a cutout material. Such hits are skipped, the
+// ray goes on to whatever is behind.
+fn is_cut_out(hit: HitRecord) -> bool {
+    let material = materials[hit.material_index];
+    if material.opacity.offset == NO_TEXTURE {
+        return false;
+    }
+    return texture_look_up(material.opacity, hit.uv.x, hit.uv.y, hit.p).x < material.alpha_cutoff;
+}
+
 fn check_intersection(ray: Ray, intersection: ptr<function, HitRecord>) -> bool {
     var closest_so_far = MAX_T;
     var hit_anything = false;
@@ -364,13 +591,14 @@ fn check_intersection(ray: Ray, intersection: ptr<function, HitRecord>) -> bool
 
     while (stack_ptr > 0u) {
         stack_ptr--;
+        bvh_visits++;
         let node_idx = stack[stack_ptr];
         let node = bvh_nodes[node_idx];
 
         let min_p = vec3<f32>(node.min_x, node.min_y, node.min_z);
         let max_p = vec3<f32>(node.max_x, node.max_y, node.max_z);
         
-        if (hit_aabb(min_p, max_p, ray, MIN_T, closest_so_far)) {
+        if (hit_aabb(min_p, max_p, ray, 0.0, closest_so_far)) {
             if (node.count > 0u) {
                 // Leaf
                 // Data has type and primitive index
@@ -380,13 +608,13 @@ fn check_intersection(ray: Ray, intersection: ptr<function, HitRecord>) -> bool
                 let idx = node.data & 0x7FFFFFFFu;
                 
                 if (type_bit == 0u) { // Sphere
-                   if (hit_sphere(idx, ray, MIN_T, closest_so_far, &tmp_rec)) {
+                   if (hit_sphere(idx, ray, 0.0, closest_so_far, &tmp_rec) && !is_cut_out(tmp_rec)) {
                         hit_anything = true;
                         closest_so_far = tmp_rec.t;
                         *intersection = tmp_rec;
                    }
                 } else { // Mesh/Triangle
-                    if (hit_triangle(idx, ray, MIN_T, closest_so_far, &tmp_rec)) {
+                    if (hit_triangle(idx, ray, 0.0, closest_so_far, &tmp_rec) && !is_cut_out(tmp_rec)) {
                         hit_anything = true;
                         closest_so_far = tmp_rec.t;
                         *intersection = tmp_rec;
@@ -413,20 +641,121 @@ fn check_intersection(ray: Ray, intersection: ptr<function, HitRecord>) -> bool
     return hit_anything;
 }
 
+// Debug views bypass accumulation, they trace a single ray through the pixel center.
+fn debug_color(x: f32, y: f32) -> vec3<f32> {
+    var rngState = 0u;
+    let ray = get_ray(&rngState, x + 0.5, y + 0.5);
+
+    bvh_visits = 0u;
+    var hit = HitRecord();
+    let hit_anything = check_intersection(ray, &hit);
+
+    if render_param.debug_view == DEBUG_VIEW_BVH_HEATMAP {
+        return heatmap(f32(bvh_visits) / BVH_HEATMAP_MAX_VISITS);
+    }
+    if !hit_anything {
+        return vec3(0.0);
+    }
+    hit.uv = apply_parallax(materials[hit.material_index], hit, ray.direction);
+
+    switch render_param.debug_view {
+        case DEBUG_VIEW_NORMALS: {
+            let normal = apply_normal_map(materials[hit.material_index], hit);
+            return 0.5 * (normal + 1.0);
+        }
+        case DEBUG_VIEW_DEPTH: {
+            // Distance along the view axis, relative to the extent of the scene.
+            let root = bvh_nodes[0];
+            let scene_min = vec3(root.min_x, root.min_y, root.min_z);
+            let scene_max = vec3(root.max_x, root.max_y, root.max_z);
+            let far = distance(camera.eye, 0.5 * (scene_min + scene_max))
+                + 0.5 * distance(scene_min, scene_max);
+            let depth = hit.t * dot(ray.direction, camera.w);
+            return vec3(1.0 - clamp(depth / far, 0.0, 1.0));
+        }
+        case DEBUG_VIEW_ALBEDO: {
+            let material = materials[hit.material_index];
+            if material.id == MAT_DIELECTRIC {
+                return vec3(1.0);
+            }
+            return texture_look_up(material.desc, hit.uv.x, hit.uv.y, hit.p);
+        }
+        default: {
+            return vec3(0.0);
+        }
+    }
+}
+
+// Fraction of `ao_samples` cosine weighted rays from the first hit that travel `ao_radius`
+// without hitting anything, ignoring materials and lights.
+fn ambient_occlusion(ray: Ray, rngState: ptr<function, u32>) -> f32 {
+    var hit = HitRecord();
+    if !check_intersection(ray, &hit) {
+        return 0.0;
+    }
+
+    let onb = pixar_onb(hit.normal);
+    let ao_samples = max(render_param.ao_samples, 1u);
+    var unoccluded = 0u;
+    for (var i = 0u; i < ao_samples; i += 1u) {
+        let d = rng_in_cosine_hemisphere(rngState);
+        let direction = normalize(onb.u * d.x + onb.v * d.y + onb.w * d.z);
+        var occluder = HitRecord();
+        if !check_intersection(spawn_ray(hit, direction), &occluder)
+            || occluder.t > render_param.ao_radius {
+            unoccluded += 1u;
+        }
+    }
+    return f32(unoccluded) / f32(ao_samples);
+}
+
+// Blue -> green -> red ramp over 0..1.
+fn heatmap(value: f32) -> vec3<f32> {
+    let t = clamp(value, 0.0, 1.0);
+    if t < 0.5 {
+        return mix(vec3(0.0, 0.0, 1.0), vec3(0.0, 1.0, 0.0), 2.0 * t);
+    }
+    return mix(vec3(0.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0), 2.0 * t - 1.0);
+}
+
 fn sample_pixel(rngState: ptr<function, u32>, x: f32, y: f32) -> vec3<f32> {
+    // Stratified sampling splits the pixel in a grid of strata x strata cells and jitters one
+    // sample inside each, the samples left over are placed at random.
+    var strata = 1u;
+    if render_param.sampler_kind == SAMPLER_STRATIFIED {
+        strata = max(1u, u32(sqrt(f32(render_param.samples_per_pixel))));
+    }
+
     var color = vec3(0.0);
     for (var i = 0u; i < render_param.samples_per_pixel; i += 1u) {
-        let ray = get_ray(rngState, x, y);
-        color += ray_color(ray, rngState);
+        var jitter = vec2(rng_next_float(rngState), rng_next_float(rngState));
+        if i < strata * strata {
+            let cell = vec2(f32(i % strata), f32(i / strata));
+            jitter = (cell + jitter) / f32(strata);
+        }
+        let ray = get_ray(rngState, x + jitter.x, y + jitter.y);
+        if render_param.debug_view == DEBUG_VIEW_AMBIENT_OCCLUSION {
+            color += vec3(ambient_occlusion(ray, rngState));
+        } else {
+            color += ray_color(ray, rngState);
+        }
     }
     return color;
 }
 
+// `x` and `y` are the jittered pixel coordinates.
 fn get_ray(rngState: ptr<function, u32>, x: f32, y: f32) -> Ray {
-    let u = f32(x + rng_next_float(rngState)) / f32(frame_data.width);
-    let v = f32(y + rng_next_float(rngState)) / f32(frame_data.height);
+    let u = x / f32(frame_data.width);
+    let v = y / f32(frame_data.height);
+
+    ray_time = camera.shutter * rng_next_float(rngState);
+
+    if camera.projection == PROJECTION_ORTHOGRAPHIC {
+        let origin = camera.lowerLeftCorner + u * camera.horizontal + v * camera.vertical;
+        return Ray(origin, camera.w);
+    }
 
-    let rd = camera.lensRadius * rng_in_unit_disk(rngState);
+    let rd = camera.lensRadius * rng_in_aperture(rngState);
 
     let origin = camera.eye + rd.x * camera.u + rd.y * camera.v;
     let direction = camera.lowerLeftCorner + u * camera.horizontal + v * camera.vertical - origin;
@@ -440,13 +769,65 @@ fn ray_color(first_ray: Ray, rngState: ptr<function, u32>) -> vec3<f32> {
     var sky_color = vec3(0.0);
     var color_from_scatter = vec3(1.0);
     var color_from_emission = vec3(0.0);
+    // Picked on the first dispersive hit, the path is monochromatic from there on.
+    var wavelength = 0.0;
 
     for (var i = 0u; i < render_param.max_depth; i += 1u) {
+        // Russian roulette, paths carrying little light are ended early and the survivors
+        // weighted up to keep the estimate unbiased.
+        if i >= render_param.min_depth {
+            let throughput = max(color_from_scatter.x, max(color_from_scatter.y, color_from_scatter.z));
+            let survival = min(throughput, 0.95);
+            if rng_next_float(rngState) >= survival {
+                break;
+            }
+            color_from_scatter /= survival;
+        }
+
         var intersection = HitRecord();
-        if !check_intersection(ray, &intersection) {
-            let direction = normalize(ray.direction);
-            let a = 0.5 * (direction.y + 1.0);
-            // sky_color = (1.0 - a) * vec3<f32>(1.0, 1.0, 1.0) + a * vec3<f32>(0.5, 0.7, 1);
+        let hit_anything = check_intersection(ray, &intersection);
+        if i == 0u {
+            primary_depth = select(0.0, intersection.t * dot(ray.direction, camera.w), hit_anything);
+        }
+
+        let sigma_t = scene_param.medium_sigma_a + scene_param.medium_sigma_s;
+        if any(sigma_t > vec3(0.0)) {
+            let ray_length = length(ray.direction);
+            let surface_distance = select(MEDIUM_FAR, intersection.t * ray_length, hit_anything);
+
+            // Sample a distance for one of the channels, weighted by the pdf averaged over
+            // the three of them.
+            let channel = min(u32(rng_next_float(rngState) * 3.0), 2u);
+            var distance = MEDIUM_FAR;
+            if sigma_t[channel] > 0.0 {
+                distance = -log(1.0 - rng_next_float(rngState)) / sigma_t[channel];
+            }
+
+            if distance < surface_distance {
+                let transmittance = exp(-sigma_t * distance);
+                let pdf = dot(sigma_t * transmittance, vec3(1.0 / 3.0));
+                if pdf <= THRESHOLD {
+                    color_from_scatter = vec3(0.0);
+                    break;
+                }
+                color_from_scatter *= scene_param.medium_sigma_s * transmittance / pdf;
+                // Isotropic phase function.
+                let origin = ray.origin + distance * ray.direction / ray_length;
+                ray = Ray(origin, rng_in_unit_sphere(rngState));
+                continue;
+            }
+
+            let transmittance = exp(-sigma_t * surface_distance);
+            let probability = dot(transmittance, vec3(1.0 / 3.0));
+            if probability <= THRESHOLD {
+                color_from_scatter = vec3(0.0);
+                break;
+            }
+            color_from_scatter *= transmittance / probability;
+        }
+
+        if !hit_anything {
+            sky_color = background_color(ray.direction);
             break;
         }
         // for triangles only
@@ -455,10 +836,12 @@ fn ray_color(first_ray: Ray, rngState: ptr<function, u32>) -> vec3<f32> {
         // }
 
         let material = materials[intersection.material_index];
-        color_from_emission += color_from_scatter * emitted(material, 0.5, 0.5, intersection);
+        intersection.uv = apply_parallax(material, intersection, ray.direction);
+        intersection.normal = apply_normal_map(material, intersection);
+        color_from_emission += color_from_scatter * emitted(material, intersection.uv.x, intersection.uv.y, intersection);
 
         var scattered = Scatter();
-        if !scatter(&scattered, ray, intersection, material, rngState) {
+        if !scatter(&scattered, ray, intersection, material, rngState, &wavelength) {
             break;
         }
         if scattered.type_pdf == PDF_NONE {
@@ -469,14 +852,26 @@ fn ray_color(first_ray: Ray, rngState: ptr<function, u32>) -> vec3<f32> {
 
         // Use Mixed Sampling (MIS)
         let dir = pdf_generate(rngState, intersection);
-        scattered.ray = Ray(intersection.p, dir);
-        
+        scattered.ray = spawn_ray(intersection, dir);
+
         let pdf = pdf_mixed_value(
             pdf_cosine_value(scattered.ray.direction, pixar_onb(intersection.normal)),
-            pdf_light_value(intersection.p, scattered.ray.direction)
+            pdf_light_value(scattered.ray.origin, scattered.ray.direction)
         );
 
-        let scattering_pdf = scattering_pdf_lambertian(intersection.normal, scattered.ray.direction);
+        var scattering_pdf = scattering_pdf_lambertian(intersection.normal, scattered.ray.direction);
+        if material.id == MAT_OREN_NAYAR {
+            scattering_pdf *= oren_nayar_factor(
+                material.fuzz,
+                intersection.normal,
+                -normalize(ray.direction),
+                normalize(scattered.ray.direction),
+            );
+        }
+
+        color_from_emission += color_from_scatter * scattered.attenuation * scene_param.ambient;
+        color_from_emission += color_from_scatter * scattered.attenuation
+            * point_lights_direct(material, intersection, ray.direction);
 
         if pdf > THRESHOLD {
              color_from_scatter *= (scattered.attenuation * scattering_pdf) / pdf;
@@ -489,6 +884,161 @@ fn ray_color(first_ray: Ray, rngState: ptr<function, u32>) -> vec3<f32> {
 }
 
 
+// Ray leaving `hit` along `direction`, its origin pushed `ray_epsilon` off the surface on the
+// side it leaves through so that it doesn't hit the surface it starts on.
+fn spawn_ray(hit: HitRecord, direction: vec3<f32>) -> Ray {
+    let side = select(-1.0, 1.0, dot(direction, hit.geometric_normal) >= 0.0);
+    return Ray(hit.p + side * render_param.ray_epsilon * hit.geometric_normal, direction);
+}
+
+// Light the point lights bring to a diffuse hit seen along `direction`, times the scattering
+// pdf in their direction. Rays never hit them, they are only seen through this shadow ray.
+fn point_lights_direct(material: Material, hit: HitRecord, direction: vec3<f32>) -> vec3<f32> {
+    var direct = vec3(0.0);
+    let sigma_t = scene_param.medium_sigma_a + scene_param.medium_sigma_s;
+    for (var i = scene_param.light_count; i < arrayLength(&lights); i += 1u) {
+        let light = lights[i];
+        if light.intensity <= 0.0 {
+            continue;
+        }
+        let shadow_ray = spawn_ray(hit, light.position.xyz - hit.p);
+        let to_light = light.position.xyz - shadow_ray.origin;
+        var scattering_pdf = scattering_pdf_lambertian(hit.normal, to_light);
+        if scattering_pdf <= 0.0 {
+            continue;
+        }
+        if material.id == MAT_OREN_NAYAR {
+            scattering_pdf *= oren_nayar_factor(
+                material.fuzz,
+                hit.normal,
+                -normalize(direction),
+                normalize(to_light),
+            );
+        }
+        // Anything between the hit and the light, t is relative to `to_light`.
+        var occluder = HitRecord();
+        if check_intersection(Ray(shadow_ray.origin, to_light), &occluder) && occluder.t < 1.0 {
+            continue;
+        }
+        let distance_sq = max(dot(to_light, to_light), POINT_LIGHT_MIN_DISTANCE_SQ);
+        let transmittance = exp(-sigma_t * sqrt(distance_sq));
+        direct += light.color.xyz * light.intensity * transmittance * scattering_pdf
+            / distance_sq;
+    }
+    return direct;
+}
+
+// Uvs where a ray along `direction` meets the height map of the material, found by marching
+// it in tangent space (parallax occlusion mapping). The hit uvs when there is no relief.
+fn apply_parallax(material: Material, hit: HitRecord, direction: vec3<f32>) -> vec2<f32> {
+    if material.height_map.offset == NO_TEXTURE || material.height_scale == 0.0
+        || hit.tangent.w == 0.0 {
+        return hit.uv;
+    }
+    let n = hit.normal;
+    let t = hit.tangent.xyz - dot(n, hit.tangent.xyz) * n;
+    if dot(t, t) <= EPSILON {
+        return hit.uv;
+    }
+    let tangent = normalize(t);
+    let bitangent = cross(n, tangent) * sign(hit.tangent.w);
+    let v = -normalize(direction);
+    let view = vec3(dot(v, tangent), dot(v, bitangent), dot(v, n));
+    if view.z <= EPSILON {
+        return hit.uv;
+    }
+
+    // Grazing rays cross more of the height map, give them more steps.
+    let layers = mix(PARALLAX_MAX_LAYERS, PARALLAX_MIN_LAYERS, view.z);
+    let layer_depth = 1.0 / layers;
+    let step = view.xy / view.z * material.height_scale * layer_depth;
+    var uv = hit.uv;
+    var depth = 0.0;
+    var surface_depth = 1.0 - texture_look_up(material.height_map, uv.x, uv.y, hit.p).x;
+    for (var i = 0u; i < u32(layers) && depth < surface_depth; i += 1u) {
+        uv -= step;
+        depth += layer_depth;
+        surface_depth = 1.0 - texture_look_up(material.height_map, uv.x, uv.y, hit.p).x;
+    }
+    if depth == 0.0 {
+        return uv;
+    }
+
+    // The surface crossed the ray between the last two steps, interpolate between them.
+    let previous_uv = uv + step;
+    let previous_surface_depth =
+        1.0 - texture_look_up(material.height_map, previous_uv.x, previous_uv.y, hit.p).x;
+    let after = surface_depth - depth;
+    let before = previous_surface_depth - (depth - layer_depth);
+    let weight = after / min(after - before, -EPSILON);
+    return mix(uv, previous_uv, clamp(weight, 0.0, 1.0));
+}
+
+// Shading normal of the hit, perturbed by the material normal map if it has one.
+fn apply_normal_map(material: Material, hit: HitRecord) -> vec3<f32> {
+    let n = hit.normal;
+    if material.normal_map.offset == NO_TEXTURE || hit.tangent.w == 0.0 {
+        return n;
+    }
+    // Gram-Schmidt, the interpolated tangent is not orthogonal to the interpolated normal.
+    let t = hit.tangent.xyz - dot(n, hit.tangent.xyz) * n;
+    if dot(t, t) <= EPSILON {
+        return n;
+    }
+    let tangent = normalize(t);
+    // The normal has been flipped towards the ray on back faces, so is the bitangent.
+    let bitangent = cross(n, tangent) * sign(hit.tangent.w);
+    let local = texture_look_up(material.normal_map, hit.uv.x, hit.uv.y, hit.p) * 2.0 - 1.0;
+    let mapped = normalize(local.x * tangent + local.y * bitangent + local.z * n);
+    // Keep the shading normal in the hemisphere of the geometric one.
+    if dot(mapped, n) <= EPSILON {
+        return n;
+    }
+    return mapped;
+}
+
+fn background_color(direction: vec3<f32>) -> vec3<f32> {
+    if scene_param.background_kind == BACKGROUND_SOLID_COLOR {
+        return scene_param.background_top;
+    }
+    if scene_param.background_kind == BACKGROUND_CUBEMAP {
+        return cubemap_color(direction);
+    }
+    let a = 0.5 * (normalize(direction).y + 1.0);
+    return mix(scene_param.background_bottom, scene_param.background_top, a);
+}
+
+// Picks the face of the dominant axis of `direction`, with the face coordinates of OpenGL
+// cubemaps. The first row of every face image is its top.
+fn cubemap_color(direction: vec3<f32>) -> vec3<f32> {
+    let d = normalize(direction);
+    let a = abs(d);
+    var face = 0u;
+    var sc = 0.0;
+    var tc = 0.0;
+    var ma = 0.0;
+    if a.x >= a.y && a.x >= a.z {
+        face = select(1u, 0u, d.x > 0.0);
+        sc = select(d.z, -d.z, d.x > 0.0);
+        tc = -d.y;
+        ma = a.x;
+    } else if a.y >= a.z {
+        face = select(3u, 2u, d.y > 0.0);
+        sc = d.x;
+        tc = select(-d.z, d.z, d.y > 0.0);
+        ma = a.y;
+    } else {
+        face = select(5u, 4u, d.z > 0.0);
+        sc = select(-d.x, d.x, d.z > 0.0);
+        tc = -d.y;
+        ma = a.z;
+    }
+    let f = scene_param.cubemap_faces[face];
+    let desc = TextureDescriptor(f.x, f.y, f.z, f.w, 1.0, 1.0, 0.0, 0.0, 0.0, 0u);
+    let s = 0.5 * (sc / ma + 1.0);
+    let t = 0.5 * (tc / ma + 1.0);
+    return texture_look_up(desc, s, 1.0 - t, d);
+}
 
 struct ONB {
     u: vec3<f32>,
@@ -510,8 +1060,11 @@ fn pixar_onb(n: vec3<f32>) -> ONB {
 fn emitted(material: Material, u: f32, v: f32, hit: HitRecord) -> vec3<f32> {
     switch (material.id) {
         case MAT_DIFFUSE_LIGHT: {
-            if hit.front_face {
-                return texture_look_up(material.desc, u, v);
+            if (material.flags & MATERIAL_FLAG_DISABLED) != 0u {
+                return vec3(0.0);
+            }
+            if hit.front_face || (material.flags & MATERIAL_FLAG_TWO_SIDED) != 0u {
+                return material.fuzz * texture_look_up(material.desc, u, v, hit.p);
             } else {
                 return vec3(0.0);
             }
@@ -528,30 +1081,69 @@ fn scatter(
     hit: HitRecord,
     material: Material,
     rngState: ptr<function, u32>,
+    wavelength: ptr<function, f32>,
 ) -> bool {
+    // Either bounce off the coat or go through to the base, with the probability of the
+    // Fresnel reflectance so that neither needs weighting.
+    if material.clearcoat > 0.0 && hit.front_face {
+        let unit_direction = normalize(ray.direction);
+        let cos_theta = clamp(dot(-unit_direction, hit.normal), 0.0, 1.0);
+        if material.clearcoat * reflectance(cos_theta, 1.0 / CLEARCOAT_IOR) > rng_next_float(rngState) {
+            let reflected = reflect(unit_direction, hit.normal)
+                + material.clearcoat_roughness * rng_in_unit_sphere(rngState);
+            *s = Scatter(spawn_ray(hit, reflected), vec3(1.0), PDF_NONE);
+            return dot(reflected, hit.normal) > 0.0;
+        }
+    }
+
     switch (material.id) 
     {
-        case MAT_LAMBERTIAN:
+        case MAT_LAMBERTIAN, MAT_OREN_NAYAR:
         {
-            (*s).attenuation = texture_look_up(material.desc, 0.5, 0.5);
+            (*s).attenuation = texture_look_up(material.desc, hit.uv.x, hit.uv.y, hit.p);
             (*s).type_pdf = PDF_COSINE;
         }
+        case MAT_PLASTIC:
+        {
+            let unit_direction = normalize(ray.direction);
+            let cos_theta = clamp(dot(-unit_direction, hit.normal), 0.0, 1.0);
+            if reflectance(cos_theta, 1.0 / material.fuzz) > rng_next_float(rngState) {
+                *s = Scatter(spawn_ray(hit, reflect(unit_direction, hit.normal)), vec3(1.0), PDF_NONE);
+            } else {
+                (*s).attenuation = texture_look_up(material.desc, hit.uv.x, hit.uv.y, hit.p);
+                (*s).type_pdf = PDF_COSINE;
+            }
+        }
+        case MAT_COOK_TORRANCE:
+        {
+            return scatter_cook_torrance(s, ray, hit, material, rngState);
+        }
         case MAT_METAL: 
         {
             var reflected = reflect(ray.direction, hit.normal);
             let fuzz = material.fuzz;
             reflected = normalize(reflected) + fuzz * rng_in_unit_sphere(rngState);
             *s = Scatter(
-                Ray(hit.p, reflected),
-                texture_look_up(material.desc, 0.5, 0.5), PDF_NONE
+                spawn_ray(hit, reflected),
+                texture_look_up(material.desc, hit.uv.x, hit.uv.y, hit.p), PDF_NONE
             );
         }
         case MAT_DIELECTRIC: 
         {
-            var ri: f32 = material.fuzz;
+            var ior = material.fuzz;
+            var attenuation = vec3(1.0);
+            if material.y > 0.0 {
+                if *wavelength == 0.0 {
+                    *wavelength = mix(WAVELENGTH_MIN, WAVELENGTH_MAX, rng_next_float(rngState));
+                    attenuation = wavelength_to_rgb(*wavelength) / WAVELENGTH_RGB_MEAN;
+                }
+                ior = cauchy_ior(material.fuzz, material.y, *wavelength);
+            }
+
+            var ri: f32 = ior;
             // use select here
             if hit.front_face {
-                ri = 1.0 / material.fuzz;
+                ri = 1.0 / ior;
             }
 
             let unit_direction = normalize(ray.direction);
@@ -566,8 +1158,8 @@ fn scatter(
                 direction = refract(unit_direction, hit.normal, ri);
             }
             *s = Scatter(
-                Ray(hit.p, direction),
-                vec3(1.0), PDF_NONE
+                spawn_ray(hit, direction),
+                attenuation, PDF_NONE
             );
         }
         case MAT_DIFFUSE_LIGHT: 
@@ -581,11 +1173,101 @@ fn scatter(
     return true;
 }
 
+// Picks the specular lobe with the probability of its Fresnel reflectance seen from the ray,
+// the diffuse one otherwise. The specular lobe samples half vectors from the GGX distribution,
+// the diffuse one is Lambertian and weighted by the light the specular one didn't reflect.
+fn scatter_cook_torrance(
+    s: ptr<function, Scatter>,
+    ray: Ray,
+    hit: HitRecord,
+    material: Material,
+    rngState: ptr<function, u32>,
+) -> bool {
+    let albedo = texture_look_up(material.desc, hit.uv.x, hit.uv.y, hit.p);
+    let metallic = clamp(material.fuzz, 0.0, 1.0);
+    let f0 = mix(vec3(DIELECTRIC_F0), albedo, metallic);
+    let wo = -normalize(ray.direction);
+    let n_dot_v = clamp(dot(hit.normal, wo), EPSILON, 1.0);
+
+    let fresnel_view = fresnel_schlick(n_dot_v, f0);
+    let specular_probability = mix(dot(fresnel_view, vec3(1.0 / 3.0)), 1.0, metallic);
+    if rng_next_float(rngState) >= specular_probability {
+        (*s).attenuation = albedo * (1.0 - fresnel_view) / (1.0 - dot(fresnel_view, vec3(1.0 / 3.0)));
+        (*s).type_pdf = PDF_COSINE;
+        return true;
+    }
+
+    let alpha = max(material.y * material.y, GGX_MIN_ALPHA);
+    let onb = pixar_onb(hit.normal);
+    let u1 = rng_next_float(rngState);
+    let u2 = rng_next_float(rngState);
+    let cos_theta = sqrt((1.0 - u2) / (1.0 + (alpha * alpha - 1.0) * u2));
+    let sin_theta = sqrt(max(0.0, 1.0 - cos_theta * cos_theta));
+    let phi = 2.0 * PI * u1;
+    let h = normalize(
+        sin_theta * cos(phi) * onb.u + sin_theta * sin(phi) * onb.v + cos_theta * onb.w
+    );
+    let wi = reflect(-wo, h);
+    let n_dot_l = dot(hit.normal, wi);
+    let v_dot_h = dot(wo, h);
+    if n_dot_l <= 0.0 || v_dot_h <= 0.0 {
+        return false;
+    }
+
+    // With half vectors sampled by D(h) cos(theta_h), the D terms cancel out of
+    // f cos(theta_l) / pdf.
+    let g = smith_g1(n_dot_v, alpha) * smith_g1(n_dot_l, alpha);
+    let weight = fresnel_schlick(v_dot_h, f0) * g * v_dot_h / (n_dot_v * cos_theta);
+    *s = Scatter(spawn_ray(hit, wi), weight / specular_probability, PDF_NONE);
+    return true;
+}
+
+fn fresnel_schlick(cosine: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(1.0 - cosine, 5.0);
+}
+
+// Smith masking of the GGX distribution for a direction at `cosine` from the normal.
+fn smith_g1(cosine: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    return 2.0 * cosine / (cosine + sqrt(a2 + (1.0 - a2) * cosine * cosine));
+}
+
 fn scattering_pdf_lambertian(normal: vec3<f32>, direction: vec3<f32>) -> f32 {
     let cos_theta = dot(normalize(direction), normal);
     return select(0.0, cos_theta / PI, cos_theta > 0.0);
 }
 
+// Qualitative Oren-Nayar model, the BRDF is the Lambertian one scaled by this factor. `wo` points
+// to the viewer and `wi` to the light, `sigma` is the roughness in radians, 0 being Lambertian.
+fn oren_nayar_factor(sigma: f32, normal: vec3<f32>, wo: vec3<f32>, wi: vec3<f32>) -> f32 {
+    let sigma2 = sigma * sigma;
+    let a = 1.0 - 0.5 * sigma2 / (sigma2 + 0.33);
+    let b = 0.45 * sigma2 / (sigma2 + 0.09);
+
+    let cos_i = clamp(dot(normal, wi), EPSILON, 1.0);
+    let cos_o = clamp(abs(dot(normal, wo)), EPSILON, 1.0);
+    let sin_i = sqrt(1.0 - cos_i * cos_i);
+    let sin_o = sqrt(1.0 - cos_o * cos_o);
+
+    // Cosine of the azimuth difference, from the directions projected on the tangent plane.
+    var cos_phi = 0.0;
+    if sin_i > EPSILON && sin_o > EPSILON {
+        let ti = normalize(wi - normal * dot(normal, wi));
+        let to = normalize(wo - normal * dot(normal, wo));
+        cos_phi = max(0.0, dot(ti, to));
+    }
+
+    // alpha = max(theta_i, theta_o), beta = min(theta_i, theta_o)
+    var sin_alpha = sin_i;
+    var tan_beta = sin_o / cos_o;
+    if cos_i > cos_o {
+        sin_alpha = sin_o;
+        tan_beta = sin_i / cos_i;
+    }
+
+    return a + b * cos_phi * sin_alpha * tan_beta;
+}
+
 fn vec3_near_zero(v: vec3<f32>) -> bool {
     let s = 1e-8;
     return (abs(v.x) < s) && (abs(v.y) < s) && (abs(v.z) < s);
@@ -602,6 +1284,40 @@ fn refract(uv: vec3<f32>, n: vec3<f32>, etai_over_etat: f32) -> vec3<f32> {
     return r_out_perp + r_out_parallel;
 }
 
+const WAVELENGTH_MIN = 380.0;
+const WAVELENGTH_MAX = 780.0;
+// Mean of wavelength_to_rgb over WAVELENGTH_MIN..WAVELENGTH_MAX, dividing by it keeps white light
+// white once averaged over wavelengths.
+const WAVELENGTH_RGB_MEAN = vec3(0.44045667, 0.28846619, 0.27324039);
+
+// Cauchy's equation, with `ior` being the index at the sodium D line and the wavelength in nm.
+fn cauchy_ior(ior: f32, b: f32, wavelength: f32) -> f32 {
+    let um = wavelength * 0.001;
+    return ior + b * (1.0 / (um * um) - 1.0 / (0.5893 * 0.5893));
+}
+
+fn cie_lobe(x: f32, mu: f32, sigma_low: f32, sigma_high: f32) -> f32 {
+    let t = (x - mu) / select(sigma_high, sigma_low, x < mu);
+    return exp(-0.5 * t * t);
+}
+
+// Linear sRGB of a wavelength in nm, using the multi-lobe fit of the CIE 1931 matching functions
+// from Wyman et al. 2013. Out of gamut values are clamped.
+fn wavelength_to_rgb(wavelength: f32) -> vec3<f32> {
+    let l = wavelength;
+    let x = 1.056 * cie_lobe(l, 599.8, 37.9, 31.0) + 0.362 * cie_lobe(l, 442.0, 16.0, 26.7)
+        - 0.065 * cie_lobe(l, 501.1, 20.4, 26.2);
+    let y = 0.821 * cie_lobe(l, 568.8, 46.9, 40.5) + 0.286 * cie_lobe(l, 530.9, 16.3, 31.1);
+    let z = 1.217 * cie_lobe(l, 437.0, 11.8, 36.0) + 0.681 * cie_lobe(l, 459.0, 26.0, 13.8);
+
+    let rgb = vec3(
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    );
+    return max(rgb, vec3(0.0));
+}
+
 fn reflectance(cosine: f32, ref_idx: f32) -> f32 {
     var r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
     r0 = r0 * r0;
@@ -666,6 +1382,23 @@ fn rng_in_unit_disk(state: ptr<function, u32>) -> vec2<f32> {
     return vec2(r * cos(theta), r * sin(theta));
 }
 
+// Uniform point on the camera aperture, a unit disk or the regular polygon with `blades`
+// corners inscribed in it.
+fn rng_in_aperture(state: ptr<function, u32>) -> vec2<f32> {
+    if camera.blades == 0u {
+        return rng_in_unit_disk(state);
+    }
+    // Every blade spans a triangle of the same area with the center, pick one then a
+    // point in it.
+    let n = f32(camera.blades);
+    let blade = floor(rng_next_float(state) * n);
+    let a0 = 2.0 * PI * blade / n + 0.5 * PI;
+    let a1 = a0 + 2.0 * PI / n;
+    let r = sqrt(rng_next_float(state));
+    let s = rng_next_float(state);
+    return r * mix(vec2(cos(a0), sin(a0)), vec2(cos(a1), sin(a1)), s);
+}
+
 fn rng_next_int(state: ptr<function, u32>) -> u32 {
     // PCG random number generator
     // Based on https://www.shadertoy.com/view/XlGcRh
@@ -706,16 +1439,36 @@ fn rng_next_vec3_surface(
     return point;
 }
 
-fn rnd_to_sphere(radius: f32, distance_squared: f32, state: ptr<function, u32>) -> vec3<f32> {
+// Cosine of the half angle of the cone the sphere subtends from `origin`. From inside the
+// sphere every direction hits it, which is a cone of half angle PI.
+fn sphere_cos_theta_max(sphere: Sphere, origin: vec3<f32>) -> f32 {
+    let center_to_origin = origin - sphere_center(sphere);
+    let dist_sq = dot(center_to_origin, center_to_origin);
+    let radius_sq = sphere.radius * sphere.radius;
+    if dist_sq <= radius_sq {
+        return -1.0;
+    }
+    return sqrt(1.0 - radius_sq / dist_sq);
+}
+
+// Direction from `origin` uniformly distributed in solid angle over the cone subtended by the
+// sphere, which has a lot less variance than picking points on its area.
+fn sphere_light_generate(sphere: Sphere, origin: vec3<f32>, state: ptr<function, u32>) -> vec3<f32> {
+    let cos_theta_max = sphere_cos_theta_max(sphere, origin);
     let r1 = rng_next_float(state);
     let r2 = rng_next_float(state);
-    let z = 1.0 + r2 * (sqrt(1.0 - radius * radius / distance_squared) - 1.0);
+    let z = 1.0 + r2 * (cos_theta_max - 1.0);
 
     let phi = 2.0 * PI * r1;
-    let x = cos(phi) * sqrt(1.0 - z * z);
-    let y = sin(phi) * sqrt(1.0 - z * z);
+    let sin_theta = sqrt(max(0.0, 1.0 - z * z));
+    let onb = pixar_onb(sphere_center(sphere) - origin);
+    return onb.u * (cos(phi) * sin_theta) + onb.v * (sin(phi) * sin_theta) + onb.w * z;
+}
 
-    return vec3(x, y, z);
+// Solid angle pdf of `sphere_light_generate`, for a direction known to hit the sphere.
+fn sphere_light_pdf(sphere: Sphere, origin: vec3<f32>) -> f32 {
+    let solid_angle = 2.0 * PI * (1.0 - sphere_cos_theta_max(sphere, origin));
+    return 1.0 / max(solid_angle, EPSILON);
 }
 
 fn area_surface(vertice: array<vec4<f32>, 3>) -> f32 {
@@ -750,7 +1503,7 @@ fn pdf_cosine_generate(state: ptr<function, u32>, onb: ONB) -> vec3<f32> {
 }
 
 fn pdf_light_generate(state: ptr<function, u32>, origin: vec3<f32>) -> vec3<f32> {
-    let light_count = arrayLength(&lights);
+    let light_count = scene_param.light_count;
     if (light_count == 0u) { return vec3(0.0, 1.0, 0.0); }
     
     let light_idx = min(u32(rng_next_float(state) * f32(light_count)), light_count - 1u);
@@ -759,15 +1512,20 @@ fn pdf_light_generate(state: ptr<function, u32>, origin: vec3<f32>) -> vec3<f32>
 
     switch obj.obj_type {
         case OBJECT_SPHERE: {
-            let sphere = spheres[obj.offset];
-            let direction = sphere.center.xyz - origin;
-            let distance = length(direction);
-            let onb = pixar_onb(direction);
-            let rnd_direction = rnd_to_sphere(sphere.radius, distance * distance, state);
-            return onb.u * rnd_direction.x + onb.v * rnd_direction.y + onb.w * rnd_direction.z;
+            return sphere_light_generate(spheres[obj.offset], origin, state);
         }
         case OBJECT_MESHES: {
-            let triangle_idx = u32(rng_next_float(state) * f32(obj.count));
+            // Pick a triangle in proportion to its area, the points are uniform over the
+            // whole light.
+            var remaining = rng_next_float(state) * light.area;
+            var triangle_idx = obj.count - 1u;
+            for (var j = 0u; j < obj.count; j += 1u) {
+                remaining -= area_surface(surfaces[obj.offset + j].vertices);
+                if remaining <= 0.0 {
+                    triangle_idx = j;
+                    break;
+                }
+            }
             let vertices = surfaces[obj.offset + triangle_idx].vertices;
             let p = rng_next_vec3_surface(state, vertices);
             return normalize(p - origin);
@@ -787,13 +1545,13 @@ fn get_pdf_for_light(light_idx: u32, origin: vec3<f32>, direction: vec3<f32>) ->
     var closest = MAX_T;
 
     if (obj.obj_type == OBJECT_SPHERE) {
-         if (hit_sphere(obj.offset, Ray(origin, direction), MIN_T, MAX_T, &hit)) {
+         if (hit_sphere(obj.offset, Ray(origin, direction), 0.0, MAX_T, &hit)) {
              hit_something = true;
          }
     } else {
          var tmp_rec = HitRecord();
          for (var j = 0u; j < obj.count; j += 1u) {
-            if (hit_triangle(obj.offset + j, Ray(origin, direction), MIN_T, closest, &tmp_rec)) {
+            if (hit_triangle(obj.offset + j, Ray(origin, direction), 0.0, closest, &tmp_rec)) {
                 hit_something = true;
                 closest = tmp_rec.t;
                 hit = tmp_rec;
@@ -805,26 +1563,14 @@ fn get_pdf_for_light(light_idx: u32, origin: vec3<f32>, direction: vec3<f32>) ->
 
     switch obj.obj_type {
         case OBJECT_SPHERE: {
-            let sphere = spheres[obj.offset];
-            let center_to_origin = origin - sphere.center.xyz;
-            let dist_sq = dot(center_to_origin, center_to_origin); 
-
-            let cos_theta_max = sqrt(1.0 - sphere.radius * sphere.radius / dist_sq);
-            let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
-
-            return 1.0 / solid_angle;
+            return sphere_light_pdf(spheres[obj.offset], origin);
         }
         case OBJECT_MESHES: {
-            // Approximation: Uses area of first triangle * count. 
-            // Correct for quads/uniform meshes.
-            let vertices = surfaces[obj.offset].vertices;
-            let area = area_surface(vertices) * f32(obj.count);
-
             let dist_sq = hit.t * hit.t * dot(direction, direction);
             let cosine = abs(dot(direction, hit.normal) / length(direction));
             
             if (cosine < THRESHOLD) { return 0.0; }
-            let pdf = dist_sq / (cosine * area);
+            let pdf = dist_sq / (cosine * light.area);
             return pdf;
         }
         default: {
@@ -834,7 +1580,7 @@ fn get_pdf_for_light(light_idx: u32, origin: vec3<f32>, direction: vec3<f32>) ->
 }
 
 fn pdf_light_value(origin: vec3<f32>, direction: vec3<f32>) -> f32 {
-    let light_len = arrayLength(&lights);
+    let light_len = scene_param.light_count;
     if (light_len == 0u) { return 0.0; }
 
     var sum_pdf = 0.0;
@@ -876,14 +1622,97 @@ fn pdf_value(pdf_type: u32, direction: vec3<f32>, onb: ONB) -> f32 {
     }
 }
 
-fn texture_look_up(desc: TextureDescriptor, x: f32, y: f32) -> vec3<f32> {
+fn texture_look_up(desc: TextureDescriptor, x: f32, y: f32, p: vec3<f32>) -> vec3<f32> {
+    if (desc.flags & TEXTURE_FLAG_NOISE) != 0u {
+        return texel(desc, 0u, 0u) * marble(p, desc.noise_scale, desc.noise_octaves);
+    }
+
+    let repeat = (desc.flags & TEXTURE_FLAG_REPEAT) != 0u;
     var u = clamp(x, 0f, 1f);
     var v = 1f - clamp(y, 0f, 1f);
+    if repeat {
+        u = fract(x * desc.uv_scale_x + desc.uv_offset_x);
+        v = 1f - fract(y * desc.uv_scale_y + desc.uv_offset_y);
+    }
 
-    let j = u32(u * f32(desc.width));
-    let i = u32(v * f32(desc.height));
-    let idx = i * desc.width + j;
+    if (desc.flags & TEXTURE_FLAG_BILINEAR) != 0u {
+        // Texel centers sit at half integers, blend the four around the sample point.
+        let p = vec2(u * f32(desc.width), v * f32(desc.height)) - 0.5;
+        let t = fract(p);
+        var j0 = u32(clamp(floor(p.x), 0.0, f32(desc.width - 1u)));
+        var i0 = u32(clamp(floor(p.y), 0.0, f32(desc.height - 1u)));
+        var j1 = min(u32(max(floor(p.x) + 1.0, 0.0)), desc.width - 1u);
+        var i1 = min(u32(max(floor(p.y) + 1.0, 0.0)), desc.height - 1u);
+        if repeat {
+            // Blend across the edges with the texels of the next tile.
+            j0 = wrap_index(floor(p.x), desc.width);
+            i0 = wrap_index(floor(p.y), desc.height);
+            j1 = wrap_index(floor(p.x) + 1.0, desc.width);
+            i1 = wrap_index(floor(p.y) + 1.0, desc.height);
+        }
+        let top = mix(texel(desc, i0, j0), texel(desc, i0, j1), t.x);
+        let bottom = mix(texel(desc, i1, j0), texel(desc, i1, j1), t.x);
+        return mix(top, bottom, t.y);
+    }
+
+    let j = min(u32(u * f32(desc.width)), desc.width - 1u);
+    let i = min(u32(v * f32(desc.height)), desc.height - 1u);
+    return texel(desc, i, j);
+}
 
-    let elem = textures[desc.offset + idx];
-    return vec3(elem[0u], elem[1u], elem[2u]);
+// Stripes along z bent by turbulence, in [0, 1].
+fn marble(p: vec3<f32>, scale: f32, octaves: u32) -> f32 {
+    return 0.5 * (1.0 + sin(scale * p.z + 10.0 * turbulence(p, octaves)));
+}
+
+// Sum of the absolute value of `octaves` layers of noise, each one at twice the frequency
+// and half the weight of the previous.
+fn turbulence(p: vec3<f32>, octaves: u32) -> f32 {
+    var sum = 0.0;
+    var q = p;
+    var weight = 1.0;
+    for (var i = 0u; i < octaves; i += 1u) {
+        sum += weight * abs(perlin(q));
+        weight *= 0.5;
+        q *= 2.0;
+    }
+    return sum;
+}
+
+// Gradient noise, interpolating the dot products with the random gradients of the corners of
+// the unit cell around `p`.
+fn perlin(p: vec3<f32>) -> f32 {
+    let cell = floor(p);
+    let f = p - cell;
+    // Quintic fade, continuous up to the second derivative.
+    let w = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+    var value = 0.0;
+    for (var i = 0u; i < 8u; i += 1u) {
+        let corner = vec3(f32(i & 1u), f32((i >> 1u) & 1u), f32((i >> 2u) & 1u));
+        let weight = mix(1.0 - w, w, corner);
+        value += weight.x * weight.y * weight.z * dot(noise_gradient(cell + corner), f - corner);
+    }
+    return value;
+}
+
+fn noise_gradient(corner: vec3<f32>) -> vec3<f32> {
+    let c = vec3<u32>(vec3<i32>(corner));
+    let h = jenkin_hash(jenkin_hash(jenkin_hash(c.x ^ NOISE_SEED) ^ c.y) ^ c.z);
+    let g = vec3(f32(h & 0x3ffu), f32((h >> 10u) & 0x3ffu), f32((h >> 20u) & 0x3ffu)) / 511.5 - 1.0;
+    return normalize(g + vec3(EPSILON));
+}
+
+// `i` modulo `n`, for indices one texel outside of the texture.
+fn wrap_index(i: f32, n: u32) -> u32 {
+    return u32(i - f32(n) * floor(i / f32(n))) % n;
+}
+
+// Linear value of a texel, decoded before filtering.
+fn texel(desc: TextureDescriptor, i: u32, j: u32) -> vec3<f32> {
+    let elem = textures[desc.offset + i * desc.width + j];
+    let color = vec3(elem[0u], elem[1u], elem[2u]);
+    if (desc.flags & TEXTURE_FLAG_SRGB) != 0u {
+        return to_linear_rgb(color);
+    }
+    return color;
 }
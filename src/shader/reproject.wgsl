@@ -0,0 +1,156 @@
+// Reprojection of the accumulation buffer into the view of a camera that moved, used for
+// temporal anti-aliasing. Every destination pixel looks for the surface it sees in the source
+// image, using the depths kept in the buffer, and takes the samples found there. Source pixels
+// that saw another surface are left out, pixels with none left start from zero samples.
+
+struct Camera {
+    eye: vec3<f32>,
+    projection: u32,
+    horizontal: vec3<f32>,
+    blades: u32,
+    vertical: vec3<f32>,
+    u: vec3<f32>,
+    v: vec3<f32>,
+    lensRadius: f32,
+    lowerLeftCorner: vec3<f32>,
+    w: vec3<f32>,
+    shutter: f32,
+}
+
+struct ReprojectParams {
+    src_camera: Camera,
+    dst_camera: Camera,
+    src_width: u32,
+    src_height: u32,
+    dst_width: u32,
+    dst_height: u32,
+    // Samples a pixel keeps at most, so that the new ones soon wash out what was wrong.
+    max_samples: f32,
+}
+
+// Words of a pixel in the f32 format of `accumulate` in raytracing.wgsl: the average color,
+// the average squared luminance, the depth and the sample count.
+const DEPTH = 4u;
+const SAMPLES = 5u;
+const WORDS = 6u;
+
+const PROJECTION_ORTHOGRAPHIC = 1u;
+
+// Relative depth difference past which a source pixel saw another surface.
+const DEPTH_TOLERANCE = 0.05;
+
+@group(0) @binding(0) var<uniform> params: ReprojectParams;
+@group(0) @binding(1) var<storage, read> src: array<array<f32, WORDS>>;
+@group(0) @binding(2) var<storage, read_write> dst: array<array<f32, WORDS>>;
+
+fn src_pixel(p: vec2<i32>) -> array<f32, WORDS> {
+    let size = vec2<i32>(i32(params.src_width), i32(params.src_height));
+    let q = clamp(p, vec2(0), size - 1);
+    return src[u32(q.y) * params.src_width + u32(q.x)];
+}
+
+fn src_depth(uv: vec2<f32>) -> f32 {
+    let size = vec2(f32(params.src_width), f32(params.src_height));
+    return src_pixel(vec2<i32>(floor(uv * size)))[DEPTH];
+}
+
+// Point seen at `uv` at view depth `depth`, w is 0 for the background seen at a depth of 0.
+fn camera_point(camera: Camera, uv: vec2<f32>, depth: f32) -> vec4<f32> {
+    let viewport = camera.lowerLeftCorner + uv.x * camera.horizontal + uv.y * camera.vertical;
+    if camera.projection == PROJECTION_ORTHOGRAPHIC {
+        if depth <= 0.0 {
+            return vec4(camera.w, 0.0);
+        }
+        return vec4(viewport + depth * camera.w, 1.0);
+    }
+    let direction = viewport - camera.eye;
+    if depth <= 0.0 {
+        return vec4(direction, 0.0);
+    }
+    return vec4(camera.eye + direction * depth / dot(direction, camera.w), 1.0);
+}
+
+// Viewport coordinates of `point` and its view depth, a negative depth when `camera` can't
+// see it.
+fn camera_project(camera: Camera, point: vec4<f32>) -> vec3<f32> {
+    let hv = vec2(dot(camera.horizontal, camera.horizontal), dot(camera.vertical, camera.vertical));
+    if camera.projection == PROJECTION_ORTHOGRAPHIC {
+        if point.w == 0.0 {
+            return vec3(0.0, 0.0, -1.0);
+        }
+        let offset = point.xyz - camera.lowerLeftCorner;
+        let uv = vec2(dot(offset, camera.horizontal), dot(offset, camera.vertical)) / hv;
+        return vec3(uv, dot(point.xyz - camera.eye, camera.w));
+    }
+    let direction = select(point.xyz - camera.eye, point.xyz, point.w == 0.0);
+    let depth = dot(direction, camera.w);
+    if depth <= 0.0 {
+        return vec3(0.0, 0.0, -1.0);
+    }
+    let focus = dot(camera.lowerLeftCorner - camera.eye, camera.w);
+    let offset = camera.eye + direction * focus / depth - camera.lowerLeftCorner;
+    let uv = vec2(dot(offset, camera.horizontal), dot(offset, camera.vertical)) / hv;
+    return vec3(uv, select(depth, 0.0, point.w == 0.0));
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.dst_width || id.y >= params.dst_height {
+        return;
+    }
+    let uv = (vec2<f32>(id.xy) + 0.5) / vec2(f32(params.dst_width), f32(params.dst_height));
+
+    // Start from the depth the source saw at the same spot, then from the depth of the surface
+    // the source saw where that lands, which converges for small moves.
+    var point = camera_point(params.dst_camera, uv, src_depth(uv));
+    var src_uv = camera_project(params.src_camera, point);
+    for (var i = 0; i < 2 && src_uv.z >= 0.0; i += 1) {
+        let seen = camera_point(params.src_camera, src_uv.xy, src_depth(src_uv.xy));
+        let depth = camera_project(params.dst_camera, seen).z;
+        if depth < 0.0 {
+            break;
+        }
+        point = camera_point(params.dst_camera, uv, depth);
+        src_uv = camera_project(params.src_camera, point);
+    }
+
+    var pixel = array<f32, WORDS>(0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+    let inside = all(src_uv.xy >= vec2(0.0)) && all(src_uv.xy <= vec2(1.0));
+    if src_uv.z < 0.0 || !inside {
+        dst[id.y * params.dst_width + id.x] = pixel;
+        return;
+    }
+
+    // Bilinear taps, only those that saw the same surface.
+    let size = vec2(f32(params.src_width), f32(params.src_height));
+    let texel = src_uv.xy * size - 0.5;
+    let p0 = vec2<i32>(floor(texel));
+    let f = texel - floor(texel);
+    var weight_sum = 0.0;
+    for (var j = 0; j < 4; j += 1) {
+        let offset = vec2(j & 1, j >> 1);
+        var tap = src_pixel(p0 + offset);
+        let same_surface = select(
+            abs(tap[DEPTH] - src_uv.z) <= DEPTH_TOLERANCE * src_uv.z,
+            tap[DEPTH] <= 0.0,
+            src_uv.z == 0.0,
+        );
+        let weight = select(1.0 - f, f, offset == vec2(1));
+        let w = select(0.0, weight.x * weight.y, same_surface && tap[SAMPLES] > 0.0);
+        for (var k = 0u; k < WORDS; k += 1u) {
+            pixel[k] += w * tap[k];
+        }
+        weight_sum += w;
+    }
+    if weight_sum <= 0.0 {
+        dst[id.y * params.dst_width + id.x] = pixel;
+        return;
+    }
+
+    for (var k = 0u; k < WORDS; k += 1u) {
+        pixel[k] /= weight_sum;
+    }
+    pixel[DEPTH] = camera_project(params.dst_camera, point).z;
+    pixel[SAMPLES] = min(pixel[SAMPLES], params.max_samples);
+    dst[id.y * params.dst_width + id.x] = pixel;
+}
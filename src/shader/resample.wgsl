@@ -0,0 +1,44 @@
+// Bilinear copy of the accumulation buffer into a buffer of another resolution, used on resize.
+// Both buffers hold per pixel averages of the color and squared luminance, a depth and a sample
+// count, every word is interpolated on its own.
+
+struct ResampleParams {
+    src_width: u32,
+    src_height: u32,
+    dst_width: u32,
+    dst_height: u32,
+}
+
+// Words per pixel of the f32 format of `accumulate` in raytracing.wgsl.
+const WORDS = 6u;
+
+@group(0) @binding(0) var<uniform> params: ResampleParams;
+@group(0) @binding(1) var<storage, read> src: array<f32>;
+@group(0) @binding(2) var<storage, read_write> dst: array<f32>;
+
+fn src_word(x: u32, y: u32, k: u32) -> f32 {
+    return src[(y * params.src_width + x) * WORDS + k];
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.dst_width || id.y >= params.dst_height {
+        return;
+    }
+
+    let src_size = vec2<f32>(f32(params.src_width), f32(params.src_height));
+    let dst_size = vec2<f32>(f32(params.dst_width), f32(params.dst_height));
+
+    // Match pixel centers, then clamp to the edge pixels.
+    let p = clamp((vec2<f32>(id.xy) + 0.5) * src_size / dst_size - 0.5, vec2(0.0), src_size - 1.0);
+    let p0 = vec2<u32>(floor(p));
+    let p1 = min(p0 + 1u, vec2(params.src_width - 1u, params.src_height - 1u));
+    let f = fract(p);
+
+    let i = id.y * params.dst_width + id.x;
+    for (var k = 0u; k < WORDS; k += 1u) {
+        let top = mix(src_word(p0.x, p0.y, k), src_word(p1.x, p0.y, k), f.x);
+        let bottom = mix(src_word(p0.x, p1.y, k), src_word(p1.x, p1.y, k), f.x);
+        dst[i * WORDS + k] = mix(top, bottom, f.y);
+    }
+}
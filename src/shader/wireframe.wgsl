@@ -0,0 +1,68 @@
+// Edges of every triangle of the scene drawn over the render, with the camera the raytracer
+// uses. The triangles are read from the surfaces buffer, 3 vertices per triangle.
+
+struct Camera {
+    eye: vec3<f32>,
+    projection: u32,
+    horizontal: vec3<f32>,
+    blades: u32,
+    vertical: vec3<f32>,
+    u: vec3<f32>,
+    v: vec3<f32>,
+    lensRadius: f32,
+    lowerLeftCorner: vec3<f32>,
+    w: vec3<f32>,
+    shutter: f32,
+}
+
+struct Surface {
+    vertices: array<vec4<f32>, 3>,
+    normals: array<vec4<f32>, 3>,
+    tangents: array<vec4<f32>, 3>,
+    uvs: array<vec2<f32>, 3>,
+    material_index: u32,
+    padding: u32,
+};
+
+const PROJECTION_ORTHOGRAPHIC = 1u;
+
+// View depths lines are clipped to.
+const NEAR = 0.001;
+const FAR = 10000.0;
+
+const WIRE_COLOR = vec4(0.1, 1.0, 0.3, 1.0);
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(0) @binding(1) var<storage, read> surfaces: array<Surface>;
+
+// Clip position of `p`, the viewport of the camera covering the whole target. The
+// perspective one is left homogeneous so that lines crossing the near plane get clipped
+// there instead of wrapping around.
+fn project(p: vec3<f32>) -> vec4<f32> {
+    let h = camera.horizontal;
+    let v = camera.vertical;
+    let hv = vec2(dot(h, h), dot(v, v));
+    if camera.projection == PROJECTION_ORTHOGRAPHIC {
+        let offset = p - camera.lowerLeftCorner;
+        let uv = vec2(dot(offset, h), dot(offset, v)) / hv;
+        return vec4(2.0 * uv - 1.0, dot(offset, camera.w) / FAR, 1.0);
+    }
+    // uv * depth, see `camera_project` in reproject.wgsl.
+    let direction = p - camera.eye;
+    let depth = dot(direction, camera.w);
+    let focus = dot(camera.lowerLeftCorner - camera.eye, camera.w);
+    let corner = camera.eye - camera.lowerLeftCorner;
+    let uv = (depth * vec2(dot(corner, h), dot(corner, v))
+        + focus * vec2(dot(direction, h), dot(direction, v))) / hv;
+    return vec4(2.0 * uv - depth, (depth - NEAR) * FAR / (FAR - NEAR), depth);
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    return project(surfaces[index / 3u].vertices[index % 3u].xyz);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return WIRE_COLOR;
+}
@@ -0,0 +1,315 @@
+use wgpu::util::DeviceExt;
+
+use super::Vertex;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct BloomParams {
+    source_scale: [f32; 2],
+    threshold: f32,
+    _padding: f32,
+}
+
+/// Levels of the chain, the first one is half the image size.
+const MAX_LEVELS: u32 = 6;
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Fragment passes blurring the bright parts of the image into a glow, see `bloom.wgsl`.
+pub struct Bloom {
+    prefilter_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    upsample_pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    /// Parameters of the prefilter, rewritten every frame.
+    params_buffer: wgpu::Buffer,
+    /// Parameters of the passes between levels, which use the whole source.
+    level_params_buffer: wgpu::Buffer,
+    /// One view per mip level, the first one holds the result.
+    views: Vec<wgpu::TextureView>,
+    prefilter_bind_group: wgpu::BindGroup,
+    /// Bind group `i` reads level `i`.
+    level_bind_groups: Vec<wgpu::BindGroup>,
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    params: &wgpu::Buffer,
+    source: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(source),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+        label: Some("bloom bind group"),
+    })
+}
+
+impl Bloom {
+    pub fn new(
+        device: &wgpu::Device,
+        source: &wgpu::TextureView,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../shader/bloom.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("bloom layout"),
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Bloom Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |entry_point, blend| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[Vertex::desc()],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: FORMAT,
+                        blend: Some(blend),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+        let add = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("bloom sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("bloom params"),
+            size: std::mem::size_of::<BloomParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let level_params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("bloom level params"),
+            contents: bytemuck::bytes_of(&BloomParams {
+                source_scale: [1.0; 2],
+                threshold: 0.0,
+                _padding: 0.0,
+            }),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let mut bloom = Self {
+            prefilter_pipeline: pipeline("fs_prefilter", wgpu::BlendState::REPLACE),
+            downsample_pipeline: pipeline("fs_downsample", wgpu::BlendState::REPLACE),
+            upsample_pipeline: pipeline(
+                "fs_upsample",
+                wgpu::BlendState {
+                    color: add,
+                    alpha: add,
+                },
+            ),
+            prefilter_bind_group: create_bind_group(
+                device,
+                &bind_group_layout,
+                &params_buffer,
+                source,
+                &sampler,
+            ),
+            bind_group_layout,
+            sampler,
+            params_buffer,
+            level_params_buffer,
+            views: Vec::new(),
+            level_bind_groups: Vec::new(),
+        };
+        bloom.resize(device, source, size);
+        bloom
+    }
+
+    /// Recreates the levels for an image of `size`, read from `source`.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        source: &wgpu::TextureView,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) {
+        let width = (size.width / 2).max(1);
+        let height = (size.height / 2).max(1);
+        // Stop before a level gets thinner than a pixel.
+        let levels = MAX_LEVELS.min(width.min(height).ilog2() + 1);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("bloom texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: levels,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        self.views = (0..levels)
+            .map(|level| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("bloom level"),
+                    base_mip_level: level,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        self.level_bind_groups = self
+            .views
+            .iter()
+            .map(|view| {
+                create_bind_group(
+                    device,
+                    &self.bind_group_layout,
+                    &self.level_params_buffer,
+                    view,
+                    &self.sampler,
+                )
+            })
+            .collect();
+        self.prefilter_bind_group = create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.params_buffer,
+            source,
+            &self.sampler,
+        );
+    }
+
+    /// The glow, at half the image size.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.views[0]
+    }
+
+    /// Records the passes filling [`Bloom::view`] from the pixels of the source brighter than
+    /// `threshold`. Only the `source_scale` part of the source is read.
+    pub fn render(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        vertex_buffer: &wgpu::Buffer,
+        threshold: f32,
+        source_scale: [f32; 2],
+    ) {
+        let params = BloomParams {
+            source_scale,
+            threshold,
+            _padding: 0.0,
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+
+        let mut pass = |pipeline, bind_group, target, load| {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Bloom Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.draw(0..3, 0..1);
+        };
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+
+        pass(
+            &self.prefilter_pipeline,
+            &self.prefilter_bind_group,
+            &self.views[0],
+            clear,
+        );
+        for level in 1..self.views.len() {
+            pass(
+                &self.downsample_pipeline,
+                &self.level_bind_groups[level - 1],
+                &self.views[level],
+                clear,
+            );
+        }
+        for level in (1..self.views.len()).rev() {
+            pass(
+                &self.upsample_pipeline,
+                &self.level_bind_groups[level],
+                &self.views[level - 1],
+                wgpu::LoadOp::Load,
+            );
+        }
+    }
+}
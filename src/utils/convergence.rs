@@ -0,0 +1,128 @@
+use super::{BufferReadback, StorageBuffer};
+use crate::scene::{AccumulationFormat, RenderParam};
+
+/// Frames between two readbacks of the accumulated image.
+const CHECK_INTERVAL: u32 = 16;
+/// Checks in a row under the threshold before the image counts as converged.
+const STABLE_CHECKS: u32 = 4;
+/// Only one pixel out of `STRIDE` in each direction is compared.
+const STRIDE: usize = 4;
+
+/// A copy of the image buffer being read back.
+struct Pending {
+    samples: u32,
+    width: u32,
+    format: AccumulationFormat,
+    readback: BufferReadback<u32>,
+}
+
+/// Watches the accumulated image by reading it back every few frames, and tells when the
+/// estimate stopped changing between two readbacks.
+pub struct ConvergenceMonitor {
+    pending: Option<Pending>,
+    /// Samples per pixel and downsampled estimate of the last readback.
+    previous: Option<(u32, Vec<f32>)>,
+    stable_checks: u32,
+    frames: u32,
+}
+
+impl ConvergenceMonitor {
+    pub fn new() -> Self {
+        Self {
+            pending: None,
+            previous: None,
+            stable_checks: 0,
+            frames: 0,
+        }
+    }
+
+    /// Forgets the previous estimate, the next checks start from scratch.
+    pub fn reset(&mut self) {
+        self.previous = None;
+        self.stable_checks = 0;
+    }
+
+    /// Call once per frame after submitting it. `image` is the accumulation buffer of a
+    /// `width` pixels wide frame rendered with `render_param`. Returns true once the mean
+    /// relative change between estimates stayed under `convergence_epsilon` for a few checks.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        (image, width): (&StorageBuffer, u32),
+        render_param: &RenderParam,
+    ) -> bool {
+        let total_samples = render_param.total_samples;
+        device.poll(wgpu::Maintain::Poll);
+
+        if let Some(pending) = &self.pending {
+            let Some(result) = pending.readback.try_take() else {
+                return false;
+            };
+            let pending = self.pending.take().unwrap();
+            match result {
+                Ok(words) => {
+                    let means = pending.format.decode(&words);
+                    let estimate = downsample(&means, pending.width);
+                    if self.compare(pending.samples, estimate, render_param.convergence_epsilon) {
+                        return true;
+                    }
+                }
+                Err(e) => log::warn!("Image readback failed: {e}"),
+            }
+        }
+
+        self.frames += 1;
+        if !self.frames.is_multiple_of(CHECK_INTERVAL) || total_samples == 0 {
+            return false;
+        }
+
+        self.pending = Some(Pending {
+            samples: total_samples,
+            width,
+            format: render_param.accumulation_format.into(),
+            readback: BufferReadback::new(device, queue, image.handle()),
+        });
+        false
+    }
+
+    fn compare(&mut self, samples: u32, estimate: Vec<f32>, epsilon: f32) -> bool {
+        match &self.previous {
+            // The accumulation restarted or the frame was resized since.
+            Some((previous_samples, previous))
+                if *previous_samples > samples || previous.len() != estimate.len() =>
+            {
+                self.stable_checks = 0;
+            }
+            // Sampling stopped at the max, there is nothing new to compare.
+            Some((previous_samples, _)) if *previous_samples == samples => return false,
+            Some((_, previous)) => {
+                let change = estimate
+                    .iter()
+                    .zip(previous)
+                    .map(|(a, b)| (a - b).abs() / (0.5 * (a + b)).max(1e-2))
+                    .sum::<f32>()
+                    / estimate.len().max(1) as f32;
+                match change < epsilon {
+                    true => self.stable_checks += 1,
+                    false => self.stable_checks = 0,
+                }
+            }
+            None => {}
+        }
+        self.previous = Some((samples, estimate));
+        self.stable_checks >= STABLE_CHECKS
+    }
+}
+
+/// Every `STRIDE`th pixel of every `STRIDE`th row.
+fn downsample(means: &[[f32; 3]], width: u32) -> Vec<f32> {
+    let width = width.max(1) as usize;
+    means
+        .chunks(width)
+        .step_by(STRIDE)
+        .flat_map(|row| row.iter().step_by(STRIDE))
+        .flatten()
+        .copied()
+        .collect()
+}
@@ -0,0 +1,57 @@
+use std::{
+    ffi::OsString,
+    path::Path,
+    sync::mpsc::{self, Receiver},
+};
+
+use notify::{RecursiveMode, Watcher};
+
+/// Tells when a file changed on disk.
+pub struct FileWatcher {
+    file_name: OsString,
+    events: Receiver<notify::Result<notify::Event>>,
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl FileWatcher {
+    /// Watches the directory of `path` rather than the file itself, editors often save by
+    /// replacing the file.
+    pub fn new(path: &Path) -> notify::Result<Self> {
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| notify::Error::generic("not a file path"))?
+            .to_owned();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+        Ok(Self {
+            file_name,
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    /// Whether the file was written or replaced since the last call, never blocks.
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        for event in self.events.try_iter() {
+            match event {
+                Ok(event) => {
+                    changed |= (event.kind.is_modify() || event.kind.is_create())
+                        && event
+                            .paths
+                            .iter()
+                            .any(|p| p.file_name() == Some(self.file_name.as_os_str()));
+                }
+                Err(e) => log::warn!("File watcher error: {e}"),
+            }
+        }
+        changed
+    }
+}
@@ -0,0 +1,37 @@
+use std::collections::VecDeque;
+
+/// Frames kept for the frame time graph.
+const CAPACITY: usize = 120;
+
+/// Durations of the last frames in milliseconds, oldest first.
+pub struct FrameTimes {
+    times: VecDeque<f32>,
+}
+
+impl FrameTimes {
+    pub fn new() -> Self {
+        Self {
+            times: VecDeque::with_capacity(CAPACITY),
+        }
+    }
+
+    /// Drops the oldest frame once full.
+    pub fn push(&mut self, dt: std::time::Duration) {
+        if self.times.len() == CAPACITY {
+            self.times.pop_front();
+        }
+        self.times.push_back(dt.as_secs_f32() * 1000.0);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = f32> + '_ {
+        self.times.iter().copied()
+    }
+
+    /// Min, average and max, `None` before the first frame.
+    pub fn stats(&self) -> Option<(f32, f32, f32)> {
+        let min = self.iter().reduce(f32::min)?;
+        let max = self.iter().reduce(f32::max)?;
+        let avg = self.iter().sum::<f32>() / self.times.len() as f32;
+        Some((min, avg, max))
+    }
+}
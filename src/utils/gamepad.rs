@@ -0,0 +1,75 @@
+use gilrs::{Axis, Button, GamepadId, Gilrs};
+
+use crate::scene::GamepadInput;
+
+/// Sticks report a little tilt at rest, anything closer to the center than this is zero.
+const DEADZONE: f32 = 0.15;
+
+/// Reads the gamepad that was used last.
+pub struct Gamepads {
+    gilrs: Gilrs,
+    active: Option<GamepadId>,
+}
+
+impl Gamepads {
+    /// `None` when the platform has no gamepad support, the reason is logged.
+    pub fn new() -> Option<Self> {
+        match Gilrs::new() {
+            Ok(gilrs) => Some(Self {
+                gilrs,
+                active: None,
+            }),
+            Err(e) => {
+                log::warn!("Gamepads are not available: {e}");
+                None
+            }
+        }
+    }
+
+    /// Drains the pending events and returns the current state, zero when no gamepad is
+    /// connected.
+    pub fn poll(&mut self) -> GamepadInput {
+        while let Some(event) = self.gilrs.next_event() {
+            self.active = Some(event.id);
+        }
+        let Some(gamepad) = self
+            .active
+            .and_then(|id| self.gilrs.connected_gamepad(id))
+            .or_else(|| self.gilrs.gamepads().next().map(|(_, gamepad)| gamepad))
+        else {
+            return GamepadInput::default();
+        };
+
+        let (right, forward) = deadzone(
+            gamepad.value(Axis::LeftStickX),
+            gamepad.value(Axis::LeftStickY),
+        );
+        let (look_horizontal, look_vertical) = deadzone(
+            gamepad.value(Axis::RightStickX),
+            gamepad.value(Axis::RightStickY),
+        );
+        let trigger = |button| gamepad.button_data(button).map_or(0.0, |data| data.value());
+        let held = |button| gamepad.is_pressed(button) as u32 as f32;
+
+        GamepadInput {
+            forward,
+            right,
+            up: trigger(Button::RightTrigger2) - trigger(Button::LeftTrigger2),
+            look_horizontal,
+            // Stick up is positive, mouse up is negative.
+            look_vertical: -look_vertical,
+            zoom: held(Button::RightTrigger) - held(Button::LeftTrigger),
+        }
+    }
+}
+
+/// Radial deadzone, the remaining range is stretched back to `0..=1` so there is no jump at
+/// its edge.
+fn deadzone(x: f32, y: f32) -> (f32, f32) {
+    let length = x.hypot(y);
+    if length <= DEADZONE {
+        return (0.0, 0.0);
+    }
+    let scale = ((length - DEADZONE) / (1.0 - DEADZONE)).min(1.0) / length;
+    (x * scale, y * scale)
+}
@@ -0,0 +1,167 @@
+use wgpu::util::DeviceExt;
+
+use super::UniformBuffer;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+impl LineVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+}
+
+/// Grid lines on each side of the origin, along both axes.
+const GRID_HALF_LINES: i32 = 20;
+const GRID_COLOR: [f32; 3] = [0.35, 0.35, 0.35];
+
+/// Draws a grid on the y = 0 plane and the world axes in red, green and blue for x, y and z
+/// over the render, see `gizmo.wgsl`. Nothing is hidden behind the scene.
+pub struct Gizmo {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl Gizmo {
+    pub fn new(device: &wgpu::Device, surface_format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../shader/gizmo.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("gizmo layout"),
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Gizmo Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Gizmo pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &LineVertex::ATTRIBS,
+                }],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+        }
+    }
+
+    /// Endpoints of the grid lines, then of the axes.
+    fn lines(grid_spacing: Option<f32>, axis_length: Option<f32>) -> Vec<LineVertex> {
+        let mut vertices = Vec::new();
+        let mut line = |from: [f32; 3], to: [f32; 3], color: [f32; 3]| {
+            vertices.push(LineVertex {
+                position: from,
+                color,
+            });
+            vertices.push(LineVertex {
+                position: to,
+                color,
+            });
+        };
+        if let Some(spacing) = grid_spacing {
+            let extent = GRID_HALF_LINES as f32 * spacing;
+            for i in -GRID_HALF_LINES..=GRID_HALF_LINES {
+                let offset = i as f32 * spacing;
+                line([offset, 0.0, -extent], [offset, 0.0, extent], GRID_COLOR);
+                line([-extent, 0.0, offset], [extent, 0.0, offset], GRID_COLOR);
+            }
+        }
+        if let Some(length) = axis_length {
+            line([0.0; 3], [length, 0.0, 0.0], [1.0, 0.0, 0.0]);
+            line([0.0; 3], [0.0, length, 0.0], [0.0, 1.0, 0.0]);
+            line([0.0; 3], [0.0, 0.0, length], [0.0, 0.0, 1.0]);
+        }
+        vertices
+    }
+
+    /// Draws the grid with lines `grid_spacing` apart and the axes `axis_length` long, either
+    /// of them skipped when None, over `view` seen from the camera in `camera`.
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        camera: &UniformBuffer,
+        grid_spacing: Option<f32>,
+        axis_length: Option<f32>,
+    ) {
+        let vertices = Self::lines(grid_spacing, axis_length);
+        if vertices.is_empty() {
+            return;
+        }
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("gizmo vertices"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera.handle().as_entire_binding(),
+            }],
+            label: Some("gizmo bind group"),
+        });
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Gizmo Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        pass.draw(0..vertices.len() as u32, 0..1);
+    }
+}
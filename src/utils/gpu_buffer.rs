@@ -91,7 +91,9 @@ impl StorageBuffer {
     ) -> Self {
         let handle = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             contents: bytes,
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
             label,
         });
 
@@ -102,7 +104,6 @@ impl StorageBuffer {
         }
     }
 
-    #[allow(dead_code)]
     pub fn handle(&self) -> &wgpu::Buffer {
         &self.handle
     }
@@ -0,0 +1,77 @@
+use std::io::{self, Write};
+
+/// Writes linear `pixels`, given row by row from the top, as an uncompressed Radiance RGBE
+/// `.hdr` image.
+pub fn write_hdr(
+    mut writer: impl Write,
+    (width, height): (u32, u32),
+    pixels: &[[f32; 3]],
+) -> io::Result<()> {
+    if pixels.len() != (width * height) as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("expected {width}x{height} pixels, got {}", pixels.len()),
+        ));
+    }
+
+    write!(
+        writer,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {height} +X {width}\n"
+    )?;
+    let data: Vec<u8> = pixels.iter().flat_map(|&rgb| to_rgbe(rgb)).collect();
+    writer.write_all(&data)
+}
+
+/// Shared exponent encoding, the largest component keeps 8 bits of mantissa.
+fn to_rgbe(rgb: [f32; 3]) -> [u8; 4] {
+    let rgb = rgb.map(|c| c.max(0.0));
+    let max = rgb[0].max(rgb[1]).max(rgb[2]);
+    if !max.is_finite() || max < 1e-32 {
+        return [0; 4];
+    }
+    // max = mantissa * 2^exponent with mantissa in [0.5, 1).
+    let exponent = max.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f32.powi(exponent);
+    let [r, g, b] = rgb.map(|c| (c * scale).min(255.0) as u8);
+    [r, g, b, (exponent + 128).clamp(0, 255) as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn written_pixels_decode_within_a_mantissa_step() {
+        let pixels = [
+            [0.0, 0.0, 0.0],
+            [1e-33, 5e-33, 0.0],
+            [0.5, 1.0, 2.0],
+            [100.0, 3.5, 0.01],
+        ];
+        let mut file = Vec::new();
+        write_hdr(&mut file, (2, 2), &pixels).unwrap();
+
+        let header_end = file.windows(2).position(|w| w == b"\n\n").unwrap() + 2;
+        let header = std::str::from_utf8(&file[..header_end]).unwrap();
+        assert!(header.starts_with("#?RADIANCE\n"));
+        assert!(header.contains("FORMAT=32-bit_rle_rgbe\n"));
+        let resolution_end =
+            header_end + file[header_end..].iter().position(|&b| b == b'\n').unwrap();
+        assert_eq!(&file[header_end..resolution_end], b"-Y 2 +X 2");
+
+        let data = &file[resolution_end + 1..];
+        assert_eq!(data.len(), pixels.len() * 4);
+        for (rgbe, expected) in data.chunks_exact(4).zip(pixels) {
+            if rgbe[3] == 0 {
+                // Everything below 1e-32 is stored as black.
+                assert!(expected.iter().all(|&c| c < 1e-32), "{expected:?}");
+                continue;
+            }
+            let step = 2f32.powi(rgbe[3] as i32 - 128) / 256.0;
+            for (m, c) in rgbe[..3].iter().zip(expected) {
+                let decoded = (*m as f32 + 0.5) * step;
+                assert!((decoded - c).abs() <= step, "{decoded} for {c}");
+            }
+        }
+    }
+}
@@ -1,8 +1,43 @@
+mod bloom;
 pub mod bvh;
+mod convergence;
 mod egui_tools;
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+mod file_watcher;
+mod frame_times;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod gizmo;
 mod gpu_buffer;
+#[cfg(not(target_arch = "wasm32"))]
+mod hdr;
+#[cfg(not(target_arch = "wasm32"))]
+mod png;
+mod post_process;
+pub mod ray;
+mod readback;
+mod reproject;
+mod resample;
+mod wireframe;
+pub use bloom::Bloom;
+pub use convergence::ConvergenceMonitor;
 pub use egui_tools::EguiRenderer;
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+pub use file_watcher::FileWatcher;
+pub use frame_times::FrameTimes;
+#[cfg(feature = "gamepad")]
+pub use gamepad::Gamepads;
+pub use gizmo::Gizmo;
 pub use gpu_buffer::{StorageBuffer, UniformBuffer};
+#[cfg(not(target_arch = "wasm32"))]
+pub use hdr::write_hdr;
+#[cfg(not(target_arch = "wasm32"))]
+pub use png::write_png;
+pub use post_process::{PostProcess, RADIANCE_FORMAT};
+pub use readback::BufferReadback;
+pub use reproject::ImageReprojector;
+pub use resample::ImageResampler;
+pub use wireframe::Wireframe;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -0,0 +1,293 @@
+use crate::scene::RenderParam;
+
+use super::{Bloom, Vertex};
+
+/// What the raytracer renders into, linear and unclamped.
+pub const RADIANCE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Only what is brighter than this glows.
+const BLOOM_THRESHOLD: f32 = 1.0;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuPostParam {
+    radiance_scale: [f32; 2],
+    bloom_intensity: f32,
+    vignette: f32,
+    white_balance: [f32; 3],
+    contrast: f32,
+    saturation: f32,
+    _padding: [f32; 3],
+}
+
+/// RGB of a black body at `kelvin`, from Tanner Helland's fit of the CIE data, relative to
+/// the 6500 K white so that it leaves the image unchanged.
+fn white_balance(kelvin: f32) -> [f32; 3] {
+    let rgb = |kelvin: f32| {
+        let t = kelvin / 100.0;
+        let red = match t <= 66.0 {
+            true => 255.0,
+            false => 329.69873 * (t - 60.0).powf(-0.13320476),
+        };
+        let green = match t <= 66.0 {
+            true => 99.4708 * t.ln() - 161.11957,
+            false => 288.12216 * (t - 60.0).powf(-0.07551485),
+        };
+        let blue = match t {
+            t if t >= 66.0 => 255.0,
+            t if t <= 19.0 => 0.0,
+            t => 138.51773 * (t - 10.0).ln() - 305.0448,
+        };
+        [red, green, blue].map(|c: f32| c.clamp(0.0, 255.0))
+    };
+    let white = rgb(6500.0);
+    let color = rgb(kelvin);
+    std::array::from_fn(|i| color[i] / white[i])
+}
+
+/// Owns the radiance texture the raytracer renders into, and shows it on the surface with
+/// the post effects, see `post.wgsl`. None of them changes the accumulated samples.
+pub struct PostProcess {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    sampler: wgpu::Sampler,
+    params_buffer: wgpu::Buffer,
+    radiance: wgpu::TextureView,
+    bloom: Bloom,
+}
+
+fn create_radiance(
+    device: &wgpu::Device,
+    size: winit::dpi::PhysicalSize<u32>,
+) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some("radiance texture"),
+            size: wgpu::Extent3d {
+                width: size.width.max(1),
+                height: size.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: RADIANCE_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+impl PostProcess {
+    pub fn new(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../shader/post.wgsl"));
+
+        let texture = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture(1),
+                texture(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("post layout"),
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Post pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: match surface_format.is_srgb() {
+                    true => Some("fs_main_srgb"),
+                    // for webgpu
+                    false => Some("fs_main_rgb"),
+                },
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("post sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("post params"),
+            size: std::mem::size_of::<GpuPostParam>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let radiance = create_radiance(device, size);
+        let bloom = Bloom::new(device, &radiance, size);
+        let bind_group = create_bind_group(
+            device,
+            &bind_group_layout,
+            &params_buffer,
+            (&radiance, bloom.view()),
+            &sampler,
+        );
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            sampler,
+            params_buffer,
+            radiance,
+            bloom,
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, size: winit::dpi::PhysicalSize<u32>) {
+        self.radiance = create_radiance(device, size);
+        self.bloom.resize(device, &self.radiance, size);
+        self.bind_group = create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.params_buffer,
+            (&self.radiance, self.bloom.view()),
+            &self.sampler,
+        );
+    }
+
+    /// Render target of the raytracing pass.
+    pub fn radiance_view(&self) -> &wgpu::TextureView {
+        &self.radiance
+    }
+
+    /// Records the passes showing the radiance on `target`, stretching the `radiance_scale`
+    /// part of it that was traced over the whole target.
+    pub fn render(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        vertex_buffer: &wgpu::Buffer,
+        target: &wgpu::TextureView,
+        render_param: &RenderParam,
+        radiance_scale: [f32; 2],
+    ) {
+        let params = GpuPostParam {
+            radiance_scale,
+            bloom_intensity: render_param.bloom_intensity,
+            vignette: render_param.vignette,
+            white_balance: white_balance(render_param.white_balance_k),
+            contrast: render_param.contrast,
+            saturation: render_param.saturation,
+            _padding: [0.0; 3],
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+
+        // The shader skips the glow too, its texture keeps whatever it last held.
+        if render_param.bloom_intensity > 0.0 {
+            self.bloom.render(
+                queue,
+                encoder,
+                vertex_buffer,
+                BLOOM_THRESHOLD,
+                radiance_scale,
+            );
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Post Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    params: &wgpu::Buffer,
+    (radiance, bloom): (&wgpu::TextureView, &wgpu::TextureView),
+    sampler: &wgpu::Sampler,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(radiance),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(bloom),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+        label: Some("post bind group"),
+    })
+}
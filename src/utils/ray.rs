@@ -0,0 +1,41 @@
+use glm::Vec3;
+
+use super::bvh::Aabb;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Ray { origin, direction }
+    }
+
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + t * self.direction
+    }
+}
+
+/// CPU side counterpart of the shader intersection routines, used for picking.
+pub trait Intersect {
+    /// Returns the ray parameter of the closest hit in `t_min..t_max`.
+    fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<f32>;
+}
+
+impl Aabb {
+    pub fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        let inv_d = Vec3::repeat(1.0).component_div(&ray.direction);
+        let t0 = (self.min - ray.origin).component_mul(&inv_d);
+        let t1 = (self.max - ray.origin).component_mul(&inv_d);
+
+        let t_small = glm::min2(&t0, &t1);
+        let t_big = glm::max2(&t0, &t1);
+
+        let tmin = t_min.max(t_small.max());
+        let tmax = t_max.min(t_big.min());
+
+        tmin <= tmax
+    }
+}
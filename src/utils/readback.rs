@@ -0,0 +1,89 @@
+use std::{
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+#[derive(Default)]
+struct MapState {
+    result: Option<Result<(), wgpu::BufferAsyncError>>,
+    waker: Option<Waker>,
+}
+
+/// Copy of a GPU buffer on its way back to the CPU.
+///
+/// Mapping only progresses when the device is polled: natively call `device.poll` before
+/// awaiting it or between calls to [`BufferReadback::try_take`], on the web the browser does it.
+pub struct BufferReadback<T> {
+    staging: wgpu::Buffer,
+    state: Arc<Mutex<MapState>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> BufferReadback<T> {
+    /// Submits a copy of the whole `buffer`, which needs `COPY_SRC` usage, and starts mapping it.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, buffer: &wgpu::Buffer) -> Self {
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback staging buffer"),
+            size: buffer.size(),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Readback Encoder"),
+        });
+        encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, buffer.size());
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let state = Arc::new(Mutex::new(MapState::default()));
+        let callback_state = state.clone();
+        staging
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let mut state = callback_state.lock().unwrap();
+                state.result = Some(result);
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+            });
+
+        Self {
+            staging,
+            state,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The buffer content if the mapping is done, without blocking.
+    pub fn try_take(&self) -> Option<Result<Vec<T>, wgpu::BufferAsyncError>> {
+        let result = self.state.lock().unwrap().result.take()?;
+        Some(result.map(|()| self.read()))
+    }
+
+    fn read(&self) -> Vec<T> {
+        let data = bytemuck::cast_slice(&self.staging.slice(..).get_mapped_range()).to_vec();
+        self.staging.unmap();
+        data
+    }
+}
+
+impl<T: bytemuck::Pod> Future for BufferReadback<T> {
+    type Output = Result<Vec<T>, wgpu::BufferAsyncError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => {
+                drop(state);
+                Poll::Ready(result.map(|()| self.read()))
+            }
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use wgpu::util::DeviceExt;
+
+use super::StorageBuffer;
+use crate::scene::GpuCamera;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ReprojectParams {
+    src_camera: GpuCamera,
+    dst_camera: GpuCamera,
+    src_width: u32,
+    src_height: u32,
+    dst_width: u32,
+    dst_height: u32,
+    max_samples: f32,
+    _padding: [u32; 3],
+}
+
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Compute pass moving an accumulation buffer into the view of another camera, so that the
+/// samples survive camera moves. Only for [`crate::scene::AccumulationFormat::F32`].
+pub struct ImageReprojector {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl ImageReprojector {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../shader/reproject.wgsl"));
+
+        let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, false),
+            ],
+            label: Some("reproject layout"),
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Reproject Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Reproject pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+        }
+    }
+
+    /// Reprojects `src`, holding `src_size` pixels seen from `src_camera`, into `dst` holding
+    /// `dst_size` pixels seen from `dst_camera`. No pixel of `dst` keeps more than
+    /// `max_samples`.
+    pub fn reproject(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        (src, src_size, src_camera): (&StorageBuffer, (u32, u32), GpuCamera),
+        (dst, dst_size, dst_camera): (&StorageBuffer, (u32, u32), GpuCamera),
+        max_samples: u32,
+    ) {
+        let params = ReprojectParams {
+            src_camera,
+            dst_camera,
+            src_width: src_size.0,
+            src_height: src_size.1,
+            dst_width: dst_size.0,
+            dst_height: dst_size.1,
+            max_samples: max_samples as f32,
+            _padding: [0; 3],
+        };
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("reproject params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: src.handle().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: dst.handle().as_entire_binding(),
+                },
+            ],
+            label: Some("reproject bind group"),
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Reproject Encoder"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Reproject Pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(
+                dst_size.0.div_ceil(WORKGROUP_SIZE),
+                dst_size.1.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+}